@@ -0,0 +1,75 @@
+//! C2A のフライトソフトウェア向けのソースコード生成
+
+pub mod c_cmd;
+pub mod c_tlm;
+
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use tlmcmddb::{Component, Database};
+
+/// 生成されたファイル
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GeneratedFile {
+    /// 出力先ディレクトリからの相対パス。component ごとのページなど、生成する内容から決まる場合がある
+    pub name: String,
+    pub content: String,
+}
+
+impl GeneratedFile {
+    pub fn new(name: impl Into<String>, content: String) -> Self {
+        Self {
+            name: name.into(),
+            content,
+        }
+    }
+}
+
+/// 生成されたファイルの先頭に置くコメント
+fn file_comment(brief: &str) -> String {
+    format!(
+        "/**\n * @file\n * @brief  {}\n * @note   このコードは自動生成されています！\n */\n",
+        brief
+    )
+}
+
+/// `name` が C の識別子として使えることを確かめる
+fn ensure_identifier(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let is_identifier = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    ensure!(is_identifier, "{:?} is not a valid C identifier", name);
+    Ok(())
+}
+
+/// 生成の対象とする component を選ぶ
+///
+/// `name` が指定されていない場合、`database` に含まれる component はただ1つでなければならない。
+pub fn select_component<'a>(database: &'a Database, name: Option<&str>) -> Result<&'a Component> {
+    match name {
+        Some(name) => database
+            .components
+            .iter()
+            .find(|component| component.name == name)
+            .ok_or_else(|| anyhow!("component {} is not found", name)),
+        None => match database.components.as_slice() {
+            [component] => Ok(component),
+            components => Err(anyhow!(
+                "--component must be specified because the database has {} components",
+                components.len()
+            )),
+        },
+    }
+}
+
+pub fn write_files(output_dir: &Path, files: &[GeneratedFile]) -> Result<()> {
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("creating output directory: {:?}", output_dir))?;
+    for file in files {
+        let path = output_dir.join(&file.name);
+        fs::write(&path, &file.content).with_context(|| format!("writing {:?}", path))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+//! `command_definitions.{c,h}` と `cmd_code.h` の生成
+//!
+//! コマンド名は `Cmd_` で始まっていてもいなくてもよく、`Cmd_CODE_*` と `Cmd_*` の名前に用いる。
+//! 地上局向けの属性である Danger Flag と Is Restricted は、`cmd_code.h` にコメントとして残す。
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use tlmcmddb::{cmd, Component};
+
+use super::{ensure_identifier, file_comment, GeneratedFile};
+
+pub fn generate(component: &Component) -> Result<Vec<GeneratedFile>> {
+    for entry in &component.cmd.entries {
+        if let cmd::Entry::Command(command) = entry {
+            ensure_identifier(&command.name)?;
+        }
+    }
+    let entries = &component.cmd.entries;
+    Ok(vec![
+        GeneratedFile::new("cmd_code.h", generate_cmd_code_h(entries)),
+        GeneratedFile::new("command_definitions.h", generate_definitions_h()),
+        GeneratedFile::new("command_definitions.c", generate_definitions_c(entries)),
+    ])
+}
+
+/// `Cmd_` を除いたコマンド名
+fn base_name(command: &cmd::Command) -> &str {
+    command.name.strip_prefix("Cmd_").unwrap_or(&command.name)
+}
+
+/// コメント行を C のコメントにする。行頭の `*` と空のセルは除き、何も残らなければ `None`
+fn section_comment(comment: &cmd::Comment) -> Option<String> {
+    let text = comment.text.trim_start_matches('*');
+    let cells: Vec<_> = text
+        .split(',')
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    (!cells.is_empty()).then(|| format!("// {}", cells.join(" ")))
+}
+
+fn generate_cmd_code_h(entries: &[cmd::Entry]) -> String {
+    let mut out = file_comment("コマンドコード定義");
+    out.push_str("#ifndef CMD_CODE_H_\n#define CMD_CODE_H_\n\n");
+    out.push_str("typedef enum\n{\n");
+    for entry in entries {
+        match entry {
+            cmd::Entry::Command(command) => {
+                let _ = write!(
+                    out,
+                    "  Cmd_CODE_{} = 0x{:04X},",
+                    base_name(command),
+                    command.code
+                );
+                let attributes: Vec<_> = [
+                    (command.is_danger, "danger"),
+                    (command.is_restricted, "restricted"),
+                ]
+                .into_iter()
+                .filter_map(|(flag, name)| flag.then_some(name))
+                .collect();
+                if !attributes.is_empty() {
+                    let _ = write!(out, " // {}", attributes.join(", "));
+                }
+                out.push('\n');
+            }
+            cmd::Entry::Comment(comment) => {
+                if let Some(comment) = section_comment(comment) {
+                    let _ = writeln!(out, "\n  {}", comment);
+                }
+            }
+        }
+    }
+    out.push_str("\n  Cmd_CODE_MAX\n} CMD_CODE;\n\n#endif\n");
+    out
+}
+
+fn generate_definitions_h() -> String {
+    let mut out = file_comment("コマンド定義");
+    out.push_str("#ifndef COMMAND_DEFINITIONS_H_\n#define COMMAND_DEFINITIONS_H_\n\n");
+    out.push_str("#include \"cmd_code.h\"\n\n");
+    out.push_str("void CA_load_cmd_table(CA_CmdInfo cmd_table[CA_MAX_CMDS]);\n\n");
+    out.push_str("#endif\n");
+    out
+}
+
+fn generate_definitions_c(entries: &[cmd::Entry]) -> String {
+    let mut out = String::from("#pragma section REPRO\n");
+    out.push_str(&file_comment("コマンド定義"));
+    out.push_str("#include <src_core/TlmCmd/command_analyze.h>\n");
+    out.push_str("#include \"command_definitions.h\"\n");
+    out.push_str("#include \"command_source.h\"\n\n");
+
+    out.push_str("void CA_load_cmd_table(CA_CmdInfo cmd_table[CA_MAX_CMDS])\n{\n");
+    for entry in entries {
+        match entry {
+            cmd::Entry::Command(command) => {
+                let _ = writeln!(
+                    out,
+                    "  cmd_table[Cmd_CODE_{name}].cmd_func = Cmd_{name};",
+                    name = base_name(command)
+                );
+            }
+            cmd::Entry::Comment(comment) => {
+                if let Some(comment) = section_comment(comment) {
+                    let _ = writeln!(out, "\n  {}", comment);
+                }
+            }
+        }
+    }
+
+    let commands_with_parameters = entries.iter().filter_map(|entry| match entry {
+        cmd::Entry::Command(command) if !command.parameters.is_empty() => Some(command),
+        _ => None,
+    });
+    for command in commands_with_parameters {
+        out.push('\n');
+        // 2つのパラメータのサイズを1オクテットに詰めて持つ
+        for (i, parameter) in command.parameters.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "  cmd_table[Cmd_CODE_{}].param_size_infos[{}].packed_info.bit.{} = {};",
+                base_name(command),
+                i / 2,
+                if i % 2 == 0 { "first" } else { "second" },
+                param_size_type(parameter.data_type)
+            );
+        }
+    }
+    out.push_str("}\n\n#pragma section\n");
+    out
+}
+
+fn param_size_type(data_type: cmd::DataType) -> &'static str {
+    match data_type {
+        cmd::DataType::Int8 | cmd::DataType::Uint8 => "CA_PARAM_SIZE_TYPE_1BYTE",
+        cmd::DataType::Int16 | cmd::DataType::Uint16 => "CA_PARAM_SIZE_TYPE_2BYTE",
+        cmd::DataType::Int32 | cmd::DataType::Uint32 | cmd::DataType::Float => {
+            "CA_PARAM_SIZE_TYPE_4BYTE"
+        }
+        cmd::DataType::Double => "CA_PARAM_SIZE_TYPE_8BYTE",
+        cmd::DataType::Raw => "CA_PARAM_SIZE_TYPE_RAW",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let json = include_bytes!("../../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        let component = Component {
+            name: "MOBC".to_string(),
+            tlm: tlmcmddb::tlm::Database {
+                telemetries: vec![],
+            },
+            cmd,
+            bct: Default::default(),
+        };
+        let files = generate(&component).unwrap();
+
+        let cmd_code_h = &files[0].content;
+        assert_eq!("cmd_code.h", files[0].name);
+        assert!(cmd_code_h.contains(
+            "  // C2A_CORE 基幹機能コマンド\n  Cmd_CODE_NOP = 0x0000,\n  Cmd_CODE_TMGR_SET_TIME = 0x0001,\n"
+        ));
+        assert!(cmd_code_h.contains("  Cmd_CODE_AM_CLEAR_APP_INFO = 0x000B, // danger\n"));
+
+        let definitions_c = &files[2].content;
+        assert_eq!("command_definitions.c", files[2].name);
+        assert!(definitions_c.contains("  cmd_table[Cmd_CODE_NOP].cmd_func = Cmd_NOP;\n"));
+        assert!(definitions_c.contains(
+            "  cmd_table[Cmd_CODE_TMGR_UPDATE_UNIXTIME].param_size_infos[0].packed_info.bit.first = CA_PARAM_SIZE_TYPE_8BYTE;\n  cmd_table[Cmd_CODE_TMGR_UPDATE_UNIXTIME].param_size_infos[0].packed_info.bit.second = CA_PARAM_SIZE_TYPE_4BYTE;\n  cmd_table[Cmd_CODE_TMGR_UPDATE_UNIXTIME].param_size_infos[1].packed_info.bit.first = CA_PARAM_SIZE_TYPE_4BYTE;\n"
+        ));
+    }
+}
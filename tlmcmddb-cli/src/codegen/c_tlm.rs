@@ -0,0 +1,270 @@
+//! `telemetry_definitions.{c,h}` と `tlm_code.h` の生成
+//!
+//! 無効（DISABLE）なテレメトリは生成しない。
+//! Variable or Function Name が空欄の FieldGroup（Space Packet のヘッダなど）は C2A core が詰めるため、値を詰めるコードを生成しない。
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use tlmcmddb::{tlm, Component};
+
+use super::{ensure_identifier, file_comment, GeneratedFile};
+
+pub fn generate(component: &Component) -> Result<Vec<GeneratedFile>> {
+    let mut telemetries: Vec<_> = component
+        .tlm
+        .telemetries
+        .iter()
+        .filter(|telemetry| telemetry.metadata.is_enabled)
+        .collect();
+    telemetries.sort_by_key(|telemetry| telemetry.metadata.packet_id);
+    for telemetry in &telemetries {
+        ensure_identifier(&telemetry.name)?;
+    }
+    Ok(vec![
+        GeneratedFile::new("tlm_code.h", generate_tlm_code_h(&telemetries)),
+        GeneratedFile::new("telemetry_definitions.h", generate_definitions_h()),
+        GeneratedFile::new(
+            "telemetry_definitions.c",
+            generate_definitions_c(&telemetries)?,
+        ),
+    ])
+}
+
+fn generate_tlm_code_h(telemetries: &[&tlm::Telemetry]) -> String {
+    let mut out = file_comment("テレメトリコード定義");
+    out.push_str("#ifndef TLM_CODE_H_\n#define TLM_CODE_H_\n\n");
+    out.push_str("typedef enum\n{\n");
+    for telemetry in telemetries {
+        let _ = writeln!(
+            out,
+            "  Tlm_CODE_{} = 0x{:02x},",
+            telemetry.name, telemetry.metadata.packet_id
+        );
+    }
+    out.push_str("\n  TLM_CODE_MAX\n} TLM_CODE;\n\n#endif\n");
+    out
+}
+
+fn generate_definitions_h() -> String {
+    let mut out = file_comment("テレメトリ定義");
+    out.push_str("#ifndef TELEMETRY_DEFINITIONS_H_\n#define TELEMETRY_DEFINITIONS_H_\n\n");
+    out.push_str("#include \"tlm_code.h\"\n\n");
+    out.push_str("void TF_load_tlm_table(TF_TlmInfo tlm_table[TF_MAX_TLMS]);\n\n");
+    out.push_str("#endif\n");
+    out
+}
+
+fn generate_definitions_c(telemetries: &[&tlm::Telemetry]) -> Result<String> {
+    let mut out = String::from("#pragma section REPRO\n");
+    out.push_str(&file_comment("テレメトリ定義"));
+    out.push_str("#include <src_core/TlmCmd/telemetry_frame.h>\n");
+    out.push_str("#include \"telemetry_definitions.h\"\n");
+    out.push_str("#include \"telemetry_source.h\"\n\n");
+
+    for telemetry in telemetries {
+        let _ = writeln!(
+            out,
+            "static TF_TLM_FUNC_ACK Tlm_{}_(uint8_t* packet, uint16_t* len, uint16_t max_len);",
+            telemetry.name
+        );
+    }
+
+    out.push_str("\nvoid TF_load_tlm_table(TF_TlmInfo tlm_table[TF_MAX_TLMS])\n{\n");
+    for telemetry in telemetries {
+        let _ = writeln!(
+            out,
+            "  tlm_table[Tlm_CODE_{name}].tlm_func = Tlm_{name}_;",
+            name = telemetry.name
+        );
+    }
+    out.push_str("}\n");
+
+    for telemetry in telemetries {
+        out.push('\n');
+        write_tlm_func(&mut out, telemetry)?;
+    }
+    out.push_str("\n#pragma section\n");
+    Ok(out)
+}
+
+fn write_tlm_func(out: &mut String, telemetry: &tlm::Telemetry) -> Result<()> {
+    let tlm::Content::Struct(entries) = &telemetry.content else {
+        return Err(anyhow!(
+            "blob telemetry {} cannot be generated as C code",
+            telemetry.name
+        ));
+    };
+    let _ = writeln!(
+        out,
+        "static TF_TLM_FUNC_ACK Tlm_{}_(uint8_t* packet, uint16_t* len, uint16_t max_len)\n{{",
+        telemetry.name
+    );
+    let local_variables = telemetry.metadata.local_variables.trim();
+    if !local_variables.is_empty() {
+        for line in local_variables.lines() {
+            let _ = writeln!(out, "  {}", line);
+        }
+        out.push('\n');
+    }
+
+    let length = packet_length(entries);
+    let _ = writeln!(
+        out,
+        "  if ({} > max_len) return TF_TLM_FUNC_ACK_TOO_SHORT_LEN;\n",
+        length
+    );
+    out.push_str("#ifndef BUILD_SETTINGS_FAST_BUILD\n");
+    for field_group in tlm::field_groups(entries) {
+        let expression = field_group.onboard_software_info.expression.trim();
+        if expression.is_empty() {
+            continue;
+        }
+        let Some(first) = field_group.fields().next() else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "  {}(&packet[{}], {});",
+            copy_func(field_group.onboard_software_info.variable_type),
+            first.extraction_info.octet_position,
+            expression
+        );
+    }
+    out.push_str("#endif\n\n");
+    let _ = writeln!(out, "  *len = {};", length);
+    out.push_str("  return TF_TLM_FUNC_ACK_SUCCESS;\n}\n");
+    Ok(())
+}
+
+/// 各 [`tlm::FieldGroup`] を `variable_type` の幅で書き込むのに必要なオクテット数
+///
+/// `TF_copy_*` は最初のフィールドの位置から型の幅だけ書き込むため、フィールドのビット長が型の幅に満たなくても型の幅を数える。
+fn packet_length(entries: &[tlm::Entry]) -> usize {
+    tlm::field_groups(entries)
+        .filter_map(|field_group| {
+            let first = field_group.fields().next()?;
+            let variable_type = field_group.onboard_software_info.variable_type;
+            Some(first.extraction_info.octet_position + variable_type.octet_width())
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+fn copy_func(variable_type: tlm::VariableType) -> &'static str {
+    match variable_type {
+        tlm::VariableType::Int8 => "TF_copy_i8",
+        tlm::VariableType::Int16 => "TF_copy_i16",
+        tlm::VariableType::Int32 => "TF_copy_i32",
+        tlm::VariableType::Uint8 => "TF_copy_u8",
+        tlm::VariableType::Uint16 => "TF_copy_u16",
+        tlm::VariableType::Uint32 => "TF_copy_u32",
+        tlm::VariableType::Float => "TF_copy_float",
+        tlm::VariableType::Double => "TF_copy_double",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, octet_position: usize, bit_length: usize) -> tlm::Field {
+        tlm::Field {
+            name: name.to_string(),
+            extraction_info: tlm::FieldExtractionInfo {
+                extraction_type: "PACKET".to_string(),
+                octet_position,
+                bit_position: 0,
+                bit_length,
+            },
+            conversion_info: tlm::ConversionInfo::None,
+            display_info: None,
+            description: String::new(),
+            note: String::new(),
+        }
+    }
+
+    fn group(variable_type: tlm::VariableType, expression: &str, field: tlm::Field) -> tlm::Entry {
+        tlm::Entry::FieldGroup(tlm::FieldGroup {
+            onboard_software_info: tlm::OnboardSoftwareInfo {
+                variable_type,
+                expression: expression.to_string(),
+            },
+            sub_entries: vec![tlm::SubEntry::Field(field)],
+        })
+    }
+
+    fn telemetry(name: &str, packet_id: u8, is_enabled: bool) -> tlm::Telemetry {
+        tlm::Telemetry {
+            name: name.to_string(),
+            metadata: tlm::Metadata {
+                target: "OBC".to_string(),
+                packet_id,
+                is_enabled,
+                is_restricted: false,
+                local_variables: "uint8_t temp = 10;".to_string(),
+            },
+            content: tlm::Content::Struct(vec![
+                group(tlm::VariableType::Uint16, "", field("PH.VER", 0, 16)),
+                group(
+                    tlm::VariableType::Float,
+                    "(float)(temp)",
+                    field("TEMP", 2, 32),
+                ),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_generate() {
+        let component = Component {
+            name: "MOBC".to_string(),
+            tlm: tlm::Database {
+                telemetries: vec![
+                    telemetry("HK", 0xf0, true),
+                    telemetry("MOBC", 0x00, true),
+                    telemetry("DISABLED", 0x01, false),
+                ],
+            },
+            cmd: tlmcmddb::cmd::Database { entries: vec![] },
+            bct: Default::default(),
+        };
+        let files = generate(&component).unwrap();
+        let names: Vec<_> = files.iter().map(|file| file.name.as_str()).collect();
+        assert_eq!(
+            vec![
+                "tlm_code.h",
+                "telemetry_definitions.h",
+                "telemetry_definitions.c"
+            ],
+            names
+        );
+
+        let tlm_code_h = &files[0].content;
+        assert!(tlm_code_h.contains("  Tlm_CODE_MOBC = 0x00,\n  Tlm_CODE_HK = 0xf0,\n"));
+        assert!(!tlm_code_h.contains("DISABLED"));
+
+        let definitions_c = &files[2].content;
+        assert!(definitions_c.contains("  tlm_table[Tlm_CODE_HK].tlm_func = Tlm_HK_;\n"));
+        assert!(definitions_c.contains(
+            "static TF_TLM_FUNC_ACK Tlm_HK_(uint8_t* packet, uint16_t* len, uint16_t max_len)\n{\n  uint8_t temp = 10;\n\n  if (6 > max_len) return TF_TLM_FUNC_ACK_TOO_SHORT_LEN;\n"
+        ));
+        assert!(definitions_c.contains("  TF_copy_float(&packet[2], (float)(temp));\n"));
+        assert!(!definitions_c.contains("&packet[0]"));
+        assert!(definitions_c.contains("  *len = 6;\n"));
+    }
+
+    #[test]
+    fn test_packet_length() {
+        // 24bit のフィールドしかもたない uint32_t の FieldGroup も4オクテット書き込まれる
+        let entries = vec![
+            group(tlm::VariableType::Uint16, "", field("PH.VER", 0, 16)),
+            group(
+                tlm::VariableType::Uint32,
+                "counter",
+                field("COUNTER", 2, 24),
+            ),
+        ];
+        assert_eq!(6, packet_length(&entries));
+    }
+}
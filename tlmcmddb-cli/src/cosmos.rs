@@ -0,0 +1,305 @@
+//! OpenC3 COSMOS のターゲット定義（`cmd_tlm/cmd.txt`, `cmd_tlm/tlm.txt`）の生成
+//!
+//! component の名前をターゲット名とする。
+//! テレメトリは SH.TLM_ID（11オクテット目）を ID_ITEM として識別する。
+//! コマンドは C2A のコマンド Space Packet 全体を定義し、Cmd ID を ID_PARAMETER とする。
+//! raw パラメータをもつコマンドは可変長であるため、CCSDS_LENGTH は送信時に設定しなければならない。
+//! BCT（[`Component::bct`]）は書き出さない。
+
+use std::fmt::Write;
+
+use anyhow::Result;
+use tlmcmddb::{
+    cmd::{
+        self,
+        encode::{
+            packet_data_length, CMD_ID_FIELD, HEADER_FIELDS, PACKET_LENGTH_FIELD,
+            PRIMARY_HEADER_LEN, SECONDARY_HEADER_LEN,
+        },
+    },
+    tlm::{
+        self, conversion,
+        decode::{TLM_ID_LEN, TLM_ID_OFFSET},
+    },
+    Component,
+};
+
+use crate::codegen::GeneratedFile;
+
+pub fn generate(component: &Component) -> Result<Vec<GeneratedFile>> {
+    Ok(vec![
+        GeneratedFile::new("cmd.txt", generate_cmd_txt(component)),
+        GeneratedFile::new("tlm.txt", generate_tlm_txt(component)),
+    ])
+}
+
+/// COSMOS の名前として使えない文字を `_` に置き換える
+fn cosmos_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// 二重引用符で囲む。COSMOS の設定ファイルは引用符のエスケープをもたないため、中の `"` は `'` に置き換える
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace('"', "'").replace(['\r', '\n'], " "))
+}
+
+fn generate_tlm_txt(component: &Component) -> String {
+    let target = cosmos_name(&component.name);
+    let mut out = String::new();
+    for telemetry in &component.tlm.telemetries {
+        let metadata = &telemetry.metadata;
+        let _ = writeln!(
+            out,
+            "TELEMETRY {} {} BIG_ENDIAN {}",
+            target,
+            cosmos_name(&telemetry.name),
+            quote(&format!(
+                "{} (packet_id: 0x{:02x})",
+                metadata.target, metadata.packet_id
+            ))
+        );
+        let mut has_id_item = false;
+        match &telemetry.content {
+            tlm::Content::Struct(_) => {
+                for (field_group, field) in telemetry.fields() {
+                    let info = &field.extraction_info;
+                    let bit_offset = info.octet_position * 8 + info.bit_position;
+                    let is_id_item =
+                        bit_offset == TLM_ID_OFFSET * 8 && info.bit_length == TLM_ID_LEN * 8;
+                    has_id_item |= is_id_item;
+                    write_item(
+                        &mut out,
+                        field_group,
+                        field,
+                        is_id_item.then_some(metadata.packet_id),
+                    );
+                }
+            }
+            tlm::Content::Blob => {
+                out.push_str("  ITEM BLOB 0 0 BLOCK \"blob telemetry\"\n");
+            }
+        }
+        if !has_id_item {
+            let _ = writeln!(
+                out,
+                "  ID_ITEM TLM_ID {} {} UINT {} \"SH.TLM_ID\"",
+                TLM_ID_OFFSET * 8,
+                TLM_ID_LEN * 8,
+                metadata.packet_id
+            );
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn item_type(variable_type: tlm::VariableType) -> &'static str {
+    match variable_type {
+        tlm::VariableType::Float | tlm::VariableType::Double => "FLOAT",
+        variable_type if variable_type.is_unsigned_integer() => "UINT",
+        _ => "INT",
+    }
+}
+
+/// `id` が指定されていれば、その値でパケットを識別する ID_ITEM とする
+fn write_item(out: &mut String, field_group: &tlm::FieldGroup, field: &tlm::Field, id: Option<u8>) {
+    let info = &field.extraction_info;
+    let item_type = item_type(field_group.onboard_software_info.variable_type);
+    let _ = write!(
+        out,
+        "  {} {} {} {} {}",
+        if id.is_some() { "ID_ITEM" } else { "ITEM" },
+        cosmos_name(&field.name),
+        info.octet_position * 8 + info.bit_position,
+        info.bit_length,
+        item_type
+    );
+    if let Some(id) = id {
+        let _ = write!(out, " {}", id);
+    }
+    let _ = writeln!(out, " {}", quote(&field.description));
+
+    match &field.conversion_info {
+        tlm::ConversionInfo::None => {}
+        tlm::ConversionInfo::Hex => {
+            let has_format = field
+                .display_info
+                .as_ref()
+                .is_some_and(|display_info| !display_info.format.is_empty());
+            if !has_format {
+                out.push_str("    FORMAT_STRING \"0x%X\"\n");
+            }
+        }
+        tlm::ConversionInfo::Polynomial(polynomial) => write_poly_read_conversion(out, polynomial),
+        tlm::ConversionInfo::Status(status) => write_states(out, status),
+    }
+    if let Some(display_info) = &field.display_info {
+        if !display_info.unit.is_empty() {
+            let unit = quote(&display_info.unit);
+            let _ = writeln!(out, "    UNITS {} {}", unit, unit);
+        }
+        if !display_info.format.is_empty() {
+            let _ = writeln!(out, "    FORMAT_STRING {}", quote(&display_info.format));
+        }
+    }
+}
+
+fn write_poly_read_conversion(out: &mut String, polynomial: &conversion::Polynomial) {
+    let coefficients = [
+        polynomial.a0,
+        polynomial.a1,
+        polynomial.a2,
+        polynomial.a3,
+        polynomial.a4,
+        polynomial.a5,
+    ];
+    // 高次の0の係数は省略する。ただし少なくとも1次の係数までは書く
+    let degree = coefficients
+        .iter()
+        .rposition(|&coefficient| coefficient != 0.0)
+        .unwrap_or(0)
+        .max(1);
+    out.push_str("    POLY_READ_CONVERSION");
+    for coefficient in &coefficients[..=degree] {
+        let _ = write!(out, " {}", coefficient);
+    }
+    out.push('\n');
+}
+
+/// ステータス変換の各値を STATE とする。`default_value` は COSMOS で表現できないため無視する
+fn write_states(out: &mut String, status: &conversion::Status) {
+    for variant in &status.variants {
+        let _ = writeln!(out, "    STATE {} {}", quote(&variant.value), variant.key);
+    }
+}
+
+fn generate_cmd_txt(component: &Component) -> String {
+    let target = cosmos_name(&component.name);
+    let mut out = String::new();
+    for entry in &component.cmd.entries {
+        let cmd::Entry::Command(command) = entry else {
+            continue;
+        };
+        let _ = writeln!(
+            out,
+            "COMMAND {} {} BIG_ENDIAN {}",
+            target,
+            cosmos_name(&command.name),
+            quote(&command.description)
+        );
+        write_header_parameters(&mut out, command);
+        let mut bit_offset = (PRIMARY_HEADER_LEN + SECONDARY_HEADER_LEN) * 8;
+        for (i, parameter) in command.parameters.iter().enumerate() {
+            let name = format!("Param{}", i + 1);
+            let description = quote(&parameter.description);
+            let (bit_size, parameter_type, range) = parameter_format(parameter.data_type);
+            let _ = writeln!(
+                out,
+                "  PARAMETER {} {} {} {} {} {}",
+                name, bit_offset, bit_size, parameter_type, range, description
+            );
+            bit_offset += bit_size;
+        }
+        if command.is_danger {
+            out.push_str("  HAZARDOUS\n");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// C2A のコマンド Space Packet のヘッダ。Cmd ID を ID_PARAMETER とする
+fn write_header_parameters(out: &mut String, command: &cmd::Command) {
+    let mut bit_offset = 0;
+    for field in HEADER_FIELDS {
+        let (keyword, value) = match field.name {
+            CMD_ID_FIELD => ("ID_PARAMETER", u64::from(command.code)),
+            // raw パラメータの長さは数えない
+            PACKET_LENGTH_FIELD => ("PARAMETER", packet_data_length(command) as u64),
+            _ => ("PARAMETER", field.default_value),
+        };
+        let (min, max) = if field.is_fixed {
+            (value, value)
+        } else {
+            (0, (1 << field.bit_length) - 1)
+        };
+        let _ = writeln!(
+            out,
+            "  {} {} {} {} UINT {} {} {} {}",
+            keyword,
+            field.name,
+            bit_offset,
+            field.bit_length,
+            min,
+            max,
+            value,
+            quote(field.description)
+        );
+        bit_offset += field.bit_length;
+    }
+}
+
+/// パラメータのビット幅と COSMOS の型、最小値・最大値・初期値
+fn parameter_format(data_type: cmd::DataType) -> (usize, &'static str, &'static str) {
+    match data_type {
+        cmd::DataType::Int8 => (8, "INT", "MIN_INT8 MAX_INT8 0"),
+        cmd::DataType::Int16 => (16, "INT", "MIN_INT16 MAX_INT16 0"),
+        cmd::DataType::Int32 => (32, "INT", "MIN_INT32 MAX_INT32 0"),
+        cmd::DataType::Uint8 => (8, "UINT", "MIN_UINT8 MAX_UINT8 0"),
+        cmd::DataType::Uint16 => (16, "UINT", "MIN_UINT16 MAX_UINT16 0"),
+        cmd::DataType::Uint32 => (32, "UINT", "MIN_UINT32 MAX_UINT32 0"),
+        cmd::DataType::Float => (32, "FLOAT", "MIN_FLOAT32 MAX_FLOAT32 0.0"),
+        cmd::DataType::Double => (64, "FLOAT", "MIN_FLOAT64 MAX_FLOAT64 0.0"),
+        // ビット幅0の BLOCK はパケットの残りすべてを表す
+        cmd::DataType::Raw => (0, "BLOCK", "\"\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        let component = Component {
+            name: "MOBC".to_string(),
+            tlm: tlm::Database {
+                telemetries: vec![telemetry],
+            },
+            cmd,
+            bct: Default::default(),
+        };
+        let files = generate(&component).unwrap();
+
+        let cmd_txt = &files[0].content;
+        assert_eq!("cmd.txt", files[0].name);
+        assert!(cmd_txt.contains("COMMAND MOBC Cmd_NOP BIG_ENDIAN "));
+        assert!(cmd_txt.contains("  ID_PARAMETER CMD_CODE 64 16 UINT 0 65535 0 \"command ID\"\n"));
+        assert!(
+            cmd_txt.contains("  PARAMETER SH_VER 48 8 UINT 1 1 1 \"secondary header version\"\n")
+        );
+        assert!(cmd_txt.contains("  PARAMETER TI 88 32 UINT 0 4294967295 0 \"time indicator\"\n"));
+        assert!(cmd_txt.contains("  PARAMETER Param1 120 32 UINT "));
+
+        let tlm_txt = &files[1].content;
+        assert_eq!("tlm.txt", files[1].name);
+        assert!(tlm_txt.contains("TELEMETRY MOBC HK BIG_ENDIAN \"OBC (packet_id: 0xf0)\"\n"));
+        assert!(tlm_txt.contains("  ITEM PH_VER 0 3 UINT "));
+        assert!(tlm_txt.contains("  ID_ITEM SH_TLM_ID 88 8 UINT 240 "));
+        assert!(tlm_txt.contains("    FORMAT_STRING \"0x%X\"\n"));
+        assert!(tlm_txt.contains("    STATE "));
+    }
+}
@@ -0,0 +1,519 @@
+//! 運用者向けのリファレンスドキュメント（Markdown / HTML）の生成
+//!
+//! 索引ページと、component ごとに1ページを生成する。
+//! コメント行は、行頭の `*` の数に応じた深さの見出しとして出力する。BCT（[`Component::bct`]）は出力しない。
+
+use std::fmt::Write;
+
+use quick_xml::escape::escape;
+use tlmcmddb::{
+    cmd,
+    tlm::{self, conversion},
+    Component, Database,
+};
+
+use crate::codegen::GeneratedFile;
+
+/// 出力形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Markdown,
+    Html,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Markdown => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+pub fn generate(database: &Database, format: Format) -> Vec<GeneratedFile> {
+    let mut files = vec![GeneratedFile::new(
+        format!("index.{}", format.extension()),
+        render(format, "TLM CMD DB", &index_page(database, format)),
+    )];
+    for component in &database.components {
+        files.push(GeneratedFile::new(
+            page_name(component, format),
+            render(format, &component.name, &component_page(component)),
+        ));
+    }
+    files
+}
+
+/// ページの構成要素
+#[derive(Debug, Clone, PartialEq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    Links(Vec<Link>),
+    Table(Table),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Link {
+    text: String,
+    href: String,
+    detail: String,
+}
+
+/// セル内の改行はそのまま改行として表示する
+#[derive(Debug, Clone, PartialEq)]
+struct Table {
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+/// コメント行で区切られた表を組み立てる
+struct Sections {
+    blocks: Vec<Block>,
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Sections {
+    fn new(header: &'static [&'static str]) -> Self {
+        Self {
+            blocks: vec![],
+            header,
+            rows: vec![],
+        }
+    }
+
+    fn flush(&mut self) {
+        if !self.rows.is_empty() {
+            self.blocks.push(Block::Table(Table {
+                header: self.header,
+                rows: std::mem::take(&mut self.rows),
+            }));
+        }
+    }
+
+    /// `base_level` は `*` が1つのコメント行に用いる見出しの深さ
+    fn comment(&mut self, base_level: usize, text: &str) {
+        if let Some((depth, text)) = section_heading(text) {
+            self.flush();
+            self.blocks
+                .push(Block::Heading((base_level + depth - 1).min(6), text));
+        }
+    }
+
+    fn row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    fn finish(mut self) -> Vec<Block> {
+        self.flush();
+        self.blocks
+    }
+}
+
+/// コメント行を見出しにする。行頭の `*` の数（最低1）を深さとし、空のセルを除いて何も残らなければ `None`
+fn section_heading(text: &str) -> Option<(usize, String)> {
+    let stripped = text.trim_start_matches('*');
+    let depth = (text.len() - stripped.len()).max(1);
+    let cells: Vec<_> = stripped
+        .split(',')
+        .map(str::trim)
+        .filter(|cell| !cell.is_empty())
+        .collect();
+    (!cells.is_empty()).then(|| (depth, cells.join(" ")))
+}
+
+/// component のページのファイル名。パスとして扱えない文字は `_` に置き換える
+fn page_name(component: &Component, format: Format) -> String {
+    let name: String = component
+        .name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}.{}", name, format.extension())
+}
+
+fn index_page(database: &Database, format: Format) -> Vec<Block> {
+    let links = database
+        .components
+        .iter()
+        .map(|component| {
+            let commands = component
+                .cmd
+                .entries
+                .iter()
+                .filter(|entry| matches!(entry, cmd::Entry::Command(_)))
+                .count();
+            Link {
+                text: component.name.clone(),
+                href: page_name(component, format),
+                detail: format!(
+                    "{} telemetries, {} commands",
+                    component.tlm.telemetries.len(),
+                    commands
+                ),
+            }
+        })
+        .collect();
+    vec![
+        Block::Heading(1, "TLM CMD DB".to_string()),
+        Block::Links(links),
+    ]
+}
+
+fn component_page(component: &Component) -> Vec<Block> {
+    let mut blocks = vec![Block::Heading(1, component.name.clone())];
+    blocks.push(Block::Heading(2, "Commands".to_string()));
+    blocks.extend(command_blocks(&component.cmd));
+    blocks.push(Block::Heading(2, "Telemetries".to_string()));
+    for telemetry in &component.tlm.telemetries {
+        blocks.extend(telemetry_blocks(telemetry));
+    }
+    blocks
+}
+
+fn yes_no(value: bool) -> String {
+    if value { "yes" } else { "no" }.to_string()
+}
+
+fn command_blocks(database: &cmd::Database) -> Vec<Block> {
+    let mut sections = Sections::new(&[
+        "Code",
+        "Name",
+        "Target",
+        "Parameters",
+        "Danger",
+        "Restricted",
+        "Description",
+        "Note",
+    ]);
+    for entry in &database.entries {
+        match entry {
+            cmd::Entry::Comment(comment) => sections.comment(3, &comment.text),
+            cmd::Entry::Command(command) => {
+                let parameters = command
+                    .parameters
+                    .iter()
+                    .enumerate()
+                    .map(|(i, parameter)| {
+                        let data_type = parameter.data_type.as_str();
+                        if parameter.description.is_empty() {
+                            format!("{}. {}", i + 1, data_type)
+                        } else {
+                            format!("{}. {}: {}", i + 1, data_type, parameter.description)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                sections.row(vec![
+                    format!("0x{:04X}", command.code),
+                    command.name.clone(),
+                    command.target.clone(),
+                    parameters,
+                    yes_no(command.is_danger),
+                    yes_no(command.is_restricted),
+                    command.description.clone(),
+                    command.note.clone(),
+                ]);
+            }
+        }
+    }
+    sections.finish()
+}
+
+fn telemetry_blocks(telemetry: &tlm::Telemetry) -> Vec<Block> {
+    let metadata = &telemetry.metadata;
+    let mut blocks = vec![
+        Block::Heading(3, telemetry.name.clone()),
+        Block::Paragraph(format!(
+            "Packet ID: 0x{:02X} / Target: {} / Enabled: {} / Restricted: {}",
+            metadata.packet_id,
+            metadata.target,
+            yes_no(metadata.is_enabled),
+            yes_no(metadata.is_restricted)
+        )),
+    ];
+    let entries = match &telemetry.content {
+        tlm::Content::Blob => {
+            blocks.push(Block::Paragraph(
+                "Blob telemetry: the payload has no field definitions.".to_string(),
+            ));
+            return blocks;
+        }
+        tlm::Content::Struct(entries) => entries,
+    };
+    let mut sections = Sections::new(&[
+        "Name",
+        "Type",
+        "Octet",
+        "Bit",
+        "Length",
+        "Conversion",
+        "Unit",
+        "Description",
+        "Note",
+    ]);
+    for entry in entries {
+        let field_group = match entry {
+            tlm::Entry::Comment(comment) => {
+                sections.comment(4, &comment.text);
+                continue;
+            }
+            tlm::Entry::FieldGroup(field_group) => field_group,
+        };
+        let variable_type = field_group.onboard_software_info.variable_type.as_str();
+        for sub_entry in &field_group.sub_entries {
+            match sub_entry {
+                tlm::SubEntry::Comment(comment) => sections.comment(4, &comment.text),
+                tlm::SubEntry::Field(field) => {
+                    let info = &field.extraction_info;
+                    let unit = field
+                        .display_info
+                        .as_ref()
+                        .map(|display_info| display_info.unit.clone())
+                        .unwrap_or_default();
+                    sections.row(vec![
+                        field.name.clone(),
+                        variable_type.to_string(),
+                        info.octet_position.to_string(),
+                        info.bit_position.to_string(),
+                        info.bit_length.to_string(),
+                        describe_conversion(&field.conversion_info),
+                        unit,
+                        field.description.clone(),
+                        field.note.clone(),
+                    ]);
+                }
+            }
+        }
+    }
+    blocks.extend(sections.finish());
+    blocks
+}
+
+fn describe_conversion(conversion_info: &tlm::ConversionInfo) -> String {
+    match conversion_info {
+        tlm::ConversionInfo::None => String::new(),
+        tlm::ConversionInfo::Hex => "HEX".to_string(),
+        tlm::ConversionInfo::Status(status) => describe_status(status),
+        tlm::ConversionInfo::Polynomial(polynomial) => {
+            format!("POLY: {}", polynomial_expression(polynomial))
+        }
+    }
+}
+
+fn describe_status(status: &conversion::Status) -> String {
+    let mut lines = vec!["STATUS".to_string()];
+    for variant in &status.variants {
+        lines.push(format!("{}: {}", variant.key, variant.value));
+    }
+    if let Some(default_value) = &status.default_value {
+        lines.push(format!("*: {}", default_value));
+    }
+    lines.join("\n")
+}
+
+/// 生値を `x` とした多項式。係数が0の項は省く
+fn polynomial_expression(polynomial: &conversion::Polynomial) -> String {
+    let coefficients = [
+        polynomial.a0,
+        polynomial.a1,
+        polynomial.a2,
+        polynomial.a3,
+        polynomial.a4,
+        polynomial.a5,
+    ];
+    let terms: Vec<_> = coefficients
+        .iter()
+        .enumerate()
+        .filter(|(_, &a)| a != 0.0)
+        .map(|(i, a)| match i {
+            0 => format!("{}", a),
+            1 => format!("{}x", a),
+            _ => format!("{}x^{}", a, i),
+        })
+        .collect();
+    if terms.is_empty() {
+        "0".to_string()
+    } else {
+        terms.join(" + ")
+    }
+}
+
+fn render(format: Format, title: &str, blocks: &[Block]) -> String {
+    match format {
+        Format::Markdown => render_markdown(blocks),
+        Format::Html => render_html(title, blocks),
+    }
+}
+
+/// Markdown の記法として解釈されうる記号をエスケープする
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '#'
+        ) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+fn render_markdown(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "{} {}\n", "#".repeat(*level), escape_markdown(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "{}\n", escape_markdown(text));
+            }
+            Block::Links(links) => {
+                for link in links {
+                    let _ = writeln!(
+                        out,
+                        "- [{}]({}) ({})",
+                        escape_markdown(&link.text),
+                        link.href,
+                        escape_markdown(&link.detail)
+                    );
+                }
+                out.push('\n');
+            }
+            Block::Table(table) => {
+                let _ = writeln!(out, "| {} |", table.header.join(" | "));
+                let _ = writeln!(out, "|{}", " --- |".repeat(table.header.len()));
+                for row in &table.rows {
+                    let cells: Vec<_> = row
+                        .iter()
+                        .map(|cell| {
+                            escape_markdown(cell.trim())
+                                .replace("\r\n", "<br>")
+                                .replace('\n', "<br>")
+                        })
+                        .collect();
+                    let _ = writeln!(out, "| {} |", cells.join(" | "));
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    escape(text).replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+fn render_html(title: &str, blocks: &[Block]) -> String {
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>{}</title>", escape(title));
+    out.push_str(concat!(
+        "<style>\n",
+        "table { border-collapse: collapse; }\n",
+        "th, td { border: 1px solid #ccc; padding: 2px 6px; vertical-align: top; }\n",
+        "</style>\n",
+        "</head>\n<body>\n",
+    ));
+    for block in blocks {
+        match block {
+            Block::Heading(level, text) => {
+                let _ = writeln!(out, "<h{0}>{1}</h{0}>", level, escape_html(text));
+            }
+            Block::Paragraph(text) => {
+                let _ = writeln!(out, "<p>{}</p>", escape_html(text));
+            }
+            Block::Links(links) => {
+                out.push_str("<ul>\n");
+                for link in links {
+                    let _ = writeln!(
+                        out,
+                        "<li><a href=\"{}\">{}</a> ({})</li>",
+                        escape(&link.href),
+                        escape_html(&link.text),
+                        escape_html(&link.detail)
+                    );
+                }
+                out.push_str("</ul>\n");
+            }
+            Block::Table(table) => {
+                out.push_str("<table>\n<tr>");
+                for header in table.header {
+                    let _ = write!(out, "<th>{}</th>", escape_html(header));
+                }
+                out.push_str("</tr>\n");
+                for row in &table.rows {
+                    out.push_str("<tr>");
+                    for cell in row {
+                        let _ = write!(out, "<td>{}</td>", escape_html(cell.trim()));
+                    }
+                    out.push_str("</tr>\n");
+                }
+                out.push_str("</table>\n");
+            }
+        }
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generate() {
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let mut cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        cmd.entries.insert(
+            0,
+            cmd::Entry::Comment(cmd::Comment {
+                text: "** C2A_CORE,基幹機能コマンド,,,".to_string(),
+            }),
+        );
+        let database = Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![telemetry],
+                },
+                cmd,
+                bct: Default::default(),
+            }],
+        };
+
+        let files = generate(&database, Format::Markdown);
+        assert_eq!("index.md", files[0].name);
+        assert!(files[0]
+            .content
+            .contains("- [MOBC](MOBC.md) (1 telemetries, "));
+        assert_eq!("MOBC.md", files[1].name);
+        let page = &files[1].content;
+        assert!(page.contains("#### C2A\\_CORE 基幹機能コマンド\n"));
+        assert!(page.contains("| 0x0000 | Cmd\\_NOP | "));
+        assert!(page.contains("Packet ID: 0xF0 / Target: "));
+        assert!(page.contains("| PH.VER | uint16\\_t | 0 | 0 | 3 | "));
+        assert!(page.contains("STATUS<br>"));
+
+        let files = generate(&database, Format::Html);
+        assert_eq!("MOBC.html", files[1].name);
+        assert!(files[1]
+            .content
+            .contains("<h4>C2A_CORE 基幹機能コマンド</h4>"));
+        assert!(files[1].content.contains("<td>0x0000</td><td>Cmd_NOP</td>"));
+    }
+}
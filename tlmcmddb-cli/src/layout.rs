@@ -0,0 +1,389 @@
+//! テレメトリのビット配置図（ASCII / SVG）の描画
+//!
+//! 1オクテットを1行とし、各ビットを占めるフィールドを示す。
+//! どのフィールドにも属さないビット（隙間）と、複数のフィールドが占めるビット（重なり）は区別して示す。
+
+use std::{fmt::Write, ops::Range};
+
+use anyhow::{anyhow, Result};
+use tlmcmddb::tlm;
+
+use crate::xml::XmlWriter;
+
+/// フィールドが占めるビットの範囲
+#[derive(Debug, Clone, PartialEq)]
+struct Placement<'a> {
+    /// 所属する [`tlm::FieldGroup`] の番号（0始まり）
+    group: usize,
+    field: &'a tlm::Field,
+    bits: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Layout<'a> {
+    groups: Vec<tlm::VariableType>,
+    placements: Vec<Placement<'a>>,
+    /// 各ビットを占めるフィールドの `placements` における添字
+    occupants: Vec<Vec<usize>>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(telemetry: &'a tlm::Telemetry) -> Result<Self> {
+        let tlm::Content::Struct(entries) = &telemetry.content else {
+            return Err(anyhow!(
+                "{} is a blob telemetry and has no field layout",
+                telemetry.name
+            ));
+        };
+        let mut groups = vec![];
+        let mut placements = vec![];
+        for field_group in tlm::field_groups(entries) {
+            let group = groups.len();
+            groups.push(field_group.onboard_software_info.variable_type);
+            for field in field_group.fields() {
+                let info = &field.extraction_info;
+                let start = info.octet_position * 8 + info.bit_position;
+                placements.push(Placement {
+                    group,
+                    field,
+                    bits: start..start + info.bit_length,
+                });
+            }
+        }
+        let total_bits = placements
+            .iter()
+            .map(|placement| placement.bits.end)
+            .max()
+            .unwrap_or_default()
+            .next_multiple_of(8);
+        let mut occupants = vec![vec![]; total_bits];
+        for (i, placement) in placements.iter().enumerate() {
+            for bit in placement.bits.clone() {
+                occupants[bit].push(i);
+            }
+        }
+        Ok(Self {
+            groups,
+            placements,
+            occupants,
+        })
+    }
+
+    fn octets(&self) -> usize {
+        self.occupants.len() / 8
+    }
+
+    /// 占めるフィールドの集合が等しいビットの連続をまとめ、`predicate` を満たすものを返す
+    fn runs(&self, predicate: impl Fn(&[usize]) -> bool) -> Vec<(Range<usize>, &[usize])> {
+        let mut runs: Vec<(Range<usize>, &[usize])> = vec![];
+        for (bit, occupants) in self.occupants.iter().enumerate() {
+            if !predicate(occupants) {
+                continue;
+            }
+            match runs.last_mut() {
+                Some((range, last)) if range.end == bit && *last == occupants.as_slice() => {
+                    range.end += 1;
+                }
+                _ => runs.push((bit..bit + 1, occupants)),
+            }
+        }
+        runs
+    }
+
+    fn gaps(&self) -> Vec<Range<usize>> {
+        self.runs(|occupants| occupants.is_empty())
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    fn overlaps(&self) -> Vec<(Range<usize>, &[usize])> {
+        self.runs(|occupants| occupants.len() > 1)
+    }
+
+    fn names(&self, indices: &[usize]) -> String {
+        indices
+            .iter()
+            .map(|&i| self.placements[i].field.name.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// `octet` の中で始まる FieldGroup の番号
+    fn groups_starting_at(&self, octet: usize) -> Vec<usize> {
+        (0..self.groups.len())
+            .filter(|&group| {
+                self.placements
+                    .iter()
+                    .filter(|placement| placement.group == group)
+                    .map(|placement| placement.bits.start)
+                    .min()
+                    .is_some_and(|start| start / 8 == octet)
+            })
+            .collect()
+    }
+}
+
+fn describe_bits(bits: &Range<usize>) -> String {
+    format!(
+        "bits {}-{} (octet {} bit {}, {} bits)",
+        bits.start,
+        bits.end - 1,
+        bits.start / 8,
+        bits.start % 8,
+        bits.len()
+    )
+}
+
+/// 端末向けの ASCII の配置図
+///
+/// 各行の右に、その行で使った文字とフィールドの対応を示す。
+/// 隙間は `.`、重なりは `!` で示す。
+pub fn render_ascii(telemetry: &tlm::Telemetry) -> Result<String> {
+    let layout = Layout::new(telemetry)?;
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} (packet ID 0x{:02X}, {} octets)\n",
+        telemetry.name,
+        telemetry.metadata.packet_id,
+        layout.octets()
+    );
+    let _ = writeln!(out, "      bit 01234567");
+    for octet in 0..layout.octets() {
+        for group in layout.groups_starting_at(octet) {
+            let _ = writeln!(
+                out,
+                "-- group {}: {}",
+                group + 1,
+                layout.groups[group].as_str()
+            );
+        }
+        let mut legend: Vec<usize> = vec![];
+        let mut overlapping: Vec<usize> = vec![];
+        let mut cells = String::new();
+        for occupants in &layout.occupants[octet * 8..octet * 8 + 8] {
+            match occupants.as_slice() {
+                [] => cells.push('.'),
+                [i] => {
+                    let position = legend.iter().position(|j| j == i).unwrap_or_else(|| {
+                        legend.push(*i);
+                        legend.len() - 1
+                    });
+                    cells.push((b'a' + position as u8) as char);
+                }
+                occupants => {
+                    cells.push('!');
+                    for i in occupants {
+                        if !overlapping.contains(i) {
+                            overlapping.push(*i);
+                        }
+                    }
+                }
+            }
+        }
+        let mut labels: Vec<_> = legend
+            .iter()
+            .enumerate()
+            .map(|(position, &i)| {
+                format!(
+                    "{}={}",
+                    (b'a' + position as u8) as char,
+                    layout.placements[i].field.name
+                )
+            })
+            .collect();
+        if !overlapping.is_empty() {
+            labels.push(format!("!={}", layout.names(&overlapping)));
+        }
+        // 凡例が空の行に空白を残さない
+        let line = format!("octet {:>3} {}  {}", octet, cells, labels.join(" "));
+        let _ = writeln!(out, "{}", line.trim_end());
+    }
+    let gaps = layout.gaps();
+    let overlaps = layout.overlaps();
+    if !gaps.is_empty() || !overlaps.is_empty() {
+        out.push('\n');
+    }
+    for gap in gaps {
+        let _ = writeln!(out, "gap: {}", describe_bits(&gap));
+    }
+    for (bits, occupants) in overlaps {
+        let _ = writeln!(
+            out,
+            "overlap: {}: {}",
+            describe_bits(&bits),
+            layout.names(occupants)
+        );
+    }
+    Ok(out)
+}
+
+const SVG_BIT_WIDTH: usize = 64;
+const SVG_ROW_HEIGHT: usize = 28;
+const SVG_LEFT: usize = 64;
+const SVG_TOP: usize = 24;
+const SVG_CHAR_WIDTH: usize = 7;
+/// FieldGroup ごとに交互に用いる塗りつぶしの色
+const SVG_GROUP_COLORS: &[&str] = &["#cfe2ff", "#d1e7dd", "#fff3cd", "#e2d9f3"];
+
+/// SVG の配置図
+///
+/// フィールドは所属する FieldGroup ごとに色分けし、隙間は灰色、重なりは赤で示す。
+/// 各矩形の `title` にフィールド名と位置を入れる。
+pub fn render_svg(telemetry: &tlm::Telemetry) -> Result<String> {
+    let layout = Layout::new(telemetry)?;
+    let width = SVG_LEFT + 8 * SVG_BIT_WIDTH + 8;
+    let height = SVG_TOP + layout.octets() * SVG_ROW_HEIGHT + 8;
+    let (width, height) = (width.to_string(), height.to_string());
+    let mut svg = XmlWriter::new();
+    svg.open(
+        "svg",
+        &[
+            ("xmlns", "http://www.w3.org/2000/svg"),
+            ("width", &width),
+            ("height", &height),
+            ("font-family", "monospace"),
+            ("font-size", "11"),
+        ],
+    );
+    svg.text("title", &[], &telemetry.name);
+    for bit in 0..8 {
+        let x = (SVG_LEFT + bit * SVG_BIT_WIDTH + SVG_BIT_WIDTH / 2).to_string();
+        svg.text(
+            "text",
+            &[("x", &x), ("y", "16"), ("text-anchor", "middle")],
+            &bit.to_string(),
+        );
+    }
+    for octet in 0..layout.octets() {
+        let y = (SVG_TOP + octet * SVG_ROW_HEIGHT + SVG_ROW_HEIGHT / 2 + 4).to_string();
+        svg.text(
+            "text",
+            &[("x", "8"), ("y", &y)],
+            &format!("octet {}", octet),
+        );
+    }
+
+    for placement in &layout.placements {
+        let color = SVG_GROUP_COLORS[placement.group % SVG_GROUP_COLORS.len()];
+        let title = format!(
+            "{} (group {}: {}) {}",
+            placement.field.name,
+            placement.group + 1,
+            layout.groups[placement.group].as_str(),
+            describe_bits(&placement.bits)
+        );
+        for segment in row_segments(&placement.bits) {
+            svg_cell(&mut svg, &segment, color, &placement.field.name, &title);
+        }
+    }
+    for gap in layout.gaps() {
+        let title = format!("gap: {}", describe_bits(&gap));
+        for segment in row_segments(&gap) {
+            svg_cell(&mut svg, &segment, "#e0e0e0", "", &title);
+        }
+    }
+    for (bits, occupants) in layout.overlaps() {
+        let title = format!(
+            "overlap: {}: {}",
+            describe_bits(&bits),
+            layout.names(occupants)
+        );
+        for segment in row_segments(&bits) {
+            svg_cell(&mut svg, &segment, "#f8d7da", "!", &title);
+        }
+    }
+    svg.close("svg");
+    Ok(svg.finish())
+}
+
+/// ビットの範囲を、オクテットの境界で分割する
+fn row_segments(bits: &Range<usize>) -> Vec<Range<usize>> {
+    let mut segments = vec![];
+    let mut start = bits.start;
+    while start < bits.end {
+        let end = bits.end.min((start / 8 + 1) * 8);
+        segments.push(start..end);
+        start = end;
+    }
+    segments
+}
+
+/// 1オクテット内に収まるビットの範囲を矩形として描く
+fn svg_cell(svg: &mut XmlWriter, segment: &Range<usize>, fill: &str, label: &str, title: &str) {
+    let x = SVG_LEFT + segment.start % 8 * SVG_BIT_WIDTH;
+    let y = SVG_TOP + segment.start / 8 * SVG_ROW_HEIGHT;
+    let width = segment.len() * SVG_BIT_WIDTH;
+    svg.open("g", &[]);
+    svg.text("title", &[], title);
+    svg.empty(
+        "rect",
+        &[
+            ("x", &x.to_string()),
+            ("y", &y.to_string()),
+            ("width", &width.to_string()),
+            ("height", &SVG_ROW_HEIGHT.to_string()),
+            ("fill", fill),
+            ("stroke", "#333333"),
+        ],
+    );
+    if !label.is_empty() {
+        let text_x = (x + width / 2).to_string();
+        let text_y = (y + SVG_ROW_HEIGHT / 2 + 4).to_string();
+        let mut attributes = vec![
+            ("x", text_x.as_str()),
+            ("y", text_y.as_str()),
+            ("text-anchor", "middle"),
+        ];
+        // 矩形に収まらない名前は字間と字幅を詰める
+        let text_length = (width - 4).to_string();
+        if label.chars().count() * SVG_CHAR_WIDTH > width - 4 {
+            attributes.push(("textLength", &text_length));
+            attributes.push(("lengthAdjust", "spacingAndGlyphs"));
+        }
+        svg.text("text", &attributes, label);
+    }
+    svg.close("g");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let ascii = render_ascii(&telemetry).unwrap();
+        assert!(ascii.contains("-- group 1: uint16_t\n"));
+        assert!(ascii.contains("octet   0 aaabcddd  a=PH.VER b=PH.TYPE c=PH.SH_FLAG d=PH.APID\n"));
+        assert!(ascii.contains("octet   1 aaaaaaaa  a=PH.APID\n"));
+        assert!(!ascii.contains("gap: "));
+
+        let tlm::Content::Struct(entries) = &mut telemetry.content else {
+            unreachable!();
+        };
+        let tlm::Entry::FieldGroup(field_group) = &mut entries[0] else {
+            unreachable!();
+        };
+        let tlm::SubEntry::Field(field) = &mut field_group.sub_entries[1] else {
+            unreachable!();
+        };
+        // PH.TYPE を PH.VER に重ね、ビット3を空ける
+        field.extraction_info.bit_position = 2;
+        let ascii = render_ascii(&telemetry).unwrap();
+        assert!(ascii
+            .contains("octet   0 aa!.bccc  a=PH.VER b=PH.SH_FLAG c=PH.APID !=PH.VER, PH.TYPE\n"));
+        assert!(ascii.contains("gap: bits 3-3 (octet 0 bit 3, 1 bits)\n"));
+        assert!(ascii.contains("overlap: bits 2-2 (octet 0 bit 2, 1 bits): PH.VER, PH.TYPE\n"));
+
+        let svg = render_svg(&telemetry).unwrap();
+        assert!(svg.contains(
+            "<title>PH.APID (group 1: uint16_t) bits 5-15 (octet 0 bit 5, 11 bits)</title>"
+        ));
+        assert!(svg
+            .contains("<title>overlap: bits 2-2 (octet 0 bit 2, 1 bits): PH.VER, PH.TYPE</title>"));
+    }
+}
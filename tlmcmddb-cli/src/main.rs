@@ -1,14 +1,27 @@
+mod codegen;
+mod cosmos;
+mod docs;
+mod layout;
+mod manifest;
+mod xml;
+mod xtce;
+mod yamcs;
+
 use std::{
-    collections::{btree_map, BTreeMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     fs,
     io::{self, BufReader},
     path::{Path, PathBuf},
+    process,
 };
 
 use anyhow::{anyhow, Context, Result};
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use manifest::FileFilter;
 use notalawyer_clap::*;
+use serde::Deserialize;
 use tlmcmddb::Database;
+use tlmcmddb_csv::diagnostic::{self, Diagnostic};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -27,6 +40,32 @@ enum Command {
         pretty: bool,
         #[clap(long)]
         component_name: Option<String>,
+        /// Skip malformed rows and files and report all problems at the end
+        #[clap(long)]
+        keep_going: bool,
+        /// Report TLM DB rows whose Octet Pos. / bit Pos. disagree with the layout computed from the field order
+        #[clap(long, conflicts_with = "compute_positions")]
+        check_positions: bool,
+        /// Ignore the stored Octet Pos. / bit Pos. and compute them from the field order
+        #[clap(long)]
+        compute_positions: bool,
+        /// Read .xlsm/.xlsx workbooks in the directories instead of CSV files, using cached formula results
+        #[clap(long)]
+        workbook: bool,
+        /// How to handle files that define the same telemetry, CMD DB or BCT of a component
+        #[clap(long, value_enum, default_value_t = ConflictPolicy::Error)]
+        on_conflict: ConflictPolicy,
+    },
+    /// Bundle all components listed in a manifest (tlmcmddb.toml) into one TLM CMD DB JSON
+    Build {
+        #[clap(long, short, default_value = "tlmcmddb.toml")]
+        manifest: PathBuf,
+        /// Skip malformed rows and files and report all problems at the end
+        #[clap(long)]
+        keep_going: bool,
+        /// Report TLM DB rows whose Octet Pos. / bit Pos. disagree with the layout computed from the field order
+        #[clap(long)]
+        check_positions: bool,
     },
     Merge {
         #[clap(required = true)]
@@ -36,60 +75,372 @@ enum Command {
         #[clap(long)]
         pretty: bool,
     },
+    /// Check semantic consistency of a bundled TLM CMD DB JSON
+    Validate { tlmcmddb: PathBuf },
+    /// Report structural changes between two bundled TLM CMD DB JSONs
+    Diff {
+        old: PathBuf,
+        new: PathBuf,
+        #[clap(long, value_enum, default_value_t = DiffFormat::Text)]
+        format: DiffFormat,
+        /// Exit with an error if any breaking change is found
+        #[clap(long)]
+        deny_breaking: bool,
+    },
+    /// Generate C2A flight software sources from a bundled TLM CMD DB JSON
+    Codegen {
+        #[command(subcommand)]
+        target: CodegenTarget,
+    },
+    /// Generate reference documentation, one page per component
+    Docs {
+        tlmcmddb: PathBuf,
+        #[clap(required = true, long, short)]
+        output_dir: PathBuf,
+        #[clap(long, value_enum, default_value_t = DocsFormat::Markdown)]
+        format: DocsFormat,
+    },
+    /// Render the bit layout of a telemetry packet
+    Layout {
+        tlmcmddb: PathBuf,
+        component: String,
+        telemetry: String,
+        #[clap(long, value_enum, default_value_t = LayoutFormat::Ascii)]
+        format: LayoutFormat,
+        /// Write to a file instead of stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Export a bundled TLM CMD DB JSON to other ground system formats
+    Export {
+        #[command(subcommand)]
+        target: ExportTarget,
+    },
+    /// Import definitions from other ground system formats as a bundled TLM CMD DB JSON
+    Import {
+        #[command(subcommand)]
+        source: ImportSource,
+    },
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum DiffFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DocsFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum LayoutFormat {
+    Ascii,
+    Svg,
+}
+
+#[derive(Subcommand)]
+enum CodegenTarget {
+    /// Generate telemetry_definitions.{c,h} and tlm_code.h
+    CTlm {
+        tlmcmddb: PathBuf,
+        /// Component to generate. Can be omitted if the database has only one component
+        #[clap(long)]
+        component: Option<String>,
+        #[clap(required = true, long, short)]
+        output_dir: PathBuf,
+    },
+    /// Generate command_definitions.{c,h} and cmd_code.h
+    CCmd {
+        tlmcmddb: PathBuf,
+        /// Component to generate. Can be omitted if the database has only one component
+        #[clap(long)]
+        component: Option<String>,
+        #[clap(required = true, long, short)]
+        output_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExportTarget {
+    /// Export as an XTCE (CCSDS 660.0) SpaceSystem
+    Xtce {
+        tlmcmddb: PathBuf,
+        #[clap(required = true, long, short)]
+        output: PathBuf,
+        /// Name of the root SpaceSystem
+        #[clap(long, default_value = "C2A")]
+        name: String,
+    },
+    /// Export as an OpenC3 COSMOS target's cmd_tlm/{cmd,tlm}.txt
+    Cosmos {
+        tlmcmddb: PathBuf,
+        /// Component to export. Can be omitted if the database has only one component
+        #[clap(long)]
+        component: Option<String>,
+        #[clap(required = true, long, short)]
+        output_dir: PathBuf,
+    },
+    /// Export as Yamcs Mission Database spreadsheet sheets, one CSV file per sheet
+    Yamcs {
+        tlmcmddb: PathBuf,
+        /// Component to export. Can be omitted if the database has only one component
+        #[clap(long)]
+        component: Option<String>,
+        #[clap(required = true, long, short)]
+        output_dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum ImportSource {
+    /// Import a subset of XTCE (CCSDS 660.0). Unsupported constructs are reported and fail the import
+    Xtce {
+        xtce: PathBuf,
+        #[clap(required = true, long, short)]
+        output: PathBuf,
+        #[clap(long)]
+        pretty: bool,
+    },
+}
+
+const SUFFIX_CMD_DB: &str = "_CMD_DB.csv";
+const SUFFIX_BCT: &str = "_BCT.csv";
+/// CMD DB の xlsm のうち、`*_CMD_DB.csv` と `*_BCT.csv` に書き出されるシート
+const SHEET_CMD_DB: &str = "CMD_DB";
+const SHEET_BCT: &str = "BCT";
+
+/// 同じコンポーネントに、複数のファイルから同じ定義が与えられた場合の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Fail and report the colliding files
+    #[default]
+    Error,
+    /// Concatenate CMD DBs and BCTs in reading order, failing on duplicate command codes or BCIDs. Colliding telemetries still fail
+    Concat,
+    /// Use the definition read last and warn about the overridden files
+    Override,
+}
+
+/// 定義と、それを読み込んだファイル
+type Sourced<T> = (PathBuf, T);
+
 #[derive(Default)]
+struct ComponentBuilder {
+    telemetries: Vec<Sourced<tlmcmddb::tlm::Telemetry>>,
+    cmddbs: Vec<Sourced<tlmcmddb::cmd::Database>>,
+    bcts: Vec<Sourced<tlmcmddb::bct::Database>>,
+}
+
+/// 読み込んだファイルの定義をコンポーネントごとに集め、[ConflictPolicy] に従って1つの DB にまとめる
+///
+/// ファイルは読み込んだ順に扱うため、同じ入力からは常に同じ結果となる。
 pub struct DatabaseBuilder {
-    components: BTreeMap<String, tlmcmddb::Component>,
+    conflict_policy: ConflictPolicy,
+    components: BTreeMap<String, ComponentBuilder>,
+}
+
+fn format_paths<'a, T: 'a>(sources: impl IntoIterator<Item = &'a Sourced<T>>) -> String {
+    sources
+        .into_iter()
+        .map(|(path, _)| format!("{:?}", path))
+        .collect::<Vec<_>>()
+        .join(", ")
 }
 
 impl DatabaseBuilder {
-    fn add_telemetry(&mut self, component: String, telemetry: tlmcmddb::tlm::Telemetry) {
-        match self.components.entry(component) {
-            btree_map::Entry::Occupied(mut occupied) => {
-                occupied.get_mut().tlm.telemetries.push(telemetry);
-            }
-            btree_map::Entry::Vacant(vacant) => {
-                let component = tlmcmddb::Component {
-                    name: vacant.key().to_string(),
-                    tlm: tlmcmddb::tlm::Database {
-                        telemetries: vec![telemetry],
-                    },
-                    cmd: tlmcmddb::cmd::Database { entries: vec![] },
-                };
-                vacant.insert(component);
-            }
+    fn new(conflict_policy: ConflictPolicy) -> Self {
+        Self {
+            conflict_policy,
+            components: BTreeMap::new(),
         }
     }
 
-    fn add_cmddb(&mut self, component: String, cmddb: tlmcmddb::cmd::Database) {
-        match self.components.entry(component) {
-            btree_map::Entry::Occupied(mut occupied) => {
-                occupied.get_mut().cmd = cmddb;
+    fn add_telemetry(
+        &mut self,
+        component: String,
+        path: PathBuf,
+        telemetry: tlmcmddb::tlm::Telemetry,
+    ) {
+        let component = self.components.entry(component).or_default();
+        component.telemetries.push((path, telemetry));
+    }
+
+    fn add_cmddb(&mut self, component: String, path: PathBuf, cmddb: tlmcmddb::cmd::Database) {
+        let component = self.components.entry(component).or_default();
+        component.cmddbs.push((path, cmddb));
+    }
+
+    fn add_bct(&mut self, component: String, path: PathBuf, bct: tlmcmddb::bct::Database) {
+        let component = self.components.entry(component).or_default();
+        component.bcts.push((path, bct));
+    }
+
+    /// 衝突をすべて調べ、1つでも解決できないものがあればそれらを列挙したエラーを返す
+    fn build(self) -> Result<tlmcmddb::Database> {
+        let mut conflicts = vec![];
+        let mut components = vec![];
+        for (name, sources) in self.components {
+            let component = self
+                .conflict_policy
+                .build_component(name, sources, &mut conflicts);
+            components.push(component);
+        }
+        if !conflicts.is_empty() {
+            for conflict in &conflicts {
+                eprintln!("error: {}", conflict);
             }
-            btree_map::Entry::Vacant(vacant) => {
-                let component = tlmcmddb::Component {
-                    name: vacant.key().to_string(),
-                    tlm: tlmcmddb::tlm::Database {
-                        telemetries: vec![],
-                    },
-                    cmd: cmddb,
+            return Err(anyhow!("{} conflict(s) found", conflicts.len()));
+        }
+        Ok(tlmcmddb::Database { components })
+    }
+}
+
+impl ConflictPolicy {
+    fn build_component(
+        self,
+        name: String,
+        sources: ComponentBuilder,
+        conflicts: &mut Vec<String>,
+    ) -> tlmcmddb::Component {
+        let mut telemetries_by_name: BTreeMap<String, Vec<_>> = BTreeMap::new();
+        for (path, telemetry) in sources.telemetries {
+            telemetries_by_name
+                .entry(telemetry.name.clone())
+                .or_default()
+                .push((path, telemetry));
+        }
+        // BTreeMap の順、すなわち名前順に並ぶ
+        let telemetries = telemetries_by_name
+            .into_iter()
+            .filter_map(|(telemetry, sources)| {
+                let what = format!("telemetry {} of component {}", telemetry, name);
+                let concat = |_: Vec<Sourced<_>>, conflicts: &mut Vec<String>| {
+                    conflicts.push(format!(
+                        "{} is defined in multiple files, which cannot be concatenated",
+                        what
+                    ));
+                    None
                 };
-                vacant.insert(component);
+                self.resolve(&what, sources, concat, conflicts)
+            })
+            .collect();
+        let cmd = self
+            .resolve(
+                &format!("CMD DB of component {}", name),
+                sources.cmddbs,
+                |sources, conflicts| concat_cmddbs(&name, sources, conflicts),
+                conflicts,
+            )
+            .unwrap_or(tlmcmddb::cmd::Database { entries: vec![] });
+        let bct = self
+            .resolve(
+                &format!("BCT of component {}", name),
+                sources.bcts,
+                |sources, conflicts| concat_bcts(&name, sources, conflicts),
+                conflicts,
+            )
+            .unwrap_or_default();
+        tlmcmddb::Component {
+            name,
+            tlm: tlmcmddb::tlm::Database { telemetries },
+            cmd,
+            bct,
+        }
+    }
+
+    /// `sources` が複数ある場合に、この方針に従って1つを選ぶか、`concat` で連結する
+    fn resolve<T>(
+        self,
+        what: &str,
+        mut sources: Vec<Sourced<T>>,
+        concat: impl FnOnce(Vec<Sourced<T>>, &mut Vec<String>) -> Option<T>,
+        conflicts: &mut Vec<String>,
+    ) -> Option<T> {
+        if sources.len() <= 1 {
+            return sources.pop().map(|(_, definition)| definition);
+        }
+        match self {
+            ConflictPolicy::Error => {
+                conflicts.push(format!(
+                    "{} is defined in multiple files: {}",
+                    what,
+                    format_paths(&sources)
+                ));
+                None
+            }
+            ConflictPolicy::Concat => concat(sources, conflicts),
+            ConflictPolicy::Override => {
+                let (path, definition) = sources.pop().unwrap();
+                eprintln!(
+                    "warning: {} in {:?} overrides the one in {}",
+                    what,
+                    path,
+                    format_paths(&sources)
+                );
+                Some(definition)
             }
         }
     }
+}
 
-    fn build(self) -> tlmcmddb::Database {
-        let mut components: Vec<_> = self.components.into_values().collect();
-        for component in components.iter_mut() {
-            component
-                .tlm
-                .telemetries
-                .sort_by(|a, b| a.name.cmp(&b.name));
+/// CMD DB を連結し、コマンドのコードの重複を調べる
+fn concat_cmddbs(
+    component: &str,
+    sources: Vec<Sourced<tlmcmddb::cmd::Database>>,
+    conflicts: &mut Vec<String>,
+) -> Option<tlmcmddb::cmd::Database> {
+    let mut codes: HashMap<u16, (String, PathBuf)> = HashMap::new();
+    let mut entries = vec![];
+    let mut ok = true;
+    for (path, cmddb) in sources {
+        for command in cmddb.commands() {
+            if let Some((other, other_path)) =
+                codes.insert(command.code, (command.name.clone(), path.clone()))
+            {
+                conflicts.push(format!(
+                    "command code 0x{:04X} of component {} is used by both {} in {:?} and {} in {:?}",
+                    command.code, component, other, other_path, command.name, path
+                ));
+                ok = false;
+            }
         }
-        tlmcmddb::Database { components }
+        entries.extend(cmddb.entries);
     }
+    ok.then_some(tlmcmddb::cmd::Database { entries })
+}
+
+/// BCT を連結し、BCID の重複を調べる
+fn concat_bcts(
+    component: &str,
+    sources: Vec<Sourced<tlmcmddb::bct::Database>>,
+    conflicts: &mut Vec<String>,
+) -> Option<tlmcmddb::bct::Database> {
+    let mut bcids: HashMap<u16, (String, PathBuf)> = HashMap::new();
+    let mut entries = vec![];
+    let mut ok = true;
+    for (path, bct) in sources {
+        for entry in &bct.entries {
+            let tlmcmddb::bct::Entry::BlockCommand(block_command) = entry else {
+                continue;
+            };
+            if let Some((other, other_path)) = bcids.insert(
+                block_command.bcid,
+                (block_command.name.clone(), path.clone()),
+            ) {
+                conflicts.push(format!(
+                    "BCID {} of component {} is used by both {} in {:?} and {} in {:?}",
+                    block_command.bcid, component, other, other_path, block_command.name, path
+                ));
+                ok = false;
+            }
+        }
+        entries.extend(bct.entries);
+    }
+    ok.then_some(tlmcmddb::bct::Database { entries })
 }
 
 #[derive(Default, Debug)]
@@ -137,6 +488,17 @@ impl DatabaseSet {
 
 fn main() -> Result<()> {
     let cli = Cli::parse_with_license_notice(include_notice!());
+    if let Err(err) = run(cli) {
+        if let Some(diagnostic) = err.downcast_ref::<Diagnostic>() {
+            eprint!("{}", diagnostic.render());
+            process::exit(1);
+        }
+        return Err(err);
+    }
+    Ok(())
+}
+
+fn run(cli: Cli) -> Result<()> {
     match cli.command {
         Command::Bundle {
             tlm_db_dir,
@@ -144,58 +506,71 @@ fn main() -> Result<()> {
             output,
             pretty,
             component_name,
+            keep_going,
+            check_positions,
+            compute_positions,
+            workbook,
+            on_conflict,
         } => {
-            let mut builder = DatabaseBuilder::default();
-            for entry in fs::read_dir(tlm_db_dir)? {
-                let entry = entry?;
-                if entry.file_type()?.is_dir() {
-                    continue;
-                }
-                let filename = entry.file_name();
-                let filename = filename.to_str().unwrap();
-                if !filename.ends_with(".csv") {
-                    // ignore non-csv files
-                    continue;
-                }
-                let ctx = format!("TLM DB CSV: {:?}", entry.path());
-                let tlmcmddb_csv::tlm::Filename {
-                    component,
-                    telemetry,
-                } = filename.parse().context(ctx.clone())?;
-                let component = component_name
-                    .clone()
-                    .or(component)
-                    .ok_or_else(|| anyhow!("filename must contain component name"))?;
-                let file = fs::OpenOptions::new()
-                    .read(true)
-                    .open(entry.path())
-                    .context(ctx.clone())?;
-                let telemetry =
-                    tlmcmddb_csv::tlm::parse_csv(telemetry, file).context(ctx.clone())?;
-                builder.add_telemetry(component, telemetry);
-            }
-            for entry in fs::read_dir(cmd_db_dir)? {
-                let entry = entry?;
-                if entry.file_type()?.is_dir() {
-                    continue;
-                }
-                let filename = entry.file_name();
-                let filename = filename.to_str().unwrap();
-                if !filename.ends_with("_CMD_DB.csv") {
-                    // ignore non-command files
-                    continue;
-                }
-                let ctx = format!("CMD DB CSV: {:?}", entry.path());
-                let file = fs::OpenOptions::new()
-                    .read(true)
-                    .open(entry.path())
-                    .context(ctx.clone())?;
-                let (component, cmddb) = tlmcmddb_csv::cmd::parse_csv(file).context(ctx.clone())?;
-                builder.add_cmddb(component, cmddb);
-            }
-            let db = builder.build();
+            let mut builder = DatabaseBuilder::new(on_conflict);
+            let mut problems = Problems::new(keep_going);
+            let sources = Sources {
+                tlm_db_dir: Some(&tlm_db_dir),
+                cmd_db_dir: Some(&cmd_db_dir),
+                bct: None,
+                component_name: component_name.as_deref(),
+                override_cmd_component: false,
+                workbook,
+                filter: &FileFilter::all(),
+            };
+            let options = BundleOptions {
+                check_positions,
+                compute_positions,
+            };
+            bundle_sources(&sources, &options, &mut builder, &mut problems)?;
+            problems.finish()?;
+            let db = builder.build()?;
             output_db(db, &output, pretty)?;
         }
+        Command::Build {
+            manifest,
+            keep_going,
+            check_positions,
+        } => {
+            let manifest = manifest::load(&manifest)?;
+            if check_positions && manifest.compute_positions {
+                return Err(anyhow!(
+                    "--check-positions cannot be used with compute_positions in the manifest"
+                ));
+            }
+            let mut builder = DatabaseBuilder::new(manifest.on_conflict);
+            let mut problems = Problems::new(keep_going);
+            let options = BundleOptions {
+                check_positions,
+                compute_positions: manifest.compute_positions,
+            };
+            for component in &manifest.components {
+                let filter = component.filter()?;
+                let sources = Sources {
+                    tlm_db_dir: component.tlm_db_dir.as_deref(),
+                    cmd_db_dir: component.cmd_db_dir.as_deref(),
+                    bct: component.bct.as_deref(),
+                    component_name: component.name.as_deref(),
+                    override_cmd_component: component.name.is_some(),
+                    workbook: component.workbook,
+                    filter: &filter,
+                };
+                bundle_sources(&sources, &options, &mut builder, &mut problems)?;
+            }
+            problems.finish()?;
+            let db = builder.build()?;
+            // manifest の出力先は `build/` などのディレクトリの下に置かれることが多い
+            if let Some(output_dir) = manifest.output.parent() {
+                fs::create_dir_all(output_dir)
+                    .with_context(|| format!("creating output directory: {:?}", output_dir))?;
+            }
+            output_db(db, &manifest.output, manifest.pretty)?;
+        }
         Command::Merge {
             tlmcmddbs,
             output,
@@ -203,23 +578,578 @@ fn main() -> Result<()> {
         } => {
             let mut datbase_set = DatabaseSet::default();
             for entry_path in tlmcmddbs {
-                let ctx = format!("TLM CMD DB Json: {:?}", entry_path);
-                let file = fs::OpenOptions::new()
-                    .read(true)
-                    .open(entry_path)
-                    .context(ctx.clone())?;
-                let reader = BufReader::new(file);
-                let database: Database = serde_json::from_reader(reader)?;
-
+                let database = read_db(&entry_path)?;
                 datbase_set.push_database(database);
             }
             let db = datbase_set.merge()?;
             output_db(db, &output, pretty)?;
         }
+        Command::Validate { tlmcmddb } => {
+            let db = read_db(&tlmcmddb)?;
+            let findings = tlmcmddb::validate::validate(&db);
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            if !findings.is_empty() {
+                return Err(anyhow!("{} problem(s) found", findings.len()));
+            }
+        }
+        Command::Diff {
+            old,
+            new,
+            format,
+            deny_breaking,
+        } => {
+            let old = read_db(&old)?;
+            let new = read_db(&new)?;
+            let changes = tlmcmddb::diff::diff(&old, &new);
+            match format {
+                DiffFormat::Text => {
+                    for change in &changes {
+                        println!("{}", change);
+                    }
+                }
+                DiffFormat::Json => {
+                    println!("{}", serde_json::to_string_pretty(&changes)?);
+                }
+            }
+            let breaking = changes
+                .iter()
+                .filter(|change| change.compatibility == tlmcmddb::diff::Compatibility::Breaking)
+                .count();
+            if deny_breaking && breaking > 0 {
+                return Err(anyhow!("{} breaking change(s) found", breaking));
+            }
+        }
+        Command::Codegen { target } => match target {
+            CodegenTarget::CTlm {
+                tlmcmddb,
+                component,
+                output_dir,
+            } => {
+                let db = read_db(&tlmcmddb)?;
+                let component = codegen::select_component(&db, component.as_deref())?;
+                let files = codegen::c_tlm::generate(component)?;
+                codegen::write_files(&output_dir, &files)?;
+            }
+            CodegenTarget::CCmd {
+                tlmcmddb,
+                component,
+                output_dir,
+            } => {
+                let db = read_db(&tlmcmddb)?;
+                let component = codegen::select_component(&db, component.as_deref())?;
+                let files = codegen::c_cmd::generate(component)?;
+                codegen::write_files(&output_dir, &files)?;
+            }
+        },
+        Command::Docs {
+            tlmcmddb,
+            output_dir,
+            format,
+        } => {
+            let db = read_db(&tlmcmddb)?;
+            let format = match format {
+                DocsFormat::Markdown => docs::Format::Markdown,
+                DocsFormat::Html => docs::Format::Html,
+            };
+            let files = docs::generate(&db, format);
+            codegen::write_files(&output_dir, &files)?;
+        }
+        Command::Layout {
+            tlmcmddb,
+            component,
+            telemetry,
+            format,
+            output,
+        } => {
+            let db = read_db(&tlmcmddb)?;
+            let component = codegen::select_component(&db, Some(&component))?;
+            let telemetry = component
+                .tlm
+                .telemetries
+                .iter()
+                .find(|t| t.name == telemetry)
+                .ok_or_else(|| anyhow!("telemetry {} is not found", telemetry))?;
+            let rendered = match format {
+                LayoutFormat::Ascii => layout::render_ascii(telemetry)?,
+                LayoutFormat::Svg => layout::render_svg(telemetry)?,
+            };
+            match output {
+                Some(output) => {
+                    fs::write(&output, rendered).with_context(|| format!("writing {:?}", output))?
+                }
+                None => print!("{}", rendered),
+            }
+        }
+        Command::Export { target } => match target {
+            ExportTarget::Xtce {
+                tlmcmddb,
+                output,
+                name,
+            } => {
+                let db = read_db(&tlmcmddb)?;
+                let xml = xtce::export::export(&db, &name)?;
+                fs::write(&output, xml).with_context(|| format!("writing {:?}", output))?;
+            }
+            ExportTarget::Cosmos {
+                tlmcmddb,
+                component,
+                output_dir,
+            } => {
+                let db = read_db(&tlmcmddb)?;
+                let component = codegen::select_component(&db, component.as_deref())?;
+                let files = cosmos::generate(component)?;
+                codegen::write_files(&output_dir, &files)?;
+            }
+            ExportTarget::Yamcs {
+                tlmcmddb,
+                component,
+                output_dir,
+            } => {
+                let db = read_db(&tlmcmddb)?;
+                let component = codegen::select_component(&db, component.as_deref())?;
+                let files = yamcs::generate(component)?;
+                codegen::write_files(&output_dir, &files)?;
+            }
+        },
+        Command::Import { source } => match source {
+            ImportSource::Xtce {
+                xtce,
+                output,
+                pretty,
+            } => {
+                let xml =
+                    fs::read_to_string(&xtce).with_context(|| format!("reading {:?}", xtce))?;
+                let (db, diagnostics) =
+                    xtce::import::import(&xml).with_context(|| format!("XTCE: {:?}", xtce))?;
+                let mut problems = Problems::new(true);
+                problems.extend(diagnostics, &xtce);
+                problems.finish()?;
+                output_db(db, &output, pretty)?;
+            }
+        },
     }
     Ok(())
 }
 
+/// `bundle` や `import` で見つかった問題
+///
+/// `keep_going` でなければ最初の問題でそのまま失敗する。
+struct Problems {
+    keep_going: bool,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Problems {
+    fn new(keep_going: bool) -> Self {
+        Self {
+            keep_going,
+            diagnostics: vec![],
+        }
+    }
+
+    /// ファイル全体を読み込めなかったことを報告する
+    fn report(&mut self, err: anyhow::Error, path: &Path) -> Result<()> {
+        if !self.keep_going {
+            return Err(err);
+        }
+        self.diagnostics
+            .push(diagnostic::with_path(err, path).into());
+        Ok(())
+    }
+
+    /// 読み飛ばした行を報告する
+    fn extend(&mut self, diagnostics: Vec<Diagnostic>, path: &Path) {
+        self.diagnostics
+            .extend(diagnostics.into_iter().map(|diagnostic| Diagnostic {
+                path: Some(path.to_path_buf()),
+                ..diagnostic
+            }));
+    }
+
+    /// 見つかった問題をすべて表示し、問題があれば失敗する
+    fn finish(self) -> Result<()> {
+        for diagnostic in &self.diagnostics {
+            eprintln!("{}", diagnostic.render());
+        }
+        if !self.diagnostics.is_empty() {
+            return Err(anyhow!("{} problem(s) found", self.diagnostics.len()));
+        }
+        Ok(())
+    }
+}
+
+/// `bundle` と `build` で共通の読み込みの設定
+struct BundleOptions {
+    check_positions: bool,
+    compute_positions: bool,
+}
+
+/// 1組の TLM DB・CMD DB の読み込み元
+struct Sources<'a> {
+    tlm_db_dir: Option<&'a Path>,
+    cmd_db_dir: Option<&'a Path>,
+    /// 指定した場合、`cmd_db_dir` の `*_BCT.csv` の代わりに読み込む BCT CSV
+    bct: Option<&'a Path>,
+    /// TLM DB のファイル名に書かれた component 名の代わりに用いる名前
+    component_name: Option<&'a str>,
+    /// CMD DB に書かれた component 名の代わりにも `component_name` を用いる
+    override_cmd_component: bool,
+    workbook: bool,
+    filter: &'a FileFilter,
+}
+
+/// ディレクトリのエントリをファイル名順に返す
+///
+/// [ConflictPolicy] で衝突を解決する際の順序が、ファイルシステムによらず定まるようにする。
+fn sorted_dir_entries(dir: &Path) -> Result<Vec<fs::DirEntry>> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    Ok(entries)
+}
+
+/// `sources` のファイルを読み込み、`builder` に加える
+fn bundle_sources(
+    sources: &Sources,
+    options: &BundleOptions,
+    builder: &mut DatabaseBuilder,
+    problems: &mut Problems,
+) -> Result<()> {
+    let mut telemetries = vec![];
+    if let Some(tlm_db_dir) = sources.tlm_db_dir {
+        let ctx = format!("TLM DB directory: {:?}", tlm_db_dir);
+        for entry in sorted_dir_entries(tlm_db_dir).context(ctx)? {
+            if entry.file_type()?.is_dir() {
+                continue;
+            }
+            let filename = entry.file_name();
+            let filename = filename.to_str().unwrap();
+            if !sources.filter.matches(filename) {
+                continue;
+            }
+            let path = entry.path();
+            if sources.workbook {
+                let Some(stem) = workbook_stem(filename) else {
+                    // ignore non-workbook files
+                    continue;
+                };
+                if let Err(err) = read_tlm_workbook(
+                    &path,
+                    stem,
+                    sources.component_name,
+                    options.check_positions,
+                    &mut telemetries,
+                    problems,
+                ) {
+                    problems.report(err, &path)?;
+                }
+                continue;
+            }
+            if !filename.ends_with(".csv") {
+                // ignore non-csv files
+                continue;
+            }
+            match read_tlm_csv(&path, filename, sources.component_name, problems) {
+                Ok((component, telemetry)) => {
+                    telemetries.push((component, path.clone(), telemetry));
+                    // 読み込めなかった CSV は既に報告されているため、読み込めたものだけ確かめる
+                    if options.check_positions {
+                        match check_tlm_csv_positions(&path) {
+                            Ok(diagnostics) => problems.extend(diagnostics, &path),
+                            Err(err) => problems.report(err, &path)?,
+                        }
+                    }
+                }
+                Err(err) => problems.report(err, &path)?,
+            }
+        }
+    }
+    for (component, path, mut telemetry) in telemetries {
+        if let tlmcmddb::tlm::Content::Struct(entries) = &mut telemetry.content {
+            if options.compute_positions {
+                tlmcmddb::tlm::position::assign(entries);
+            }
+        }
+        builder.add_telemetry(component, path, telemetry);
+    }
+    // CMD DB に書かれた component 名を、manifest の指定に応じて置き換える
+    let cmd_component = |component: String| match sources.component_name {
+        Some(name) if sources.override_cmd_component => name.to_string(),
+        _ => component,
+    };
+    // BCT CSV には component 名が書かれていないため、ファイル名の接頭辞が同じ CMD DB CSV のものを用いる
+    let mut cmd_db_components = HashMap::new();
+    let mut bcts = vec![];
+    if let Some(cmd_db_dir) = sources.cmd_db_dir {
+        let ctx = format!("CMD DB directory: {:?}", cmd_db_dir);
+        for entry in sorted_dir_entries(cmd_db_dir).context(ctx)? {
+            if entry.file_type()?.is_dir() {
+                continue;
+            }
+            let filename = entry.file_name();
+            let filename = filename.to_str().unwrap();
+            if !sources.filter.matches(filename) {
+                continue;
+            }
+            let path = entry.path();
+            if sources.workbook {
+                let Some(stem) = workbook_stem(filename) else {
+                    // ignore non-workbook files
+                    continue;
+                };
+                // ワークブックのシートは、CSV 書き出しでの名前 `{stem}_{sheet}.csv` のファイルと同様に扱う
+                let mut book = match tlmcmddb_csv::xlsx::open(&path) {
+                    Ok(book) => book,
+                    Err(err) => {
+                        problems.report(err, &path)?;
+                        continue;
+                    }
+                };
+                let sheet_path = tlmcmddb_csv::xlsx::sheet_path(&path, SHEET_CMD_DB);
+                match read_cmd_sheet(&mut book, &sheet_path, problems) {
+                    Ok((component, cmddb)) => {
+                        let component = cmd_component(component);
+                        cmd_db_components.insert(stem.to_string(), component.clone());
+                        builder.add_cmddb(component, sheet_path.clone(), cmddb);
+                    }
+                    Err(err) => problems.report(err, &sheet_path)?,
+                }
+                if sources.bct.is_none() && book.sheet_names().iter().any(|name| name == SHEET_BCT)
+                {
+                    let sheet_path = tlmcmddb_csv::xlsx::sheet_path(&path, SHEET_BCT);
+                    let bct = read_bct_sheet(&mut book, &sheet_path);
+                    bcts.push((Some(stem.to_string()), sheet_path, bct));
+                }
+                continue;
+            }
+            if let Some(prefix) = filename.strip_suffix(SUFFIX_BCT) {
+                if sources.bct.is_none() {
+                    bcts.push((Some(prefix.to_string()), path.clone(), read_bct_csv(&path)));
+                }
+                continue;
+            }
+            let Some(prefix) = filename.strip_suffix(SUFFIX_CMD_DB) else {
+                // ignore non-command files
+                continue;
+            };
+            match read_cmd_csv(&path, problems) {
+                Ok((component, cmddb)) => {
+                    let component = cmd_component(component);
+                    cmd_db_components.insert(prefix.to_string(), component.clone());
+                    builder.add_cmddb(component, path.clone(), cmddb);
+                }
+                Err(err) => problems.report(err, &path)?,
+            }
+        }
+    }
+    if let Some(path) = sources.bct {
+        bcts.push((None, path.to_path_buf(), read_bct_csv(path)));
+    }
+    for (prefix, path, bct) in bcts {
+        let component = match &prefix {
+            Some(prefix) => cmd_db_components.get(prefix).cloned(),
+            // 明示された BCT CSV は、CMD DB が1つだけであればその component のものとする
+            None if cmd_db_components.len() == 1 => cmd_db_components.values().next().cloned(),
+            None => None,
+        }
+        .or(sources.component_name.map(str::to_string))
+        .ok_or_else(|| match &prefix {
+            Some(prefix) => anyhow!(
+                "component of BCT CSV is unknown: {}{} is not found",
+                prefix,
+                SUFFIX_CMD_DB
+            ),
+            None => anyhow!("component of BCT CSV is unknown: specify the component name"),
+        });
+        match component.and_then(|component| bct.map(|bct| (component, bct))) {
+            Ok((component, bct)) => builder.add_bct(component, path, bct),
+            Err(err) => problems.report(err, &path)?,
+        }
+    }
+    Ok(())
+}
+
+fn read_tlm_csv(
+    path: &Path,
+    filename: &str,
+    component_name: Option<&str>,
+    problems: &mut Problems,
+) -> Result<(String, tlmcmddb::tlm::Telemetry)> {
+    let ctx = format!("TLM DB CSV: {:?}", path);
+    let tlmcmddb_csv::tlm::Filename {
+        component,
+        telemetry,
+    } = filename.parse().context(ctx.clone())?;
+    let component = component_name
+        .map(str::to_string)
+        .or(component)
+        .ok_or_else(|| anyhow!("filename must contain component name"))?;
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context(ctx.clone())?;
+    let telemetry = if problems.keep_going {
+        let (telemetry, diagnostics) = tlmcmddb_csv::tlm::parse_csv_recovering(telemetry, file)?;
+        problems.extend(diagnostics, path);
+        telemetry
+    } else {
+        tlmcmddb_csv::tlm::parse_csv(telemetry, file)
+            .map_err(|err| diagnostic::with_path(err, path))?
+    };
+    Ok((component, telemetry))
+}
+
+/// `.xlsm` または `.xlsx` であれば、拡張子を除いたファイル名
+fn workbook_stem(filename: &str) -> Option<&str> {
+    filename
+        .strip_suffix(".xlsm")
+        .or_else(|| filename.strip_suffix(".xlsx"))
+}
+
+/// TLM DB のワークブックのテレメトリ定義のシートをすべて読み込み、`telemetries` に加える
+///
+/// シートは、CSV 書き出しでの名前 `{stem}_{sheet}.csv` のファイルと同様に扱う。
+/// 個々のシートの問題は `problems` に報告する。
+fn read_tlm_workbook(
+    path: &Path,
+    stem: &str,
+    component_name: Option<&str>,
+    check_positions: bool,
+    telemetries: &mut Vec<(String, PathBuf, tlmcmddb::tlm::Telemetry)>,
+    problems: &mut Problems,
+) -> Result<()> {
+    let mut book = tlmcmddb_csv::xlsx::open(path)?;
+    for sheet in book.sheet_names() {
+        if tlmcmddb_csv::xlsx::TLM_DB_NON_TELEMETRY_SHEETS.contains(&sheet.as_str()) {
+            continue;
+        }
+        let sheet_path = tlmcmddb_csv::xlsx::sheet_path(path, &sheet);
+        let filename = format!("{}_{}.csv", stem, sheet);
+        match read_tlm_sheet(
+            &mut book,
+            &sheet,
+            &sheet_path,
+            &filename,
+            component_name,
+            problems,
+        ) {
+            Ok((component, telemetry, records)) => {
+                telemetries.push((component, sheet_path.clone(), telemetry));
+                if check_positions {
+                    let iter = records.into_iter().map(Ok::<_, csv::Error>);
+                    match tlmcmddb_csv::tlm::telemetry::check_positions(iter) {
+                        Ok(diagnostics) => problems.extend(diagnostics, &sheet_path),
+                        Err(err) => problems.report(err, &sheet_path)?,
+                    }
+                }
+            }
+            Err(err) => problems.report(err, &sheet_path)?,
+        }
+    }
+    Ok(())
+}
+
+/// テレメトリ定義のシートを読み込み、位置の確認に用いるためにシートの行も返す
+fn read_tlm_sheet(
+    book: &mut tlmcmddb_csv::xlsx::Workbook<BufReader<fs::File>>,
+    sheet: &str,
+    sheet_path: &Path,
+    filename: &str,
+    component_name: Option<&str>,
+    problems: &mut Problems,
+) -> Result<(String, tlmcmddb::tlm::Telemetry, Vec<csv::StringRecord>)> {
+    let tlmcmddb_csv::tlm::Filename {
+        component,
+        telemetry,
+    } = filename.parse()?;
+    let component = component_name
+        .map(str::to_string)
+        .or(component)
+        .ok_or_else(|| anyhow!("workbook name must contain component name"))?;
+    let records = book.records(sheet)?;
+    let iter = records.iter().cloned().map(Ok::<_, csv::Error>);
+    let telemetry = if problems.keep_going {
+        let (telemetry, diagnostics) =
+            tlmcmddb_csv::tlm::telemetry::parse_recovering(telemetry, iter)?;
+        problems.extend(diagnostics, sheet_path);
+        telemetry
+    } else {
+        tlmcmddb_csv::tlm::telemetry::parse(telemetry, iter)
+            .map_err(|err| diagnostic::with_path(err, sheet_path))?
+    };
+    Ok((component, telemetry, records))
+}
+
+fn read_cmd_sheet(
+    book: &mut tlmcmddb_csv::xlsx::Workbook<BufReader<fs::File>>,
+    sheet_path: &Path,
+    problems: &mut Problems,
+) -> Result<(String, tlmcmddb::cmd::Database)> {
+    let records = book.records(SHEET_CMD_DB)?;
+    let iter = records.into_iter().map(Ok::<_, csv::Error>);
+    if problems.keep_going {
+        let (component, cmddb, diagnostics) = tlmcmddb_csv::cmd::parse_recovering(iter)?;
+        problems.extend(diagnostics, sheet_path);
+        Ok((component, cmddb))
+    } else {
+        tlmcmddb_csv::cmd::parse(iter).map_err(|err| diagnostic::with_path(err, sheet_path))
+    }
+}
+
+fn read_bct_sheet(
+    book: &mut tlmcmddb_csv::xlsx::Workbook<BufReader<fs::File>>,
+    sheet_path: &Path,
+) -> Result<tlmcmddb::bct::Database> {
+    let records = book.records(SHEET_BCT)?;
+    tlmcmddb_csv::bct::parse(records.into_iter().map(Ok::<_, csv::Error>))
+        .map_err(|err| diagnostic::with_path(err, sheet_path))
+}
+
+fn check_tlm_csv_positions(path: &Path) -> Result<Vec<Diagnostic>> {
+    let ctx = format!("TLM DB CSV: {:?}", path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context(ctx.clone())?;
+    tlmcmddb_csv::tlm::check_positions(file).map_err(|err| diagnostic::with_path(err, path))
+}
+
+fn read_cmd_csv(path: &Path, problems: &mut Problems) -> Result<(String, tlmcmddb::cmd::Database)> {
+    let ctx = format!("CMD DB CSV: {:?}", path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context(ctx.clone())?;
+    if problems.keep_going {
+        let (component, cmddb, diagnostics) = tlmcmddb_csv::cmd::parse_csv_recovering(file)?;
+        problems.extend(diagnostics, path);
+        Ok((component, cmddb))
+    } else {
+        tlmcmddb_csv::cmd::parse_csv(file).map_err(|err| diagnostic::with_path(err, path))
+    }
+}
+
+fn read_bct_csv(path: &Path) -> Result<tlmcmddb::bct::Database> {
+    let ctx = format!("BCT CSV: {:?}", path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context(ctx.clone())?;
+    tlmcmddb_csv::bct::parse_csv(file).map_err(|err| diagnostic::with_path(err, path))
+}
+
+fn read_db(path: &Path) -> Result<Database> {
+    let ctx = format!("TLM CMD DB Json: {:?}", path);
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .open(path)
+        .context(ctx.clone())?;
+    let reader = BufReader::new(file);
+    let database = serde_json::from_reader(reader).context(ctx)?;
+    Ok(database)
+}
+
 fn output_db(db: Database, output: &Path, pretty: bool) -> Result<()> {
     let output_file = fs::OpenOptions::new()
         .create(true)
@@ -227,7 +1157,8 @@ fn output_db(db: Database, output: &Path, pretty: bool) -> Result<()> {
         .append(false)
         .read(true)
         .truncate(true)
-        .open(output)?;
+        .open(output)
+        .with_context(|| format!("writing {:?}", output))?;
     let bufwriter = io::BufWriter::new(output_file);
     if pretty {
         serde_json::to_writer_pretty(bufwriter, &db).map_err(|e| anyhow::anyhow!(e))
@@ -235,3 +1166,54 @@ fn output_db(db: Database, output: &Path, pretty: bool) -> Result<()> {
         serde_json::to_writer(bufwriter, &db).map_err(|e| anyhow::anyhow!(e))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_cmddb() -> tlmcmddb::cmd::Database {
+        let csv = fs::read("../tlm-cmd-db/CMD_DB/SAMPLE_CMD_DB_CMD_DB.csv").unwrap();
+        tlmcmddb_csv::cmd::parse_csv(csv.as_slice()).unwrap().1
+    }
+
+    fn builder(policy: ConflictPolicy, cmddbs: Vec<tlmcmddb::cmd::Database>) -> DatabaseBuilder {
+        let mut builder = DatabaseBuilder::new(policy);
+        for (i, cmddb) in cmddbs.into_iter().enumerate() {
+            let path = PathBuf::from(format!("{}_CMD_DB.csv", i));
+            builder.add_cmddb("MOBC".to_string(), path, cmddb);
+        }
+        builder
+    }
+
+    #[test]
+    fn test_conflict_policy() {
+        let cmddb = sample_cmddb();
+        let num_entries = cmddb.entries.len();
+        let (first, second) = cmddb.entries.split_at(num_entries / 2);
+        let first = tlmcmddb::cmd::Database {
+            entries: first.to_vec(),
+        };
+        let second = tlmcmddb::cmd::Database {
+            entries: second.to_vec(),
+        };
+
+        let both = || vec![first.clone(), second.clone()];
+        assert!(builder(ConflictPolicy::Error, both()).build().is_err());
+        let db = builder(ConflictPolicy::Concat, both()).build().unwrap();
+        assert_eq!(num_entries, db.components[0].cmd.entries.len());
+        let db = builder(ConflictPolicy::Override, both()).build().unwrap();
+        assert_eq!(second, db.components[0].cmd);
+
+        // 同じコマンドのコードをもつ CMD DB は連結できない
+        let duplicated = vec![first.clone(), first.clone()];
+        assert!(builder(ConflictPolicy::Concat, duplicated).build().is_err());
+
+        // テレメトリは連結できない
+        let csv = fs::read("../tlm-cmd-db/TLM_DB/calced_data/SAMPLE_TLM_DB_HK.csv").unwrap();
+        let hk = tlmcmddb_csv::tlm::parse_csv("HK".to_string(), csv.as_slice()).unwrap();
+        let mut builder = builder(ConflictPolicy::Concat, vec![]);
+        builder.add_telemetry("MOBC".to_string(), "a.csv".into(), hk.clone());
+        builder.add_telemetry("MOBC".to_string(), "b.csv".into(), hk);
+        assert!(builder.build().is_err());
+    }
+}
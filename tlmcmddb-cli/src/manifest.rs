@@ -0,0 +1,185 @@
+//! `tlmcmddb build` の manifest（`tlmcmddb.toml`）
+//!
+//! 衛星を構成する各コンポーネントの TLM DB・CMD DB の読み込み元を列挙し、衛星全体の DB を一度に生成するために用いる。
+//! 相対パスは manifest のあるディレクトリを基準とする。
+//!
+//! ```toml
+//! output = "build/tlmcmddb.json"
+//! pretty = true
+//! on_conflict = "error"
+//!
+//! [[components]]
+//! name = "MOBC"
+//! tlm_db_dir = "MOBC/TLM_DB/calced_data"
+//! cmd_db_dir = "MOBC/CMD_DB"
+//! exclude = ["*_TEMPLATE.csv"]
+//!
+//! [[components]]
+//! name = "AOBC"
+//! tlm_db_dir = "AOBC/TLM_DB"
+//! cmd_db_dir = "AOBC/CMD_DB"
+//! workbook = true
+//! ```
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use glob::Pattern;
+use serde::Deserialize;
+
+use crate::ConflictPolicy;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    /// 出力する TLM CMD DB JSON のパス
+    pub output: PathBuf,
+    #[serde(default)]
+    pub pretty: bool,
+    /// 格納されている Octet Pos. / bit Pos. の代わりに、フィールドの並びから計算した位置を用いる
+    #[serde(default)]
+    pub compute_positions: bool,
+    /// 複数のファイルが同じ定義を与えた場合の扱い。`components` の順に読み込む
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+    pub components: Vec<ComponentSource>,
+}
+
+/// 1組の TLM DB・CMD DB の読み込み元
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ComponentSource {
+    /// component 名。指定した場合、ファイル名や CMD DB に書かれた名前の代わりに用いる
+    pub name: Option<String>,
+    /// TLM DB CSV（`workbook` の場合は xlsm）を置いたディレクトリ
+    pub tlm_db_dir: Option<PathBuf>,
+    /// CMD DB CSV と BCT CSV（`workbook` の場合は xlsm）を置いたディレクトリ
+    pub cmd_db_dir: Option<PathBuf>,
+    /// BCT CSV。指定した場合、`cmd_db_dir` の BCT の代わりに用いる
+    pub bct: Option<PathBuf>,
+    /// CSV の代わりに xlsm / xlsx を読み込む
+    #[serde(default)]
+    pub workbook: bool,
+    /// 読み込むファイル名の glob。省略した場合はすべてのファイルを読み込む
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// 読み込まないファイル名の glob。`include` より優先する
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl ComponentSource {
+    pub fn filter(&self) -> Result<FileFilter> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| {
+                    Pattern::new(pattern).with_context(|| format!("invalid glob: {}", pattern))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(FileFilter {
+            include: compile(&self.include)?,
+            exclude: compile(&self.exclude)?,
+        })
+    }
+}
+
+/// ディレクトリ内のファイルを、ファイル名の glob で選ぶ
+pub struct FileFilter {
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+}
+
+impl FileFilter {
+    /// すべてのファイルを選ぶ
+    pub fn all() -> Self {
+        Self {
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+
+    pub fn matches(&self, filename: &str) -> bool {
+        let included = self.include.is_empty() || self.include.iter().any(|p| p.matches(filename));
+        included && !self.exclude.iter().any(|p| p.matches(filename))
+    }
+}
+
+/// `path` の manifest を読み込み、相対パスを manifest のあるディレクトリを基準に解決する
+pub fn load(path: &Path) -> Result<Manifest> {
+    let ctx = format!("manifest: {:?}", path);
+    let content = fs::read_to_string(path).context(ctx.clone())?;
+    let base_dir = path.parent().unwrap_or(Path::new(""));
+    parse(&content, base_dir).context(ctx)
+}
+
+fn parse(content: &str, base_dir: &Path) -> Result<Manifest> {
+    let mut manifest: Manifest = toml::from_str(content)?;
+    manifest.output = base_dir.join(&manifest.output);
+    for component in &mut manifest.components {
+        for path in [
+            &mut component.tlm_db_dir,
+            &mut component.cmd_db_dir,
+            &mut component.bct,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            *path = base_dir.join(&*path);
+        }
+    }
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let content = r#"
+output = "build/tlmcmddb.json"
+
+[[components]]
+name = "MOBC"
+tlm_db_dir = "MOBC/TLM_DB"
+cmd_db_dir = "/abs/CMD_DB"
+exclude = ["*_TEMPLATE.csv"]
+
+[[components]]
+tlm_db_dir = "PAYLOAD/TLM_DB"
+include = ["*_TLM_DB_*.csv"]
+"#;
+        let manifest = parse(content, Path::new("sat")).unwrap();
+        assert_eq!(Path::new("sat/build/tlmcmddb.json"), manifest.output);
+        assert!(!manifest.pretty);
+        assert_eq!(ConflictPolicy::Error, manifest.on_conflict);
+
+        let mobc = &manifest.components[0];
+        assert_eq!(Some("MOBC"), mobc.name.as_deref());
+        assert_eq!(
+            Some(Path::new("sat/MOBC/TLM_DB")),
+            mobc.tlm_db_dir.as_deref()
+        );
+        assert_eq!(Some(Path::new("/abs/CMD_DB")), mobc.cmd_db_dir.as_deref());
+        let filter = mobc.filter().unwrap();
+        assert!(filter.matches("SAMPLE_MOBC_TLM_DB_HK.csv"));
+        assert!(!filter.matches("SAMPLE_MOBC_TLM_DB_TEMPLATE.csv"));
+
+        let payload = &manifest.components[1];
+        assert!(payload.name.is_none() && payload.cmd_db_dir.is_none());
+        let filter = payload.filter().unwrap();
+        assert!(filter.matches("PL_TLM_DB_HK.csv"));
+        assert!(!filter.matches("PL_CMD_DB.csv"));
+
+        assert!(parse(
+            "output = \"a.json\"\ncomponents = []\ntypo = 1\n",
+            Path::new("")
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,80 @@
+//! XTCE や SVG などの XML の書き出し
+
+use std::fmt::Write;
+
+/// インデントつきで XML を書き出す
+pub struct XmlWriter {
+    out: String,
+    depth: usize,
+}
+
+impl XmlWriter {
+    pub fn new() -> Self {
+        Self {
+            out: String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"),
+            depth: 0,
+        }
+    }
+
+    fn start_tag(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+        self.out.push('<');
+        self.out.push_str(name);
+        for (key, value) in attributes {
+            let _ = write!(
+                self.out,
+                " {}=\"{}\"",
+                key,
+                quick_xml::escape::escape(value)
+            );
+        }
+    }
+
+    pub fn open(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.out.push_str(">\n");
+        self.depth += 1;
+    }
+
+    pub fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        for _ in 0..self.depth {
+            self.out.push_str("  ");
+        }
+        let _ = writeln!(self.out, "</{}>", name);
+    }
+
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, &str)]) {
+        self.start_tag(name, attributes);
+        self.out.push_str("/>\n");
+    }
+
+    pub fn text(&mut self, name: &str, attributes: &[(&str, &str)], text: &str) {
+        self.start_tag(name, attributes);
+        let _ = writeln!(self.out, ">{}</{}>", quick_xml::escape::escape(text), name);
+    }
+
+    pub fn finish(self) -> String {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_writer() {
+        let mut xml = XmlWriter::new();
+        xml.open("svg", &[("xmlns", "http://www.w3.org/2000/svg")]);
+        xml.empty("rect", &[("width", "8")]);
+        xml.text("title", &[], "A & B");
+        xml.close("svg");
+        assert_eq!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<svg xmlns=\"http://www.w3.org/2000/svg\">\n  <rect width=\"8\"/>\n  <title>A &amp; B</title>\n</svg>\n",
+            xml.finish()
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! XTCE (CCSDS 660.0) との相互変換
+//!
+//! XTCE では表現できない tlmcmddb 固有の情報は、`tlmcmddb.` で始まる名前の AncillaryData として保持する。
+//! XTCE の名前には `.` などを使えないため `_` に置き換え、元の名前は名前空間 [ALIAS_NAMESPACE] の Alias として保持する。
+
+pub mod export;
+pub mod import;
+
+/// XTCE 1.2 の名前空間
+const XTCE_NAMESPACE: &str = "http://www.omg.org/spec/XTCE/20180204";
+
+/// 元の名前を保持する Alias の名前空間
+const ALIAS_NAMESPACE: &str = "tlmcmddb";
+
+mod ancillary {
+    pub const TARGET: &str = "tlmcmddb.target";
+    pub const IS_ENABLED: &str = "tlmcmddb.isEnabled";
+    pub const IS_RESTRICTED: &str = "tlmcmddb.isRestricted";
+    pub const LOCAL_VARIABLES: &str = "tlmcmddb.localVariables";
+    pub const VARIABLE_TYPE: &str = "tlmcmddb.variableType";
+    pub const EXPRESSION: &str = "tlmcmddb.expression";
+    pub const CONVERSION: &str = "tlmcmddb.conversion";
+    pub const STATUS_DEFAULT: &str = "tlmcmddb.statusDefault";
+    pub const NOTE: &str = "tlmcmddb.note";
+    pub const VARIABLE_LENGTH: &str = "tlmcmddb.variableLength";
+    pub const BLOB: &str = "tlmcmddb.blob";
+}
+
+/// `name` を XTCE の名前（NameType）として使える形にする
+fn xtce_name(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            '.' | '/' | ':' | '[' | ']' | ' ' => '_',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xtce_name() {
+        assert_eq!("PH_VER", xtce_name("PH.VER"));
+        assert_eq!("a_b_c", xtce_name("a b/c"));
+    }
+}
@@ -0,0 +1,648 @@
+//! [Database] を XTCE の SpaceSystem として書き出す
+//!
+//! - [Component] は SpaceSystem になる
+//! - [`tlm::Telemetry`] は SequenceContainer になり、SH.TLM_ID が `packet_id` と一致することを RestrictionCriteria とする
+//! - [`tlm::Field`] は Parameter になり、エンコーディングは `bit_length` と `variable_type` から決める
+//! - [`cmd::Command`] は、C2A のコマンド Space Packet のヘッダを引数としてもつ抽象 MetaCommand [BASE_COMMAND] を継承する MetaCommand になる。
+//!   コマンドコードとパケット長は ArgumentAssignment で与え、CommandContainer にはヘッダに続けて引数を並べる
+//!
+//! コメント行と BCT（[`Component::bct`]）は書き出さない。blob テレメトリはエントリをもたない SequenceContainer になる。
+
+use anyhow::{ensure, Result};
+use tlmcmddb::{
+    cmd::{
+        self,
+        encode::{
+            packet_data_length, HeaderField, CMD_ID_FIELD, HEADER_FIELDS, PACKET_LENGTH_FIELD,
+        },
+    },
+    tlm::{
+        self, conversion,
+        decode::{TLM_ID_LEN, TLM_ID_OFFSET},
+    },
+    Component, Database,
+};
+
+use super::{ancillary, xtce_name, ALIAS_NAMESPACE, XTCE_NAMESPACE};
+use crate::xml::XmlWriter;
+
+/// 各 component の SpaceSystem を子にもつ、名前 `name` の SpaceSystem を書き出す
+pub fn export(database: &Database, name: &str) -> Result<String> {
+    let mut xml = XmlWriter::new();
+    xml.open(
+        "SpaceSystem",
+        &[("xmlns", XTCE_NAMESPACE), ("name", &xtce_name(name))],
+    );
+    for component in &database.components {
+        write_component(&mut xml, component)?;
+    }
+    xml.close("SpaceSystem");
+    Ok(xml.finish())
+}
+
+/// 各 component における SH.TLM_ID の Parameter の名前
+const TLM_ID_PARAMETER: &str = "TLM_ID";
+
+/// 各 component の、SH.TLM_ID のみをもつ抽象 SequenceContainer の名前
+const BASE_CONTAINER: &str = "TlmPacket";
+
+/// 各 component の、C2A のコマンド Space Packet のヘッダを引数としてもつ抽象 MetaCommand の名前
+const BASE_COMMAND: &str = "C2aCommand";
+
+fn write_component(xml: &mut XmlWriter, component: &Component) -> Result<()> {
+    xml.open("SpaceSystem", &[("name", &xtce_name(&component.name))]);
+    write_telemetry_meta_data(xml, &component.tlm)?;
+    write_command_meta_data(xml, &component.cmd);
+    xml.close("SpaceSystem");
+    Ok(())
+}
+
+/// XTCE の名前 `name` が元の名前 `original` と異なる場合に、元の名前を Alias として書き出す
+fn write_alias(xml: &mut XmlWriter, name: &str, original: &str) {
+    if name == original {
+        return;
+    }
+    xml.open("AliasSet", &[]);
+    xml.empty(
+        "Alias",
+        &[("nameSpace", ALIAS_NAMESPACE), ("alias", original)],
+    );
+    xml.close("AliasSet");
+}
+
+/// 値が空のものを除いて AncillaryDataSet を書き出す
+fn write_ancillary_data(xml: &mut XmlWriter, data: &[(&str, &str)]) {
+    let data: Vec<_> = data.iter().filter(|(_, value)| !value.is_empty()).collect();
+    if data.is_empty() {
+        return;
+    }
+    xml.open("AncillaryDataSet", &[]);
+    for (name, value) in data {
+        xml.text("AncillaryData", &[("name", name)], value);
+    }
+    xml.close("AncillaryDataSet");
+}
+
+fn bool_str(value: bool) -> &'static str {
+    if value {
+        "true"
+    } else {
+        "false"
+    }
+}
+
+/// テレメトリ名を前置した Parameter の名前
+fn parameter_name(telemetry: &tlm::Telemetry, field: &tlm::Field) -> String {
+    xtce_name(&format!("{}.{}", telemetry.name, field.name))
+}
+
+fn write_telemetry_meta_data(xml: &mut XmlWriter, database: &tlm::Database) -> Result<()> {
+    xml.open("TelemetryMetaData", &[]);
+
+    xml.open("ParameterTypeSet", &[]);
+    let tlm_id_type = format!("{}_Type", TLM_ID_PARAMETER);
+    xml.open(
+        "IntegerParameterType",
+        &[("name", &tlm_id_type), ("signed", "false")],
+    );
+    let size = (TLM_ID_LEN * 8).to_string();
+    xml.empty(
+        "IntegerDataEncoding",
+        &[("sizeInBits", &size), ("encoding", "unsigned")],
+    );
+    xml.close("IntegerParameterType");
+    for telemetry in &database.telemetries {
+        for (field_group, field) in telemetry.fields() {
+            write_parameter_type(xml, &parameter_name(telemetry, field), field_group, field)?;
+        }
+    }
+    xml.close("ParameterTypeSet");
+
+    xml.open("ParameterSet", &[]);
+    xml.empty(
+        "Parameter",
+        &[
+            ("name", TLM_ID_PARAMETER),
+            ("parameterTypeRef", &tlm_id_type),
+        ],
+    );
+    for telemetry in &database.telemetries {
+        for (field_group, field) in telemetry.fields() {
+            let name = parameter_name(telemetry, field);
+            let type_ref = format!("{}_Type", name);
+            let mut attributes = vec![("name", name.as_str()), ("parameterTypeRef", &type_ref)];
+            if !field.description.is_empty() {
+                attributes.push(("shortDescription", &field.description));
+            }
+            xml.open("Parameter", &attributes);
+            write_alias(xml, &name, &field.name);
+            let info = &field_group.onboard_software_info;
+            let variable_type = info.variable_type.as_str();
+            write_ancillary_data(
+                xml,
+                &[
+                    (ancillary::VARIABLE_TYPE, variable_type),
+                    (ancillary::EXPRESSION, &info.expression),
+                    (ancillary::NOTE, &field.note),
+                ],
+            );
+            xml.close("Parameter");
+        }
+    }
+    xml.close("ParameterSet");
+
+    xml.open("ContainerSet", &[]);
+    xml.open(
+        "SequenceContainer",
+        &[("name", BASE_CONTAINER), ("abstract", "true")],
+    );
+    xml.open("EntryList", &[]);
+    write_parameter_ref_entry(xml, TLM_ID_PARAMETER, TLM_ID_OFFSET * 8);
+    xml.close("EntryList");
+    xml.close("SequenceContainer");
+    for telemetry in &database.telemetries {
+        write_container(xml, telemetry);
+    }
+    xml.close("ContainerSet");
+
+    xml.close("TelemetryMetaData");
+    Ok(())
+}
+
+fn write_parameter_ref_entry(xml: &mut XmlWriter, parameter_ref: &str, location_in_bits: usize) {
+    xml.open("ParameterRefEntry", &[("parameterRef", parameter_ref)]);
+    xml.open(
+        "LocationInContainerInBits",
+        &[("referenceLocation", "containerStart")],
+    );
+    xml.text("FixedValue", &[], &location_in_bits.to_string());
+    xml.close("LocationInContainerInBits");
+    xml.close("ParameterRefEntry");
+}
+
+fn write_container(xml: &mut XmlWriter, telemetry: &tlm::Telemetry) {
+    let metadata = &telemetry.metadata;
+    // blob の中身は XTCE で表現できないため、空の EntryList とする
+    let is_blob = match telemetry.content {
+        tlm::Content::Blob => "true",
+        tlm::Content::Struct(_) => "",
+    };
+    let name = xtce_name(&telemetry.name);
+    xml.open("SequenceContainer", &[("name", &name)]);
+    write_alias(xml, &name, &telemetry.name);
+    write_ancillary_data(
+        xml,
+        &[
+            (ancillary::TARGET, &metadata.target),
+            (ancillary::IS_ENABLED, bool_str(metadata.is_enabled)),
+            (ancillary::IS_RESTRICTED, bool_str(metadata.is_restricted)),
+            (ancillary::LOCAL_VARIABLES, &metadata.local_variables),
+            (ancillary::BLOB, is_blob),
+        ],
+    );
+    xml.open("EntryList", &[]);
+    for (_, field) in telemetry.fields() {
+        let info = &field.extraction_info;
+        write_parameter_ref_entry(
+            xml,
+            &parameter_name(telemetry, field),
+            info.octet_position * 8 + info.bit_position,
+        );
+    }
+    xml.close("EntryList");
+    xml.open("BaseContainer", &[("containerRef", BASE_CONTAINER)]);
+    xml.open("RestrictionCriteria", &[]);
+    xml.empty(
+        "Comparison",
+        &[
+            ("parameterRef", TLM_ID_PARAMETER),
+            ("value", &metadata.packet_id.to_string()),
+        ],
+    );
+    xml.close("RestrictionCriteria");
+    xml.close("BaseContainer");
+    xml.close("SequenceContainer");
+}
+
+fn write_unit_set(xml: &mut XmlWriter, field: &tlm::Field) {
+    let Some(display_info) = &field.display_info else {
+        return;
+    };
+    if display_info.unit.is_empty() {
+        return;
+    }
+    xml.open("UnitSet", &[]);
+    xml.text("Unit", &[], &display_info.unit);
+    xml.close("UnitSet");
+}
+
+/// フィールドの生値のエンコーディング
+fn write_data_encoding(xml: &mut XmlWriter, field_group: &tlm::FieldGroup, field: &tlm::Field) {
+    write_unit_set(xml, field);
+    let size = field.extraction_info.bit_length.to_string();
+    match field_group.onboard_software_info.variable_type {
+        tlm::VariableType::Float | tlm::VariableType::Double => {
+            xml.empty("FloatDataEncoding", &[("sizeInBits", &size)]);
+        }
+        variable_type => {
+            let encoding = if variable_type.is_unsigned_integer() {
+                "unsigned"
+            } else {
+                "twosComplement"
+            };
+            xml.empty(
+                "IntegerDataEncoding",
+                &[("sizeInBits", &size), ("encoding", encoding)],
+            );
+        }
+    }
+}
+
+fn write_parameter_type(
+    xml: &mut XmlWriter,
+    parameter_name: &str,
+    field_group: &tlm::FieldGroup,
+    field: &tlm::Field,
+) -> Result<()> {
+    let name = format!("{}_Type", parameter_name);
+    let variable_type = field_group.onboard_software_info.variable_type;
+    let is_float = matches!(
+        variable_type,
+        tlm::VariableType::Float | tlm::VariableType::Double
+    );
+    match &field.conversion_info {
+        tlm::ConversionInfo::None | tlm::ConversionInfo::Hex => {
+            let conversion = match field.conversion_info {
+                tlm::ConversionInfo::Hex => "HEX",
+                _ => "",
+            };
+            if is_float {
+                let size = field.extraction_info.bit_length.to_string();
+                xml.open(
+                    "FloatParameterType",
+                    &[("name", &name), ("sizeInBits", &size)],
+                );
+                write_ancillary_data(xml, &[(ancillary::CONVERSION, conversion)]);
+                write_data_encoding(xml, field_group, field);
+                xml.close("FloatParameterType");
+            } else {
+                let signed = bool_str(!variable_type.is_unsigned_integer());
+                xml.open(
+                    "IntegerParameterType",
+                    &[("name", &name), ("signed", signed)],
+                );
+                write_ancillary_data(xml, &[(ancillary::CONVERSION, conversion)]);
+                write_data_encoding(xml, field_group, field);
+                xml.close("IntegerParameterType");
+            }
+        }
+        tlm::ConversionInfo::Polynomial(polynomial) => {
+            xml.open(
+                "FloatParameterType",
+                &[("name", &name), ("sizeInBits", "64")],
+            );
+            xml.open_encoding_with_calibrator(field_group, field);
+            write_polynomial_calibrator(xml, polynomial);
+            xml.close_encoding_with_calibrator(variable_type);
+            xml.close("FloatParameterType");
+        }
+        tlm::ConversionInfo::Status(status) => {
+            xml.open("EnumeratedParameterType", &[("name", &name)]);
+            let default = status.default_value.as_deref().unwrap_or_default();
+            write_ancillary_data(xml, &[(ancillary::STATUS_DEFAULT, default)]);
+            ensure!(
+                !is_float,
+                "{}: status conversion requires an integer variable type",
+                parameter_name
+            );
+            write_data_encoding(xml, field_group, field);
+            write_enumeration_list(xml, status);
+            xml.close("EnumeratedParameterType");
+        }
+    }
+    Ok(())
+}
+
+impl XmlWriter {
+    /// DefaultCalibrator を子にもつデータエンコーディングの開始タグを書き出す
+    fn open_encoding_with_calibrator(&mut self, field_group: &tlm::FieldGroup, field: &tlm::Field) {
+        write_unit_set(self, field);
+        let size = field.extraction_info.bit_length.to_string();
+        match field_group.onboard_software_info.variable_type {
+            tlm::VariableType::Float | tlm::VariableType::Double => {
+                self.open("FloatDataEncoding", &[("sizeInBits", &size)]);
+            }
+            variable_type => {
+                let encoding = if variable_type.is_unsigned_integer() {
+                    "unsigned"
+                } else {
+                    "twosComplement"
+                };
+                self.open(
+                    "IntegerDataEncoding",
+                    &[("sizeInBits", &size), ("encoding", encoding)],
+                );
+            }
+        }
+        self.open("DefaultCalibrator", &[]);
+    }
+
+    fn close_encoding_with_calibrator(&mut self, variable_type: tlm::VariableType) {
+        self.close("DefaultCalibrator");
+        match variable_type {
+            tlm::VariableType::Float | tlm::VariableType::Double => self.close("FloatDataEncoding"),
+            _ => self.close("IntegerDataEncoding"),
+        }
+    }
+}
+
+fn write_polynomial_calibrator(xml: &mut XmlWriter, polynomial: &conversion::Polynomial) {
+    xml.open("PolynomialCalibrator", &[]);
+    let coefficients = [
+        polynomial.a0,
+        polynomial.a1,
+        polynomial.a2,
+        polynomial.a3,
+        polynomial.a4,
+        polynomial.a5,
+    ];
+    for (exponent, coefficient) in coefficients.iter().enumerate() {
+        if *coefficient == 0.0 {
+            continue;
+        }
+        xml.empty(
+            "Term",
+            &[
+                ("coefficient", &coefficient.to_string()),
+                ("exponent", &exponent.to_string()),
+            ],
+        );
+    }
+    xml.close("PolynomialCalibrator");
+}
+
+fn write_enumeration_list(xml: &mut XmlWriter, status: &conversion::Status) {
+    xml.open("EnumerationList", &[]);
+    for variant in &status.variants {
+        xml.empty(
+            "Enumeration",
+            &[
+                ("value", &variant.key.to_string()),
+                ("label", &variant.value),
+            ],
+        );
+    }
+    xml.close("EnumerationList");
+}
+
+const DATA_TYPES: [cmd::DataType; 9] = [
+    cmd::DataType::Int8,
+    cmd::DataType::Int16,
+    cmd::DataType::Int32,
+    cmd::DataType::Uint8,
+    cmd::DataType::Uint16,
+    cmd::DataType::Uint32,
+    cmd::DataType::Float,
+    cmd::DataType::Double,
+    cmd::DataType::Raw,
+];
+
+/// 各 [cmd::DataType] に対応する ArgumentType を書き出す。名前は C の型名と同じ
+fn write_argument_type(xml: &mut XmlWriter, data_type: cmd::DataType) {
+    let name = data_type.as_str();
+    match data_type {
+        cmd::DataType::Int8
+        | cmd::DataType::Int16
+        | cmd::DataType::Int32
+        | cmd::DataType::Uint8
+        | cmd::DataType::Uint16
+        | cmd::DataType::Uint32 => {
+            let (size, signed) = match data_type {
+                cmd::DataType::Int8 => (8, true),
+                cmd::DataType::Int16 => (16, true),
+                cmd::DataType::Int32 => (32, true),
+                cmd::DataType::Uint8 => (8, false),
+                cmd::DataType::Uint16 => (16, false),
+                _ => (32, false),
+            };
+            let size = size.to_string();
+            xml.open(
+                "IntegerArgumentType",
+                &[("name", name), ("signed", bool_str(signed))],
+            );
+            let encoding = if signed { "twosComplement" } else { "unsigned" };
+            xml.empty(
+                "IntegerDataEncoding",
+                &[("sizeInBits", &size), ("encoding", encoding)],
+            );
+            xml.close("IntegerArgumentType");
+        }
+        cmd::DataType::Float | cmd::DataType::Double => {
+            let size = if data_type == cmd::DataType::Float {
+                "32"
+            } else {
+                "64"
+            };
+            xml.open("FloatArgumentType", &[("name", name), ("sizeInBits", size)]);
+            xml.empty("FloatDataEncoding", &[("sizeInBits", size)]);
+            xml.close("FloatArgumentType");
+        }
+        cmd::DataType::Raw => {
+            // 可変長であることは XTCE で表現できないため、AncillaryData で示す
+            xml.open("BinaryArgumentType", &[("name", name)]);
+            write_ancillary_data(xml, &[(ancillary::VARIABLE_LENGTH, "true")]);
+            xml.open("BinaryDataEncoding", &[]);
+            xml.open("SizeInBits", &[]);
+            xml.text("FixedValue", &[], "0");
+            xml.close("SizeInBits");
+            xml.close("BinaryDataEncoding");
+            xml.close("BinaryArgumentType");
+        }
+    }
+}
+
+fn write_command_meta_data(xml: &mut XmlWriter, database: &cmd::Database) {
+    xml.open("CommandMetaData", &[]);
+    xml.open("ArgumentTypeSet", &[]);
+    for data_type in DATA_TYPES {
+        write_argument_type(xml, data_type);
+    }
+    for field in HEADER_FIELDS {
+        write_header_argument_type(xml, &field);
+    }
+    xml.close("ArgumentTypeSet");
+    xml.open("MetaCommandSet", &[]);
+    write_base_meta_command(xml);
+    for entry in &database.entries {
+        if let cmd::Entry::Command(command) = entry {
+            write_meta_command(xml, command);
+        }
+    }
+    xml.close("MetaCommandSet");
+    xml.close("CommandMetaData");
+}
+
+/// ヘッダのフィールド `field` の ArgumentType。名前はフィールド名に `_Type` を付けたもの
+fn write_header_argument_type(xml: &mut XmlWriter, field: &HeaderField) {
+    let name = format!("{}_Type", field.name);
+    let default_value = field.default_value.to_string();
+    xml.open(
+        "IntegerArgumentType",
+        &[
+            ("name", &name),
+            ("signed", "false"),
+            ("initialValue", &default_value),
+        ],
+    );
+    xml.empty(
+        "IntegerDataEncoding",
+        &[
+            ("sizeInBits", &field.bit_length.to_string()),
+            ("encoding", "unsigned"),
+        ],
+    );
+    xml.close("IntegerArgumentType");
+}
+
+/// ヘッダの各フィールドを引数とし、それらを順に並べた CommandContainer をもつ [BASE_COMMAND]
+fn write_base_meta_command(xml: &mut XmlWriter) {
+    xml.open(
+        "MetaCommand",
+        &[("name", BASE_COMMAND), ("abstract", "true")],
+    );
+    xml.open("ArgumentList", &[]);
+    for field in HEADER_FIELDS {
+        xml.empty(
+            "Argument",
+            &[
+                ("name", field.name),
+                ("argumentTypeRef", &format!("{}_Type", field.name)),
+                ("shortDescription", field.description),
+            ],
+        );
+    }
+    xml.close("ArgumentList");
+    xml.open(
+        "CommandContainer",
+        &[("name", &format!("{}_Container", BASE_COMMAND))],
+    );
+    xml.open("EntryList", &[]);
+    for field in HEADER_FIELDS {
+        xml.empty("ArgumentRefEntry", &[("argumentRef", field.name)]);
+    }
+    xml.close("EntryList");
+    xml.close("CommandContainer");
+    xml.close("MetaCommand");
+}
+
+fn write_meta_command(xml: &mut XmlWriter, command: &cmd::Command) {
+    let name = xtce_name(&command.name);
+    let mut attributes = vec![("name", name.as_str())];
+    if !command.description.is_empty() {
+        attributes.push(("shortDescription", &command.description));
+    }
+    xml.open("MetaCommand", &attributes);
+    write_alias(xml, &name, &command.name);
+    write_ancillary_data(
+        xml,
+        &[
+            (ancillary::TARGET, &command.target),
+            (ancillary::IS_RESTRICTED, bool_str(command.is_restricted)),
+            (ancillary::NOTE, &command.note),
+        ],
+    );
+    xml.open("BaseMetaCommand", &[("metaCommandRef", BASE_COMMAND)]);
+    xml.open("ArgumentAssignmentList", &[]);
+    for (argument_name, argument_value) in [
+        (CMD_ID_FIELD, command.code as usize),
+        (PACKET_LENGTH_FIELD, packet_data_length(command)),
+    ] {
+        xml.empty(
+            "ArgumentAssignment",
+            &[
+                ("argumentName", argument_name),
+                ("argumentValue", &argument_value.to_string()),
+            ],
+        );
+    }
+    xml.close("ArgumentAssignmentList");
+    xml.close("BaseMetaCommand");
+    if !command.parameters.is_empty() {
+        xml.open("ArgumentList", &[]);
+        for (i, parameter) in command.parameters.iter().enumerate() {
+            let name = format!("Param{}", i + 1);
+            let mut attributes = vec![
+                ("name", name.as_str()),
+                ("argumentTypeRef", parameter.data_type.as_str()),
+            ];
+            if !parameter.description.is_empty() {
+                attributes.push(("shortDescription", &parameter.description));
+            }
+            xml.empty("Argument", &attributes);
+        }
+        xml.close("ArgumentList");
+    }
+    let container = format!("{}_Container", name);
+    xml.open("CommandContainer", &[("name", &container)]);
+    xml.open("EntryList", &[]);
+    for i in 0..command.parameters.len() {
+        xml.empty(
+            "ArgumentRefEntry",
+            &[("argumentRef", &format!("Param{}", i + 1))],
+        );
+    }
+    xml.close("EntryList");
+    xml.empty(
+        "BaseContainer",
+        &[("containerRef", &format!("{}_Container", BASE_COMMAND))],
+    );
+    xml.close("CommandContainer");
+    if command.is_danger {
+        xml.empty("DefaultSignificance", &[("consequenceLevel", "critical")]);
+    }
+    xml.close("MetaCommand");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export() {
+        let json = include_bytes!("../../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let json = include_bytes!("../../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        let database = Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![telemetry],
+                },
+                cmd,
+                bct: Default::default(),
+            }],
+        };
+        let xml = export(&database, "SAMPLE").unwrap();
+        assert!(xml.contains("<SpaceSystem name=\"MOBC\">"));
+        assert!(xml.contains("<Parameter name=\"HK_PH_VER\" parameterTypeRef=\"HK_PH_VER_Type\""));
+        assert!(xml.contains("<Alias nameSpace=\"tlmcmddb\" alias=\"PH.VER\"/>"));
+        assert!(xml.contains("<Comparison parameterRef=\"TLM_ID\" value=\"240\"/>"));
+        assert!(xml.contains("<MetaCommand name=\"C2aCommand\" abstract=\"true\">"));
+        let header = xml
+            .split("<CommandContainer name=\"C2aCommand_Container\">")
+            .nth(1)
+            .unwrap();
+        let cmd_code = header
+            .find("<ArgumentRefEntry argumentRef=\"CMD_CODE\"")
+            .unwrap();
+        assert_eq!(9, header[..cmd_code].matches("<ArgumentRefEntry").count());
+        assert!(xml.contains("<ArgumentAssignment argumentName=\"CMD_CODE\" argumentValue=\"0\"/>"));
+        assert!(
+            xml.contains("<ArgumentAssignment argumentName=\"CCSDS_LENGTH\" argumentValue=\"8\"/>")
+        );
+        assert!(xml.contains("<BaseContainer containerRef=\"C2aCommand_Container\"/>"));
+        assert!(xml.contains("<EnumerationList>"));
+    }
+}
@@ -0,0 +1,1116 @@
+//! XTCE の SpaceSystem を [Database] として読み込む
+//!
+//! 読み込めるのは XTCE の次のような一部分のみである。
+//!
+//! - TelemetryMetaData か CommandMetaData をもつ SpaceSystem は [Component] になる
+//! - 抽象でない SequenceContainer のうち、RestrictionCriteria の Comparison で `packet_id` が決まるものはテレメトリになる。
+//!   エントリはコンテナ先頭からの固定位置をもつ ParameterRefEntry のみ
+//! - パラメータの型は Integer/Float/EnumeratedParameterType で、エンコーディングは IntegerDataEncoding か FloatDataEncoding。
+//!   較正は PolynomialCalibrator のみ
+//! - MetaCommand は6個以下の ArgumentRefEntry を並べたもの。コマンドコードは、BaseMetaCommand の `CMD_CODE` の ArgumentAssignment か、
+//!   継承しない場合は ArgumentRefEntry の前に置いた16ビットの FixedValueEntry で与える
+//!
+//! 表現できない要素は、それを含むテレメトリやコマンドごと読み飛ばし、XML 上の行番号をもつ [Diagnostic] として報告する。
+//! [export](super::export) が書き出した Alias と AncillaryData があれば、元の名前や XTCE で表現できない情報を復元する。
+
+use std::collections::HashMap;
+
+use anyhow::{bail, ensure, Context, Result};
+use quick_xml::{
+    events::{BytesStart, Event},
+    Reader,
+};
+use tlmcmddb::{
+    cmd::{self, encode::CMD_ID_FIELD},
+    tlm::{self, conversion},
+    Component, Database,
+};
+use tlmcmddb_csv::diagnostic::Diagnostic;
+
+use super::{ancillary, ALIAS_NAMESPACE};
+
+/// コマンドがもてるパラメータの最大数
+const MAX_PARAMETERS: usize = 6;
+
+/// `xml` を読み込み、読み込めた [Database] と、読み飛ばした要素の [Diagnostic] を返す
+///
+/// XML として不正な場合はエラーになる。
+pub fn import(xml: &str) -> Result<(Database, Vec<Diagnostic>)> {
+    let root = parse_xml(xml)?;
+    ensure!(
+        root.name == "SpaceSystem",
+        "root element must be SpaceSystem, but is {}",
+        root.name
+    );
+    let mut importer = Importer {
+        lines: xml.lines().collect(),
+        diagnostics: vec![],
+    };
+    let mut components = vec![];
+    importer.import_space_system(&root, &mut components);
+    Ok((Database { components }, importer.diagnostics))
+}
+
+/// XML の要素
+struct Element {
+    /// 名前空間接頭辞を除いた要素名
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+    /// 開始タグの行番号（1始まり）
+    line: usize,
+}
+
+impl Element {
+    fn from_start(start: &BytesStart, line: usize) -> Result<Self> {
+        let mut attributes = vec![];
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            let key = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            attributes.push((key, attribute.unescape_value()?.into_owned()));
+        }
+        Ok(Self {
+            name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+            attributes,
+            children: vec![],
+            text: String::new(),
+            line,
+        })
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// `name` 属性の値
+    fn name_attribute(&self) -> &str {
+        self.attribute("name").unwrap_or_default()
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn parse_xml(xml: &str) -> Result<Element> {
+    let line_starts: Vec<_> = std::iter::once(0)
+        .chain(xml.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_of = |position: u64| line_starts.partition_point(|&start| start as u64 <= position);
+
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<Element> = vec![];
+    loop {
+        let event = reader
+            .read_event()
+            .with_context(|| format!("parsing XML at line {}", line_of(reader.error_position())))?;
+        // 読み込んだ直後の位置はタグの末尾の次を指す
+        let line = line_of(reader.buffer_position().saturating_sub(1));
+        let element = match event {
+            Event::Start(start) => {
+                stack.push(Element::from_start(&start, line)?);
+                continue;
+            }
+            Event::Empty(start) => Element::from_start(&start, line)?,
+            Event::End(_) => stack.pop().context("unexpected end tag")?,
+            Event::Text(text) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text.unescape()?);
+                }
+                continue;
+            }
+            Event::CData(cdata) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&String::from_utf8_lossy(&cdata));
+                }
+                continue;
+            }
+            Event::Eof => bail!("unexpected end of XML document"),
+            _ => continue,
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => return Ok(element),
+        }
+    }
+}
+
+/// `/SpaceSystem/Name` のようなパスによる参照から、名前の部分を取り出す
+fn local_ref(reference: &str) -> &str {
+    reference.rsplit('/').next().unwrap_or(reference)
+}
+
+/// 名前空間 [ALIAS_NAMESPACE] の Alias があればその名前、なければ `name` 属性の値
+fn original_name(element: &Element) -> &str {
+    element
+        .child("AliasSet")
+        .into_iter()
+        .flat_map(|alias_set| alias_set.children_named("Alias"))
+        .find(|alias| alias.attribute("nameSpace") == Some(ALIAS_NAMESPACE))
+        .and_then(|alias| alias.attribute("alias"))
+        .unwrap_or_else(|| element.name_attribute())
+}
+
+/// 名前が `name` の AncillaryData の値。なければ空文字列
+fn ancillary_data<'a>(element: &'a Element, name: &str) -> &'a str {
+    element
+        .child("AncillaryDataSet")
+        .into_iter()
+        .flat_map(|set| set.children_named("AncillaryData"))
+        .find(|data| data.attribute("name") == Some(name))
+        .map_or("", |data| data.text.as_str())
+}
+
+/// `set` の子要素を `name` 属性で引けるようにする
+fn named_children(set: Option<&Element>) -> HashMap<&str, &Element> {
+    set.into_iter()
+        .flat_map(|set| set.children.iter())
+        .map(|child| (child.name_attribute(), child))
+        .collect()
+}
+
+const VARIABLE_TYPES: [tlm::VariableType; 8] = [
+    tlm::VariableType::Int8,
+    tlm::VariableType::Int16,
+    tlm::VariableType::Int32,
+    tlm::VariableType::Uint8,
+    tlm::VariableType::Uint16,
+    tlm::VariableType::Uint32,
+    tlm::VariableType::Float,
+    tlm::VariableType::Double,
+];
+
+fn parse_variable_type(s: &str) -> Option<tlm::VariableType> {
+    VARIABLE_TYPES
+        .into_iter()
+        .find(|&variable_type| variable_type.as_str() == s)
+}
+
+/// 生値のエンコーディング
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Integer { signed: bool },
+    Float,
+}
+
+/// ParameterType から読み込んだ情報
+struct ParameterType {
+    encoding: Encoding,
+    bit_length: usize,
+    conversion_info: tlm::ConversionInfo,
+    unit: Option<String>,
+}
+
+/// ParameterRefEntry から読み込んだフィールドと、[`tlm::FieldGroup`] にまとめるための情報
+struct ImportedField {
+    field: tlm::Field,
+    /// コンテナ先頭からの位置（ビット）
+    location: usize,
+    encoding: Encoding,
+    /// AncillaryData に書かれた、FieldGroup の変数の型
+    variable_type: Option<tlm::VariableType>,
+    expression: String,
+}
+
+impl ImportedField {
+    fn end(&self) -> usize {
+        self.location + self.field.extraction_info.bit_length
+    }
+}
+
+struct Importer<'a> {
+    /// XML の各行。[Diagnostic] に該当行を含めるために用いる
+    lines: Vec<&'a str>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Importer<'_> {
+    /// `element` の位置を指す [Diagnostic] をもつエラー
+    fn diagnostic(&self, element: &Element, message: impl Into<String>) -> anyhow::Error {
+        Diagnostic {
+            row: Some(element.line),
+            line: self
+                .lines
+                .get(element.line - 1)
+                .map(|line| line.trim().to_string()),
+            ..Diagnostic::new(message)
+        }
+        .into()
+    }
+
+    fn import_space_system(&mut self, space_system: &Element, components: &mut Vec<Component>) {
+        let tlm_meta_data = space_system.child("TelemetryMetaData");
+        let cmd_meta_data = space_system.child("CommandMetaData");
+        if tlm_meta_data.is_some() || cmd_meta_data.is_some() {
+            let mut telemetries = tlm_meta_data
+                .map(|meta_data| self.import_telemetry_meta_data(meta_data))
+                .unwrap_or_default();
+            telemetries.sort_by(|a, b| a.name.cmp(&b.name));
+            let entries = cmd_meta_data
+                .map(|meta_data| self.import_command_meta_data(meta_data))
+                .unwrap_or_default();
+            components.push(Component {
+                name: space_system.name_attribute().to_string(),
+                tlm: tlm::Database { telemetries },
+                cmd: cmd::Database { entries },
+                bct: Default::default(),
+            });
+        }
+        for child in space_system.children_named("SpaceSystem") {
+            self.import_space_system(child, components);
+        }
+    }
+
+    fn import_telemetry_meta_data(&mut self, meta_data: &Element) -> Vec<tlm::Telemetry> {
+        let parameter_types = named_children(meta_data.child("ParameterTypeSet"));
+        let parameters = named_children(meta_data.child("ParameterSet"));
+        let containers = meta_data
+            .child("ContainerSet")
+            .into_iter()
+            .flat_map(|set| set.children_named("SequenceContainer"))
+            .filter(|container| container.attribute("abstract") != Some("true"));
+        let mut telemetries = vec![];
+        for container in containers {
+            match self.import_container(container, &parameters, &parameter_types) {
+                Ok(telemetry) => telemetries.push(telemetry),
+                Err(err) => self.diagnostics.push(err.into()),
+            }
+        }
+        telemetries
+    }
+
+    fn import_container(
+        &self,
+        container: &Element,
+        parameters: &HashMap<&str, &Element>,
+        parameter_types: &HashMap<&str, &Element>,
+    ) -> Result<tlm::Telemetry> {
+        let name = original_name(container).to_string();
+        let metadata = tlm::Metadata {
+            target: ancillary_data(container, ancillary::TARGET).to_string(),
+            packet_id: self.packet_id(container)?,
+            is_enabled: ancillary_data(container, ancillary::IS_ENABLED) != "false",
+            is_restricted: ancillary_data(container, ancillary::IS_RESTRICTED) == "true",
+            local_variables: ancillary_data(container, ancillary::LOCAL_VARIABLES).to_string(),
+        };
+        let content = if ancillary_data(container, ancillary::BLOB) == "true" {
+            tlm::Content::Blob
+        } else {
+            let mut fields = vec![];
+            for entry in container
+                .child("EntryList")
+                .into_iter()
+                .flat_map(|list| list.children.iter())
+            {
+                fields.push(self.import_entry(entry, parameters, parameter_types)?);
+            }
+            fields.sort_by_key(|field| field.location);
+            tlm::Content::Struct(self.group_fields(container, &fields)?)
+        };
+        Ok(tlm::Telemetry {
+            name,
+            metadata,
+            content,
+        })
+    }
+
+    /// BaseContainer の RestrictionCriteria から `packet_id` を決める
+    fn packet_id(&self, container: &Element) -> Result<u8> {
+        let Some(criteria) = container
+            .child("BaseContainer")
+            .and_then(|base| base.child("RestrictionCriteria"))
+        else {
+            return Err(self.diagnostic(
+                container,
+                format!(
+                    "SequenceContainer {} has no RestrictionCriteria to determine packet_id",
+                    container.name_attribute()
+                ),
+            ));
+        };
+        let comparison = match criteria.children.as_slice() {
+            [comparison] if comparison.name == "Comparison" => comparison,
+            [list] if list.name == "ComparisonList" && list.children.len() == 1 => {
+                &list.children[0]
+            }
+            _ => {
+                return Err(self.diagnostic(
+                    criteria,
+                    "RestrictionCriteria must be a single Comparison to determine packet_id",
+                ))
+            }
+        };
+        if comparison.attribute("comparisonOperator").unwrap_or("==") != "==" {
+            return Err(self.diagnostic(
+                comparison,
+                "comparisonOperator of the Comparison for packet_id must be ==",
+            ));
+        }
+        comparison
+            .attribute("value")
+            .and_then(|value| value.parse().ok())
+            .ok_or_else(|| {
+                self.diagnostic(comparison, "packet_id must be an integer from 0 to 255")
+            })
+    }
+
+    fn import_entry(
+        &self,
+        entry: &Element,
+        parameters: &HashMap<&str, &Element>,
+        parameter_types: &HashMap<&str, &Element>,
+    ) -> Result<ImportedField> {
+        if entry.name != "ParameterRefEntry" {
+            return Err(self.diagnostic(
+                entry,
+                format!(
+                    "{} is not supported; only ParameterRefEntry can be imported",
+                    entry.name
+                ),
+            ));
+        }
+        let parameter_ref = local_ref(entry.attribute("parameterRef").unwrap_or_default());
+        let location = entry
+            .child("LocationInContainerInBits")
+            .filter(|location| location.attribute("referenceLocation") == Some("containerStart"))
+            .and_then(|location| location.child("FixedValue"))
+            .and_then(|value| value.text.trim().parse().ok())
+            .ok_or_else(|| {
+                self.diagnostic(
+                    entry,
+                    format!(
+                        "location of {} must be a FixedValue relative to containerStart",
+                        parameter_ref
+                    ),
+                )
+            })?;
+        let parameter = parameters.get(parameter_ref).ok_or_else(|| {
+            self.diagnostic(entry, format!("parameter {} is not defined", parameter_ref))
+        })?;
+        let type_ref = local_ref(parameter.attribute("parameterTypeRef").unwrap_or_default());
+        let parameter_type = parameter_types.get(type_ref).ok_or_else(|| {
+            self.diagnostic(
+                parameter,
+                format!("parameter type {} is not defined", type_ref),
+            )
+        })?;
+        let parameter_type = self.import_parameter_type(parameter_type)?;
+        let variable_type = match ancillary_data(parameter, ancillary::VARIABLE_TYPE) {
+            "" => None,
+            s => Some(parse_variable_type(s).ok_or_else(|| {
+                self.diagnostic(parameter, format!("unknown variable type {}", s))
+            })?),
+        };
+        let field = tlm::Field {
+            name: original_name(parameter).to_string(),
+            extraction_info: tlm::FieldExtractionInfo {
+                extraction_type: "PACKET".to_string(),
+                octet_position: location / 8,
+                bit_position: location % 8,
+                bit_length: parameter_type.bit_length,
+            },
+            conversion_info: parameter_type.conversion_info,
+            display_info: parameter_type.unit.map(|unit| tlm::DisplayInfo {
+                unit,
+                ..Default::default()
+            }),
+            description: parameter
+                .attribute("shortDescription")
+                .unwrap_or_default()
+                .to_string(),
+            note: ancillary_data(parameter, ancillary::NOTE).to_string(),
+        };
+        Ok(ImportedField {
+            field,
+            location,
+            encoding: parameter_type.encoding,
+            variable_type,
+            expression: ancillary_data(parameter, ancillary::EXPRESSION).to_string(),
+        })
+    }
+
+    fn import_parameter_type(&self, parameter_type: &Element) -> Result<ParameterType> {
+        let is_enumerated = match parameter_type.name.as_str() {
+            "IntegerParameterType" | "FloatParameterType" => false,
+            "EnumeratedParameterType" => true,
+            name => {
+                return Err(self.diagnostic(
+                    parameter_type,
+                    format!(
+                        "{} {} is not supported",
+                        name,
+                        parameter_type.name_attribute()
+                    ),
+                ))
+            }
+        };
+        let (encoding, bit_length, encoding_element) = self.data_encoding(parameter_type)?;
+        let polynomial = self.polynomial_calibrator(encoding_element)?;
+        let conversion_info = if is_enumerated {
+            if polynomial.is_some() || encoding == Encoding::Float {
+                return Err(self.diagnostic(
+                    parameter_type,
+                    "EnumeratedParameterType must have an IntegerDataEncoding without calibrators",
+                ));
+            }
+            let default_value = match ancillary_data(parameter_type, ancillary::STATUS_DEFAULT) {
+                "" => None,
+                default_value => Some(default_value.to_string()),
+            };
+            tlm::ConversionInfo::Status(conversion::Status {
+                variants: self.enumeration_list(parameter_type)?,
+                default_value,
+            })
+        } else if let Some(polynomial) = polynomial {
+            tlm::ConversionInfo::Polynomial(polynomial)
+        } else if ancillary_data(parameter_type, ancillary::CONVERSION) == "HEX" {
+            tlm::ConversionInfo::Hex
+        } else {
+            tlm::ConversionInfo::None
+        };
+        let unit = parameter_type
+            .child("UnitSet")
+            .and_then(|unit_set| unit_set.child("Unit"))
+            .map(|unit| unit.text.clone());
+        Ok(ParameterType {
+            encoding,
+            bit_length,
+            conversion_info,
+            unit,
+        })
+    }
+
+    /// データ型の要素から、エンコーディングとそのビット幅、エンコーディングの要素を読み込む
+    fn data_encoding<'e>(&self, data_type: &'e Element) -> Result<(Encoding, usize, &'e Element)> {
+        let Some(encoding) = data_type
+            .children
+            .iter()
+            .find(|child| child.name.ends_with("DataEncoding"))
+        else {
+            return Err(self.diagnostic(
+                data_type,
+                format!(
+                    "{} {} has no data encoding",
+                    data_type.name,
+                    data_type.name_attribute()
+                ),
+            ));
+        };
+        if encoding
+            .attribute("byteOrder")
+            .unwrap_or("mostSignificantByteFirst")
+            != "mostSignificantByteFirst"
+        {
+            return Err(self.diagnostic(encoding, "only big-endian encodings are supported"));
+        }
+        match encoding.name.as_str() {
+            "IntegerDataEncoding" => {
+                let signed = match encoding.attribute("encoding").unwrap_or("unsigned") {
+                    "unsigned" => false,
+                    "twosComplement" => true,
+                    other => {
+                        return Err(self.diagnostic(
+                            encoding,
+                            format!("integer encoding {} is not supported", other),
+                        ))
+                    }
+                };
+                let size = self.size_in_bits(encoding, 8)?;
+                Ok((Encoding::Integer { signed }, size, encoding))
+            }
+            "FloatDataEncoding" => {
+                let float_encoding = encoding.attribute("encoding").unwrap_or("IEEE754_1985");
+                if !matches!(float_encoding, "IEEE754_1985" | "IEEE754") {
+                    return Err(self.diagnostic(
+                        encoding,
+                        format!("float encoding {} is not supported", float_encoding),
+                    ));
+                }
+                let size = self.size_in_bits(encoding, 32)?;
+                if size != 32 && size != 64 {
+                    return Err(self
+                        .diagnostic(encoding, format!("float of {} bits is not supported", size)));
+                }
+                Ok((Encoding::Float, size, encoding))
+            }
+            name => Err(self.diagnostic(encoding, format!("{} is not supported", name))),
+        }
+    }
+
+    fn size_in_bits(&self, encoding: &Element, default: usize) -> Result<usize> {
+        match encoding.attribute("sizeInBits") {
+            None => Ok(default),
+            Some(size) => {
+                size.parse().ok().filter(|&size| size > 0).ok_or_else(|| {
+                    self.diagnostic(encoding, format!("invalid sizeInBits: {}", size))
+                })
+            }
+        }
+    }
+
+    fn polynomial_calibrator(&self, encoding: &Element) -> Result<Option<conversion::Polynomial>> {
+        if let Some(list) = encoding.child("ContextCalibratorList") {
+            return Err(self.diagnostic(list, "ContextCalibratorList is not supported"));
+        }
+        let Some(calibrator) = encoding.child("DefaultCalibrator") else {
+            return Ok(None);
+        };
+        let Some(polynomial) = calibrator.child("PolynomialCalibrator") else {
+            let name = calibrator
+                .children
+                .first()
+                .map_or("empty DefaultCalibrator", |child| child.name.as_str());
+            return Err(self.diagnostic(
+                calibrator,
+                format!(
+                    "{} is not supported; only PolynomialCalibrator can be imported",
+                    name
+                ),
+            ));
+        };
+        let mut coefficients = [0.0; 6];
+        for term in polynomial.children_named("Term") {
+            let coefficient: f64 = term
+                .attribute("coefficient")
+                .and_then(|coefficient| coefficient.parse().ok())
+                .ok_or_else(|| self.diagnostic(term, "invalid coefficient"))?;
+            let exponent: f64 = term
+                .attribute("exponent")
+                .and_then(|exponent| exponent.parse().ok())
+                .filter(|exponent: &f64| *exponent >= 0.0 && exponent.fract() == 0.0)
+                .ok_or_else(|| self.diagnostic(term, "invalid exponent"))?;
+            let Some(slot) = coefficients.get_mut(exponent as usize) else {
+                return Err(self.diagnostic(
+                    term,
+                    format!(
+                        "term of degree {} cannot be represented; up to degree 5 is supported",
+                        exponent
+                    ),
+                ));
+            };
+            *slot += coefficient;
+        }
+        let [a0, a1, a2, a3, a4, a5] = coefficients;
+        Ok(Some(conversion::Polynomial {
+            a0,
+            a1,
+            a2,
+            a3,
+            a4,
+            a5,
+        }))
+    }
+
+    fn enumeration_list(&self, parameter_type: &Element) -> Result<Vec<conversion::Variant>> {
+        let Some(list) = parameter_type.child("EnumerationList") else {
+            return Err(self.diagnostic(parameter_type, "EnumerationList is missing"));
+        };
+        list.children_named("Enumeration")
+            .map(|enumeration| {
+                let value = enumeration.attribute("value").unwrap_or_default();
+                if enumeration
+                    .attribute("maxValue")
+                    .is_some_and(|max_value| max_value != value)
+                {
+                    return Err(self.diagnostic(
+                        enumeration,
+                        "Enumeration with a range of values (maxValue) is not supported",
+                    ));
+                }
+                let key = value.parse().map_err(|_| {
+                    self.diagnostic(enumeration, format!("invalid enumeration value: {}", value))
+                })?;
+                Ok(conversion::Variant {
+                    key,
+                    value: enumeration
+                        .attribute("label")
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// 位置の順に並んだフィールドを [`tlm::FieldGroup`] にまとめる
+    ///
+    /// 変数の型が AncillaryData に書かれていなければ、エンコーディングから推測する。
+    fn group_fields(
+        &self,
+        container: &Element,
+        fields: &[ImportedField],
+    ) -> Result<Vec<tlm::Entry>> {
+        let mut entries = vec![];
+        let mut rest = fields;
+        while let [first, ..] = rest {
+            let (variable_type, end) = match first.variable_type {
+                Some(variable_type) => (variable_type, first.location + variable_type.bit_width()),
+                None => self.infer_variable_type(container, rest)?,
+            };
+            if first.end() > end {
+                return Err(self.diagnostic(
+                    container,
+                    format!(
+                        "field {} is wider than its variable type {}",
+                        first.field.name,
+                        variable_type.as_str()
+                    ),
+                ));
+            }
+            // 2つめ以降のフィールドは、符号なし整数型の変数に収まるビットフィールドのみ
+            let length = 1 + rest[1..]
+                .iter()
+                .take_while(|field| {
+                    variable_type.is_unsigned_integer()
+                        && field.end() <= end
+                        && field.encoding == Encoding::Integer { signed: false }
+                        && field
+                            .variable_type
+                            .map_or(true, |other| other == variable_type)
+                        && field.expression == first.expression
+                })
+                .count();
+            let (group, remaining) = rest.split_at(length);
+            entries.push(tlm::Entry::FieldGroup(tlm::FieldGroup {
+                onboard_software_info: tlm::OnboardSoftwareInfo {
+                    variable_type,
+                    expression: first.expression.clone(),
+                },
+                sub_entries: group
+                    .iter()
+                    .map(|field| tlm::SubEntry::Field(field.field.clone()))
+                    .collect(),
+            }));
+            rest = remaining;
+        }
+        Ok(entries)
+    }
+
+    /// 先頭のフィールドのエンコーディングから、FieldGroup の変数の型とその終端の位置を推測する
+    ///
+    /// オクテットアラインされていない符号なし整数は、オクテット境界で終わるまで続くフィールドと1つの変数にまとめる。
+    fn infer_variable_type(
+        &self,
+        container: &Element,
+        fields: &[ImportedField],
+    ) -> Result<(tlm::VariableType, usize)> {
+        let first = &fields[0];
+        let size = first.field.extraction_info.bit_length;
+        let variable_type = match (first.encoding, size) {
+            (Encoding::Float, 32) => tlm::VariableType::Float,
+            (Encoding::Float, 64) => tlm::VariableType::Double,
+            (Encoding::Integer { signed: true }, 8) => tlm::VariableType::Int8,
+            (Encoding::Integer { signed: true }, 16) => tlm::VariableType::Int16,
+            (Encoding::Integer { signed: true }, 32) => tlm::VariableType::Int32,
+            (Encoding::Integer { signed: false }, _) => {
+                let mut end = first.end();
+                for field in &fields[1..] {
+                    if (end - first.location) % 8 == 0
+                        || field.location != end
+                        || field.encoding != first.encoding
+                        || field.variable_type.is_some()
+                    {
+                        break;
+                    }
+                    end = field.end();
+                }
+                let variable_type = match end - first.location {
+                    1..=8 => tlm::VariableType::Uint8,
+                    9..=16 => tlm::VariableType::Uint16,
+                    17..=32 => tlm::VariableType::Uint32,
+                    width => {
+                        return Err(self.diagnostic(
+                            container,
+                            format!(
+                                "field {} and the following bit fields span {} bits, which exceeds 32 bits",
+                                first.field.name, width
+                            ),
+                        ))
+                    }
+                };
+                return Ok((variable_type, end));
+            }
+            (encoding, size) => {
+                return Err(self.diagnostic(
+                    container,
+                    format!(
+                        "field {}: {:?} of {} bits cannot be represented",
+                        first.field.name, encoding, size
+                    ),
+                ))
+            }
+        };
+        Ok((variable_type, first.end()))
+    }
+
+    fn import_command_meta_data(&mut self, meta_data: &Element) -> Vec<cmd::Entry> {
+        let argument_types = named_children(meta_data.child("ArgumentTypeSet"));
+        let meta_commands = meta_data
+            .child("MetaCommandSet")
+            .into_iter()
+            .flat_map(|set| set.children_named("MetaCommand"))
+            .filter(|meta_command| meta_command.attribute("abstract") != Some("true"));
+        let mut entries = vec![];
+        for meta_command in meta_commands {
+            match self.import_meta_command(meta_command, &argument_types) {
+                Ok(command) => entries.push(cmd::Entry::Command(command)),
+                Err(err) => self.diagnostics.push(err.into()),
+            }
+        }
+        entries
+    }
+
+    fn import_meta_command(
+        &self,
+        meta_command: &Element,
+        argument_types: &HashMap<&str, &Element>,
+    ) -> Result<cmd::Command> {
+        let name = original_name(meta_command).to_string();
+        let base = meta_command.child("BaseMetaCommand");
+        let arguments: Vec<_> = meta_command
+            .child("ArgumentList")
+            .into_iter()
+            .flat_map(|list| list.children_named("Argument"))
+            .collect();
+        if arguments.len() > MAX_PARAMETERS {
+            return Err(self.diagnostic(
+                meta_command,
+                format!(
+                    "MetaCommand {} has {} arguments, but at most {} are supported",
+                    name,
+                    arguments.len(),
+                    MAX_PARAMETERS
+                ),
+            ));
+        }
+        let Some(container) = meta_command.child("CommandContainer") else {
+            return Err(self.diagnostic(
+                meta_command,
+                format!("MetaCommand {} has no CommandContainer", name),
+            ));
+        };
+        if base.is_some() != container.child("BaseContainer").is_some() {
+            return Err(self.diagnostic(
+                container,
+                format!(
+                    "CommandContainer of {} must have a BaseContainer if and only if the MetaCommand has a BaseMetaCommand",
+                    name
+                ),
+            ));
+        }
+
+        let mut entries = container
+            .child("EntryList")
+            .into_iter()
+            .flat_map(|list| list.children.iter());
+        let code = match base {
+            Some(base) => self.import_command_code_assignment(base, &name)?,
+            None => match entries.next() {
+                Some(entry)
+                    if entry.name == "FixedValueEntry"
+                        && entry.attribute("sizeInBits") == Some("16") =>
+                {
+                    let binary_value = entry.attribute("binaryValue").unwrap_or_default();
+                    u16::from_str_radix(binary_value, 16).map_err(|_| {
+                        self.diagnostic(
+                            entry,
+                            format!("invalid command code: {:?}", binary_value),
+                        )
+                    })?
+                }
+                _ => {
+                    return Err(self.diagnostic(
+                        container,
+                        format!(
+                            "CommandContainer of {} must start with a 16-bit FixedValueEntry of the command code",
+                            name
+                        ),
+                    ))
+                }
+            },
+        };
+        let mut parameters = vec![];
+        for entry in entries {
+            parameters.push(self.import_argument_ref_entry(entry, &arguments, argument_types)?);
+        }
+        if parameters.len() != arguments.len() {
+            return Err(self.diagnostic(
+                container,
+                format!(
+                    "CommandContainer of {} must place each of its {} arguments exactly once",
+                    name,
+                    arguments.len()
+                ),
+            ));
+        }
+
+        let consequence_level = meta_command
+            .child("DefaultSignificance")
+            .and_then(|significance| significance.attribute("consequenceLevel"));
+        Ok(cmd::Command {
+            name,
+            target: ancillary_data(meta_command, ancillary::TARGET).to_string(),
+            code,
+            parameters,
+            is_danger: matches!(consequence_level, Some("critical" | "catastrophic")),
+            is_restricted: ancillary_data(meta_command, ancillary::IS_RESTRICTED) == "true",
+            description: meta_command
+                .attribute("shortDescription")
+                .unwrap_or_default()
+                .to_string(),
+            note: ancillary_data(meta_command, ancillary::NOTE).to_string(),
+        })
+    }
+
+    /// BaseMetaCommand の ArgumentAssignment からコマンドコードを読み込む
+    fn import_command_code_assignment(&self, base: &Element, name: &str) -> Result<u16> {
+        let assignment = base
+            .child("ArgumentAssignmentList")
+            .into_iter()
+            .flat_map(|list| list.children_named("ArgumentAssignment"))
+            .find(|assignment| assignment.attribute("argumentName") == Some(CMD_ID_FIELD));
+        let Some(assignment) = assignment else {
+            return Err(self.diagnostic(
+                base,
+                format!(
+                    "BaseMetaCommand of {} must assign the command code to {}",
+                    name, CMD_ID_FIELD
+                ),
+            ));
+        };
+        let value = assignment.attribute("argumentValue").unwrap_or_default();
+        value
+            .parse()
+            .map_err(|_| self.diagnostic(assignment, format!("invalid command code: {:?}", value)))
+    }
+
+    /// コマンドコードに続く ArgumentRefEntry を読み込む。引数は隙間なく並んでいなければならない
+    fn import_argument_ref_entry(
+        &self,
+        entry: &Element,
+        arguments: &[&Element],
+        argument_types: &HashMap<&str, &Element>,
+    ) -> Result<cmd::Parameter> {
+        if entry.name != "ArgumentRefEntry" {
+            return Err(self.diagnostic(
+                entry,
+                format!(
+                    "{} is not supported; only ArgumentRefEntry can follow the command code",
+                    entry.name
+                ),
+            ));
+        }
+        if let Some(location) = entry.child("LocationInContainerInBits") {
+            let is_contiguous = location
+                .attribute("referenceLocation")
+                .unwrap_or("previousEntry")
+                == "previousEntry"
+                && location
+                    .child("FixedValue")
+                    .map_or(true, |value| value.text.trim() == "0");
+            if !is_contiguous {
+                return Err(self.diagnostic(
+                    location,
+                    "arguments must follow the previous entry without gaps",
+                ));
+            }
+        }
+        let argument_ref = entry.attribute("argumentRef").unwrap_or_default();
+        let Some(argument) = arguments
+            .iter()
+            .find(|argument| argument.name_attribute() == argument_ref)
+        else {
+            return Err(self.diagnostic(
+                entry,
+                format!("argument {} is not defined in ArgumentList", argument_ref),
+            ));
+        };
+        let type_ref = local_ref(argument.attribute("argumentTypeRef").unwrap_or_default());
+        let argument_type = argument_types.get(type_ref).ok_or_else(|| {
+            self.diagnostic(
+                argument,
+                format!("argument type {} is not defined", type_ref),
+            )
+        })?;
+        Ok(cmd::Parameter {
+            data_type: self.import_argument_type(argument_type)?,
+            description: argument
+                .attribute("shortDescription")
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    fn import_argument_type(&self, argument_type: &Element) -> Result<cmd::DataType> {
+        match argument_type.name.as_str() {
+            "IntegerArgumentType" | "FloatArgumentType" | "EnumeratedArgumentType" => {
+                let (encoding, size, _) = self.data_encoding(argument_type)?;
+                match (encoding, size) {
+                    (Encoding::Integer { signed: true }, 8) => Ok(cmd::DataType::Int8),
+                    (Encoding::Integer { signed: true }, 16) => Ok(cmd::DataType::Int16),
+                    (Encoding::Integer { signed: true }, 32) => Ok(cmd::DataType::Int32),
+                    (Encoding::Integer { signed: false }, 8) => Ok(cmd::DataType::Uint8),
+                    (Encoding::Integer { signed: false }, 16) => Ok(cmd::DataType::Uint16),
+                    (Encoding::Integer { signed: false }, 32) => Ok(cmd::DataType::Uint32),
+                    (Encoding::Float, 32) => Ok(cmd::DataType::Float),
+                    (Encoding::Float, 64) => Ok(cmd::DataType::Double),
+                    (encoding, size) => Err(self.diagnostic(
+                        argument_type,
+                        format!(
+                            "{:?} argument of {} bits cannot be represented",
+                            encoding, size
+                        ),
+                    )),
+                }
+            }
+            "BinaryArgumentType"
+                if ancillary_data(argument_type, ancillary::VARIABLE_LENGTH) == "true" =>
+            {
+                Ok(cmd::DataType::Raw)
+            }
+            name => Err(self.diagnostic(
+                argument_type,
+                format!(
+                    "{} {} is not supported",
+                    name,
+                    argument_type.name_attribute()
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn without_comments(component: &mut Component) {
+        for telemetry in &mut component.tlm.telemetries {
+            if let tlm::Content::Struct(entries) = &mut telemetry.content {
+                entries.retain(|entry| matches!(entry, tlm::Entry::FieldGroup(_)));
+                for field_group in tlm::field_groups_mut(entries) {
+                    field_group
+                        .sub_entries
+                        .retain(|sub_entry| matches!(sub_entry, tlm::SubEntry::Field(_)));
+                    for field in field_group.fields_mut() {
+                        field.display_info = None;
+                    }
+                }
+            }
+        }
+        component
+            .cmd
+            .entries
+            .retain(|entry| matches!(entry, cmd::Entry::Command(_)));
+    }
+
+    #[test]
+    fn test_import_exported() {
+        let json = include_bytes!("../../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let json = include_bytes!("../../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        let mut component = Component {
+            name: "MOBC".to_string(),
+            tlm: tlm::Database {
+                telemetries: vec![telemetry],
+            },
+            cmd,
+            bct: Default::default(),
+        };
+        let xml = super::super::export::export(
+            &Database {
+                components: vec![component.clone()],
+            },
+            "SAMPLE",
+        )
+        .unwrap();
+        let (database, diagnostics) = import(&xml).unwrap();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        without_comments(&mut component);
+        assert_eq!(vec![component], database.components);
+    }
+
+    #[test]
+    fn test_import_unsupported() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<xtce:SpaceSystem xmlns:xtce="http://www.omg.org/spec/XTCE/20180204" name="PAYLOAD">
+  <xtce:TelemetryMetaData>
+    <xtce:ParameterTypeSet>
+      <xtce:IntegerParameterType name="U8">
+        <xtce:IntegerDataEncoding sizeInBits="8"/>
+      </xtce:IntegerParameterType>
+      <xtce:StringParameterType name="STR">
+        <xtce:StringDataEncoding/>
+      </xtce:StringParameterType>
+    </xtce:ParameterTypeSet>
+    <xtce:ParameterSet>
+      <xtce:Parameter name="MODE" parameterTypeRef="U8"/>
+      <xtce:Parameter name="NAME" parameterTypeRef="STR"/>
+    </xtce:ParameterSet>
+    <xtce:ContainerSet>
+      <xtce:SequenceContainer name="STATUS">
+        <xtce:EntryList>
+          <xtce:ParameterRefEntry parameterRef="MODE">
+            <xtce:LocationInContainerInBits referenceLocation="containerStart">
+              <xtce:FixedValue>96</xtce:FixedValue>
+            </xtce:LocationInContainerInBits>
+          </xtce:ParameterRefEntry>
+        </xtce:EntryList>
+        <xtce:BaseContainer containerRef="Header">
+          <xtce:RestrictionCriteria>
+            <xtce:Comparison parameterRef="ID" value="1"/>
+          </xtce:RestrictionCriteria>
+        </xtce:BaseContainer>
+      </xtce:SequenceContainer>
+      <xtce:SequenceContainer name="NAMES">
+        <xtce:EntryList>
+          <xtce:ParameterRefEntry parameterRef="NAME">
+            <xtce:LocationInContainerInBits referenceLocation="containerStart">
+              <xtce:FixedValue>96</xtce:FixedValue>
+            </xtce:LocationInContainerInBits>
+          </xtce:ParameterRefEntry>
+        </xtce:EntryList>
+        <xtce:BaseContainer containerRef="Header">
+          <xtce:RestrictionCriteria>
+            <xtce:Comparison parameterRef="ID" value="2"/>
+          </xtce:RestrictionCriteria>
+        </xtce:BaseContainer>
+      </xtce:SequenceContainer>
+    </xtce:ContainerSet>
+  </xtce:TelemetryMetaData>
+</xtce:SpaceSystem>
+"#;
+        let (database, diagnostics) = import(xml).unwrap();
+        let telemetries = &database.components[0].tlm.telemetries;
+        assert_eq!(1, telemetries.len());
+        assert_eq!("STATUS", telemetries[0].name);
+        assert_eq!(1, telemetries[0].metadata.packet_id);
+        let tlm::Content::Struct(entries) = &telemetries[0].content else {
+            panic!("STATUS must be a struct telemetry");
+        };
+        let [tlm::Entry::FieldGroup(field_group)] = entries.as_slice() else {
+            panic!("STATUS must have a field group");
+        };
+        assert_eq!(
+            tlm::VariableType::Uint8,
+            field_group.onboard_software_info.variable_type
+        );
+
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Some(8), diagnostics[0].row);
+        assert_eq!(
+            "StringParameterType STR is not supported",
+            diagnostics[0].message
+        );
+    }
+}
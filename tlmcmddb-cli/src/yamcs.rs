@@ -0,0 +1,503 @@
+//! Yamcs Mission Database のスプレッドシート形式（フォーマットバージョン 7）の生成
+//!
+//! 各シートを同名の CSV ファイルとして書き出す。
+//! テレメトリは SH.TLM_ID を条件とし、抽象コンテナ [ROOT_CONTAINER] を親とするコンテナになる。
+//! コマンドは、C2A のコマンド Space Packet のヘッダを引数としてもつ抽象コマンド [BASE_COMMAND] を親とし、
+//! 引数の割り当てでコマンドコードとパケット長を決める。
+//! raw パラメータは長さが決まらないため、サイズを指定しない binary の引数として書き出す。
+//! raw パラメータをもつコマンドのパケット長は raw パラメータを含まないため、送信時に設定しなければならない。
+//! BCT（[`Component::bct`]）は書き出さない。
+
+use anyhow::Result;
+use tlmcmddb::{
+    cmd::{
+        self,
+        encode::{packet_data_length, CMD_ID_FIELD, HEADER_FIELDS, PACKET_LENGTH_FIELD},
+    },
+    tlm::{
+        self, conversion,
+        decode::{TLM_ID_LEN, TLM_ID_OFFSET},
+    },
+    Component,
+};
+
+use crate::codegen::GeneratedFile;
+
+const FORMAT_VERSION: &str = "7.0";
+
+/// 全テレメトリの親となる抽象コンテナの名前
+const ROOT_CONTAINER: &str = "TlmPacket";
+
+/// SH.TLM_ID を表すパラメータの名前
+const TLM_ID_PARAMETER: &str = "TLM_ID";
+
+/// 全コマンドの親となる抽象コマンドの名前
+const BASE_COMMAND: &str = "C2aCommand";
+
+pub fn generate(component: &Component) -> Result<Vec<GeneratedFile>> {
+    let mut data_types = Sheet::new(&[
+        "type name",
+        "eng type",
+        "raw type",
+        "encoding",
+        "eng unit",
+        "calibration",
+        "initial value",
+        "description",
+    ]);
+    let mut parameters = Sheet::new(&[
+        "parameter name",
+        "data type",
+        "description",
+        "long description",
+    ]);
+    let mut containers = Sheet::new(&[
+        "container name",
+        "parent",
+        "condition",
+        "flags",
+        "entry",
+        "position",
+        "size in bits",
+        "expected interval",
+        "description",
+    ]);
+    let mut calibration = Sheet::new(&["calibrator name", "type", "calib1", "calib2"]);
+    write_telemetries(
+        component,
+        &mut data_types,
+        &mut parameters,
+        &mut containers,
+        &mut calibration,
+    );
+
+    let mut commands = Sheet::new(&[
+        "command name",
+        "parent",
+        "argument assignment",
+        "flags",
+        "argument name",
+        "relpos",
+        "position",
+        "data type",
+        "default value",
+        "range low",
+        "range high",
+        "description",
+    ]);
+    let mut command_options = Sheet::new(&[
+        "command name",
+        "transmission constraints",
+        "command significance",
+        "significance reason",
+    ]);
+    write_commands(
+        component,
+        &mut data_types,
+        &mut commands,
+        &mut command_options,
+    );
+
+    let mut general = Sheet::new(&["format version", "name", "document version"]);
+    general.push(vec![
+        FORMAT_VERSION.to_string(),
+        yamcs_name(&component.name),
+        String::new(),
+    ]);
+
+    Ok(vec![
+        GeneratedFile::new("General.csv", general.to_csv()?),
+        GeneratedFile::new("DataTypes.csv", data_types.to_csv()?),
+        GeneratedFile::new("Parameters.csv", parameters.to_csv()?),
+        GeneratedFile::new("Containers.csv", containers.to_csv()?),
+        GeneratedFile::new("Calibration.csv", calibration.to_csv()?),
+        GeneratedFile::new("Commands.csv", commands.to_csv()?),
+        GeneratedFile::new("CommandOptions.csv", command_options.to_csv()?),
+    ])
+}
+
+/// スプレッドシートの1シート
+struct Sheet {
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+impl Sheet {
+    fn new(header: &'static [&'static str]) -> Self {
+        Self {
+            header,
+            rows: vec![],
+        }
+    }
+
+    /// 行を追加する。足りない列は空欄にする
+    fn push(&mut self, mut row: Vec<String>) {
+        row.resize(self.header.len(), String::new());
+        self.rows.push(row);
+    }
+
+    fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(vec![]);
+        writer.write_record(self.header)?;
+        for row in &self.rows {
+            writer.write_record(row)?;
+        }
+        Ok(String::from_utf8(writer.into_inner()?)?)
+    }
+}
+
+/// Yamcs の名前として使えない文字を `_` に置き換える
+fn yamcs_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn write_telemetries(
+    component: &Component,
+    data_types: &mut Sheet,
+    parameters: &mut Sheet,
+    containers: &mut Sheet,
+    calibration: &mut Sheet,
+) {
+    let tlm_id_type = format!("{}_t", TLM_ID_PARAMETER);
+    data_types.push(vec![
+        tlm_id_type.clone(),
+        "uint".to_string(),
+        "uint".to_string(),
+        format!("unsigned({})", TLM_ID_LEN * 8),
+    ]);
+    parameters.push(vec![
+        TLM_ID_PARAMETER.to_string(),
+        tlm_id_type,
+        "SH.TLM_ID".to_string(),
+    ]);
+    containers.push(vec![
+        ROOT_CONTAINER.to_string(),
+        String::new(),
+        String::new(),
+        "A".to_string(),
+    ]);
+    containers.push(vec![
+        String::new(),
+        String::new(),
+        String::new(),
+        String::new(),
+        TLM_ID_PARAMETER.to_string(),
+        (TLM_ID_OFFSET * 8).to_string(),
+    ]);
+
+    for telemetry in &component.tlm.telemetries {
+        let metadata = &telemetry.metadata;
+        containers.push(vec![
+            yamcs_name(&telemetry.name),
+            ROOT_CONTAINER.to_string(),
+            format!("{}=={}", TLM_ID_PARAMETER, metadata.packet_id),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            metadata.target.clone(),
+        ]);
+        // blob テレメトリはフィールドをもたないため、エントリのないコンテナとなる
+        for (field_group, field) in telemetry.fields() {
+            let name = yamcs_name(&format!("{}.{}", telemetry.name, field.name));
+            let data_type = format!("{}_t", name);
+            let calibrator = write_calibrator(calibration, &name, &field.conversion_info);
+            let variable_type = field_group.onboard_software_info.variable_type;
+            let info = &field.extraction_info;
+            let unit = field
+                .display_info
+                .as_ref()
+                .map(|display_info| display_info.unit.clone())
+                .unwrap_or_default();
+            data_types.push(vec![
+                data_type.clone(),
+                eng_type(variable_type, field).to_string(),
+                raw_type(variable_type).to_string(),
+                encoding(variable_type, info.bit_length),
+                unit,
+                calibrator,
+            ]);
+            parameters.push(vec![
+                name.clone(),
+                data_type,
+                field.description.clone(),
+                field.note.clone(),
+            ]);
+            containers.push(vec![
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                name,
+                (info.octet_position * 8 + info.bit_position).to_string(),
+                info.bit_length.to_string(),
+            ]);
+        }
+    }
+}
+
+fn raw_type(variable_type: tlm::VariableType) -> &'static str {
+    match variable_type {
+        tlm::VariableType::Float | tlm::VariableType::Double => "float",
+        variable_type if variable_type.is_unsigned_integer() => "uint",
+        _ => "int",
+    }
+}
+
+fn encoding(variable_type: tlm::VariableType, bit_length: usize) -> String {
+    match variable_type {
+        tlm::VariableType::Float | tlm::VariableType::Double => {
+            format!("IEEE754_1985({})", bit_length)
+        }
+        variable_type if variable_type.is_unsigned_integer() => format!("unsigned({})", bit_length),
+        _ => format!("twosComplement({})", bit_length),
+    }
+}
+
+fn eng_type(variable_type: tlm::VariableType, field: &tlm::Field) -> &'static str {
+    match field.conversion_info {
+        tlm::ConversionInfo::Status(_) => "enumerated",
+        tlm::ConversionInfo::Polynomial(_) => "double",
+        tlm::ConversionInfo::None | tlm::ConversionInfo::Hex => match variable_type {
+            tlm::VariableType::Float => "float",
+            tlm::VariableType::Double => "double",
+            variable_type if variable_type.is_unsigned_integer() => "uint",
+            _ => "int",
+        },
+    }
+}
+
+/// 工学値変換があれば Calibration シートに書き出し、その名前を返す
+///
+/// ステータス変換の `default_value` は表現できないため無視する。
+fn write_calibrator(
+    calibration: &mut Sheet,
+    parameter_name: &str,
+    conversion_info: &tlm::ConversionInfo,
+) -> String {
+    let name = format!("{}_cal", parameter_name);
+    match conversion_info {
+        tlm::ConversionInfo::None | tlm::ConversionInfo::Hex => return String::new(),
+        tlm::ConversionInfo::Polynomial(polynomial) => {
+            for (i, coefficient) in polynomial_coefficients(polynomial).iter().enumerate() {
+                let (name, calibrator_type) = if i == 0 {
+                    (name.clone(), "polynomial".to_string())
+                } else {
+                    (String::new(), String::new())
+                };
+                calibration.push(vec![name, calibrator_type, coefficient.to_string()]);
+            }
+        }
+        tlm::ConversionInfo::Status(status) => {
+            for (i, variant) in status.variants.iter().enumerate() {
+                let (name, calibrator_type) = if i == 0 {
+                    (name.clone(), "enumeration".to_string())
+                } else {
+                    (String::new(), String::new())
+                };
+                calibration.push(vec![
+                    name,
+                    calibrator_type,
+                    variant.key.to_string(),
+                    variant.value.clone(),
+                ]);
+            }
+        }
+    }
+    name
+}
+
+/// 多項式の係数。高次の0の係数は除く
+fn polynomial_coefficients(polynomial: &conversion::Polynomial) -> Vec<f64> {
+    let mut coefficients = vec![
+        polynomial.a0,
+        polynomial.a1,
+        polynomial.a2,
+        polynomial.a3,
+        polynomial.a4,
+        polynomial.a5,
+    ];
+    while coefficients.len() > 2 && coefficients.last() == Some(&0.0) {
+        coefficients.pop();
+    }
+    coefficients
+}
+
+/// 各 [cmd::DataType] に対応する引数の型の、工学値の型・生値の型・エンコーディング
+fn argument_type(data_type: cmd::DataType) -> (&'static str, &'static str, &'static str) {
+    match data_type {
+        cmd::DataType::Int8 => ("int", "int", "twosComplement(8)"),
+        cmd::DataType::Int16 => ("int", "int", "twosComplement(16)"),
+        cmd::DataType::Int32 => ("int", "int", "twosComplement(32)"),
+        cmd::DataType::Uint8 => ("uint", "uint", "unsigned(8)"),
+        cmd::DataType::Uint16 => ("uint", "uint", "unsigned(16)"),
+        cmd::DataType::Uint32 => ("uint", "uint", "unsigned(32)"),
+        cmd::DataType::Float => ("float", "float", "IEEE754_1985(32)"),
+        cmd::DataType::Double => ("double", "float", "IEEE754_1985(64)"),
+        cmd::DataType::Raw => ("binary", "binary", "binary"),
+    }
+}
+
+const DATA_TYPES: [cmd::DataType; 9] = [
+    cmd::DataType::Int8,
+    cmd::DataType::Int16,
+    cmd::DataType::Int32,
+    cmd::DataType::Uint8,
+    cmd::DataType::Uint16,
+    cmd::DataType::Uint32,
+    cmd::DataType::Float,
+    cmd::DataType::Double,
+    cmd::DataType::Raw,
+];
+
+fn write_commands(
+    component: &Component,
+    data_types: &mut Sheet,
+    commands: &mut Sheet,
+    command_options: &mut Sheet,
+) {
+    for data_type in DATA_TYPES {
+        let (eng_type, raw_type, encoding) = argument_type(data_type);
+        data_types.push(vec![
+            data_type.as_str().to_string(),
+            eng_type.to_string(),
+            raw_type.to_string(),
+            encoding.to_string(),
+        ]);
+    }
+    commands.push(vec![
+        BASE_COMMAND.to_string(),
+        String::new(),
+        String::new(),
+        "A".to_string(),
+    ]);
+    let mut position = 0;
+    for field in HEADER_FIELDS {
+        let data_type = format!("{}_t", field.name);
+        data_types.push(vec![
+            data_type.clone(),
+            "uint".to_string(),
+            "uint".to_string(),
+            format!("unsigned({})", field.bit_length),
+        ]);
+        commands.push(vec![
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            field.name.to_string(),
+            String::new(),
+            position.to_string(),
+            data_type,
+            field.default_value.to_string(),
+            String::new(),
+            String::new(),
+            field.description.to_string(),
+        ]);
+        position += field.bit_length;
+    }
+
+    for entry in &component.cmd.entries {
+        let cmd::Entry::Command(command) = entry else {
+            continue;
+        };
+        let name = yamcs_name(&command.name);
+        commands.push(vec![
+            name.clone(),
+            BASE_COMMAND.to_string(),
+            format!(
+                "{}=0x{:04X};{}={}",
+                CMD_ID_FIELD,
+                command.code,
+                PACKET_LENGTH_FIELD,
+                packet_data_length(command)
+            ),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            String::new(),
+            command.description.clone(),
+        ]);
+        for (i, parameter) in command.parameters.iter().enumerate() {
+            commands.push(vec![
+                String::new(),
+                String::new(),
+                String::new(),
+                String::new(),
+                format!("Param{}", i + 1),
+                String::new(),
+                String::new(),
+                parameter.data_type.as_str().to_string(),
+                String::new(),
+                String::new(),
+                String::new(),
+                parameter.description.clone(),
+            ]);
+        }
+        if command.is_danger {
+            command_options.push(vec![
+                name,
+                String::new(),
+                "critical".to_string(),
+                "danger flag is set in the CMD DB".to_string(),
+            ]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tlmcmddb::cmd::encode::CMD_ID_OFFSET;
+
+    #[test]
+    fn test_generate() {
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/TLM_DB/valid.json");
+        let mut telemetry: tlm::Telemetry = serde_json::from_slice(json).unwrap();
+        telemetry.name = "HK".to_string();
+        let json = include_bytes!("../../tlmcmddb-csv/fixtures/CMD_DB/valid.json");
+        let cmd: cmd::Database = serde_json::from_slice(json).unwrap();
+        let component = Component {
+            name: "MOBC".to_string(),
+            tlm: tlm::Database {
+                telemetries: vec![telemetry],
+            },
+            cmd,
+            bct: Default::default(),
+        };
+        let files = generate(&component).unwrap();
+        let file = |name: &str| &files.iter().find(|file| file.name == name).unwrap().content;
+
+        assert_eq!(
+            "format version,name,document version\n7.0,MOBC,\n",
+            file("General.csv")
+        );
+        assert!(file("Containers.csv").contains("\nHK,TlmPacket,TLM_ID==240,,,,,,OBC\n"));
+        assert!(file("Containers.csv").contains("\n,,,,HK_PH_VER,0,3,,\n"));
+        assert!(file("DataTypes.csv").contains("\nHK_PH_VER_t,uint,uint,unsigned(3),,,,\n"));
+        assert!(file("Parameters.csv").contains("\nHK_PH_VER,HK_PH_VER_t,"));
+        assert!(file("Calibration.csv").contains(",enumeration,0,"));
+        // ヘッダを含めた位置にコマンドコードを置く
+        assert!(file("Commands.csv").contains(&format!(
+            "\n,,,,CMD_CODE,,{},CMD_CODE_t,0,,,command ID\n",
+            CMD_ID_OFFSET * 8
+        )));
+        assert!(file("DataTypes.csv").contains("\nCCSDS_APID_t,uint,uint,unsigned(11),,,,\n"));
+        assert!(
+            file("Commands.csv").contains("\nCmd_NOP,C2aCommand,CMD_CODE=0x0000;CCSDS_LENGTH=8,")
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::io::Read;
+
+use anyhow::{ensure, Result};
+use csv::StringRecord;
+use serde::Deserialize;
+use tlmcmddb::bct as model;
+
+use crate::{
+    diagnostic::{at_record, deserialize_error, is_valid_value},
+    escape::unescape,
+    macros::check_header,
+    util,
+};
+
+/*
++----------+-------+------------+-------+-------------------------------------------------------------+---------+--------------+-------+
+| Comment  | Name  | ShortName  | BCID  |                          エイリアス                          | Danger  | Description  | Note  |
+|          |       |            |       +---------+-------------------+--------+-----------+-----------+ Flag    |              |       |
+|          |       |            |       | Deploy  | SetBlockPosition  | Clear  | Activate  | Inactivate|         |              |       |
++----------+-------+------------+-------+---------+-------------------+--------+-----------+-----------+---------+--------------+-------+
+*/
+
+mod header {
+    pub const COMMENT: &str = "Comment";
+    pub const NAME: &str = "Name";
+    pub const SHORT_NAME: &str = "ShortName";
+    pub const BCID: &str = "BCID";
+    pub const ALIASES: &str = "エイリアス";
+    pub const DANGER_FLAG: &str = "Danger Flag";
+    pub const DESCRIPTION: &str = "Description";
+    pub const NOTE: &str = "Note";
+    pub const DEPLOY: &str = "Deploy";
+    pub const SET_BLOCK_POSITION: &str = "SetBlockPosition";
+    pub const CLEAR: &str = "Clear";
+    pub const ACTIVATE: &str = "Activate";
+    pub const INACTIVATE: &str = "Inactivate";
+}
+
+/// BCT CSV の列数
+const NUM_COLUMNS: usize = 12;
+
+mod column {
+    pub const DEPLOY: usize = 4;
+    pub const INACTIVATE: usize = 8;
+    pub const DANGER_FLAG: usize = 9;
+}
+
+/// エラー表示に用いる列のヘッダ名
+fn column_header(column: usize) -> Option<&'static str> {
+    const HEADERS: [&str; NUM_COLUMNS] = [
+        header::COMMENT,
+        header::NAME,
+        header::SHORT_NAME,
+        header::BCID,
+        header::DEPLOY,
+        header::SET_BLOCK_POSITION,
+        header::CLEAR,
+        header::ACTIVATE,
+        header::INACTIVATE,
+        header::DANGER_FLAG,
+        header::DESCRIPTION,
+        header::NOTE,
+    ];
+    HEADERS.get(column).copied()
+}
+
+fn check_first_header(record: &StringRecord) -> Result<()> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch"
+    );
+    check_header!(record, 0, header::COMMENT);
+    check_header!(record, 1, header::NAME);
+    check_header!(record, 2, header::SHORT_NAME);
+    check_header!(record, 3, header::BCID);
+    check_header!(record, 4, header::ALIASES);
+    check_header!(record, 9, header::DANGER_FLAG);
+    check_header!(record, 10, header::DESCRIPTION);
+    check_header!(record, 11, header::NOTE);
+    Ok(())
+}
+
+fn check_second_header(record: &StringRecord) -> Result<()> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch"
+    );
+    check_header!(record, 4, header::DEPLOY);
+    check_header!(record, 5, header::SET_BLOCK_POSITION);
+    check_header!(record, 6, header::CLEAR);
+    check_header!(record, 7, header::ACTIVATE);
+    check_header!(record, 8, header::INACTIVATE);
+    Ok(())
+}
+
+fn build_comment(record: &StringRecord) -> model::Comment {
+    let text = record.iter().map(unescape).collect::<Vec<_>>().join(",");
+    model::Comment { text }
+}
+
+/// serde の独自エラーとなりうる列のうち、解釈できない値をもつ最初の列
+fn invalid_column(record: &StringRecord) -> Option<usize> {
+    (column::DEPLOY..=column::INACTIVATE)
+        .find(|&column| !is_valid_value::<AliasMark>(&record[column]))
+        .or_else(|| {
+            (!is_valid_value::<DangerFlag>(&record[column::DANGER_FLAG]))
+                .then_some(column::DANGER_FLAG)
+        })
+}
+
+fn parse_line(record: &StringRecord) -> Result<model::Entry> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch"
+    );
+    if record[0].is_empty() {
+        let line: Line = record
+            .deserialize(None)
+            .map_err(|err| deserialize_error(err, record, invalid_column))?;
+        Ok(model::Entry::BlockCommand(line.into()))
+    } else {
+        Ok(model::Entry::Comment(build_comment(record)))
+    }
+}
+
+pub fn parse<I, E>(mut iter: I) -> Result<model::Database>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let record = util::next_record(&mut iter)?;
+    check_first_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let record = util::next_record(&mut iter)?;
+    check_second_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let mut entries = vec![];
+    while let Some(record) = util::try_next_record(&mut iter)? {
+        let entry = parse_line(&record).map_err(|err| at_record(err, &record, column_header))?;
+        entries.push(entry);
+    }
+    Ok(model::Database { entries })
+}
+
+pub fn parse_csv<R: Read>(rdr: R) -> Result<model::Database> {
+    let mut csv = crate::csv_reader_builder().from_reader(rdr);
+    let mut iter = csv.records();
+    parse(&mut iter)
+}
+
+#[derive(Debug, Deserialize)]
+struct Line {
+    _comment_mark: String,
+    name: String,
+    short_name: String,
+    bcid: u16,
+    deploy: Option<AliasMark>,
+    set_block_position: Option<AliasMark>,
+    clear: Option<AliasMark>,
+    activate: Option<AliasMark>,
+    inactivate: Option<AliasMark>,
+    danger_flag: Option<DangerFlag>,
+    description: String,
+    note: String,
+}
+
+impl From<Line> for model::BlockCommand {
+    fn from(line: Line) -> Self {
+        model::BlockCommand {
+            name: unescape(&line.name),
+            short_name: unescape(&line.short_name),
+            bcid: line.bcid,
+            aliases: model::Aliases {
+                deploy: line.deploy.is_some(),
+                set_block_position: line.set_block_position.is_some(),
+                clear: line.clear.is_some(),
+                activate: line.activate.is_some(),
+                inactivate: line.inactivate.is_some(),
+            },
+            is_danger: line.danger_flag.is_some(),
+            description: unescape(&line.description),
+            note: unescape(&line.note),
+        }
+    }
+}
+
+/// エイリアスコマンドを生成することを示す印
+#[derive(Debug, Deserialize)]
+enum AliasMark {
+    #[serde(rename = "○")]
+    Marked,
+}
+
+#[derive(Debug, Deserialize)]
+enum DangerFlag {
+    #[serde(rename = "danger")]
+    Danger,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test() {
+        let csv = include_bytes!("../fixtures/BCT/valid.csv");
+        let json = include_bytes!("../fixtures/BCT/valid.json");
+        let expected: model::Database = serde_json::from_slice(json).unwrap();
+        let actual = parse_csv(csv.as_slice()).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_invalid_alias() {
+        let csv = std::str::from_utf8(include_bytes!("../fixtures/BCT/valid.csv"))
+            .unwrap()
+            .replacen(",○,", ",x,", 1);
+        let err = parse_csv(csv.as_bytes()).unwrap_err();
+        let diagnostic = err.downcast_ref::<crate::diagnostic::Diagnostic>().unwrap();
+        assert_eq!(Some(column::DEPLOY), diagnostic.column);
+        assert_eq!(Some("Deploy"), diagnostic.header.as_deref());
+    }
+}
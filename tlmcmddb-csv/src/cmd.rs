@@ -1,11 +1,19 @@
-use std::io::Read;
+use std::io::{Read, Write};
 
 use anyhow::{anyhow, ensure, Result};
 use csv::StringRecord;
-use serde::{de::Visitor, Deserialize, Deserializer};
+use serde::{
+    de::{value, IntoDeserializer, Visitor},
+    Deserialize, Deserializer,
+};
 use tlmcmddb::cmd as model;
 
-use crate::{escape::unescape, macros::check_header, util};
+use crate::{
+    diagnostic::{at_record, deserialize_error, is_valid_value, Diagnostic},
+    escape::{escape, unescape},
+    macros::{check_header, ensure_column},
+    util,
+};
 
 /*
 +------------+-------+---------+-------+---------------------------------------------------------------------------------------------------------------------------+---------+-------------+--------------+-------+
@@ -39,98 +47,310 @@ mod header {
     pub const PARAM_DESCRIPTION: &str = "Description";
 }
 
-fn check_first_header(record: StringRecord) -> Result<()> {
-    ensure!(record.len() >= 21, "the number of columns is mismatch");
-    check_header!(&record[0], header::COMPONENT);
-    check_header!(&record[1], header::NAME);
-    check_header!(&record[2], header::TARGET);
-    check_header!(&record[3], header::CODE);
-    check_header!(&record[4], header::PARAMS);
-    check_header!(&record[17], header::DANGER_FLAG);
-    check_header!(&record[18], header::IS_RESTRICTED);
-    check_header!(&record[19], header::DESCRIPTION);
-    check_header!(&record[20], header::NOTE);
+/// CMD DB CSV の列数
+const NUM_COLUMNS: usize = 21;
+
+mod column {
+    pub const CODE: usize = 3;
+    pub const NUM_PARAMS: usize = 4;
+    pub const PARAM1_TYPE: usize = 5;
+    pub const PARAM2_TYPE: usize = 7;
+    pub const PARAM3_TYPE: usize = 9;
+    pub const PARAM4_TYPE: usize = 11;
+    pub const PARAM5_TYPE: usize = 13;
+    pub const PARAM6_TYPE: usize = 15;
+    pub const DANGER_FLAG: usize = 17;
+    pub const IS_RESTRICTED: usize = 18;
+}
+
+/// エラー表示に用いる列のヘッダ名
+fn column_header(column: usize) -> Option<&'static str> {
+    const HEADERS: [&str; NUM_COLUMNS] = [
+        "Comment",
+        "Name",
+        "Target",
+        "Code",
+        "Num Params",
+        "Param1 Type",
+        "Param1 Description",
+        "Param2 Type",
+        "Param2 Description",
+        "Param3 Type",
+        "Param3 Description",
+        "Param4 Type",
+        "Param4 Description",
+        "Param5 Type",
+        "Param5 Description",
+        "Param6 Type",
+        "Param6 Description",
+        "Danger Flag",
+        "Is Restricted",
+        "Description",
+        "Note",
+    ];
+    HEADERS.get(column).copied()
+}
+
+fn check_first_header(record: &StringRecord) -> Result<()> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch: expected {}, but found {}",
+        NUM_COLUMNS,
+        record.len()
+    );
+    check_header!(record, 0, header::COMPONENT);
+    check_header!(record, 1, header::NAME);
+    check_header!(record, 2, header::TARGET);
+    check_header!(record, 3, header::CODE);
+    check_header!(record, 4, header::PARAMS);
+    check_header!(record, 17, header::DANGER_FLAG);
+    check_header!(record, 18, header::IS_RESTRICTED);
+    check_header!(record, 19, header::DESCRIPTION);
+    check_header!(record, 20, header::NOTE);
     Ok(())
 }
 
-fn parse_second_header(record: StringRecord) -> Result<String> {
+fn parse_second_header(record: &StringRecord) -> Result<String> {
     ensure!(record.len() >= 16, "the number of columns is mismatch");
-    check_header!(&record[4], header::NUM_PARAMS);
-    check_header!(&record[5], header::PARAM1);
-    check_header!(&record[7], header::PARAM2);
-    check_header!(&record[9], header::PARAM3);
-    check_header!(&record[11], header::PARAM4);
-    check_header!(&record[13], header::PARAM5);
-    check_header!(&record[15], header::PARAM6);
+    check_header!(record, 4, header::NUM_PARAMS);
+    check_header!(record, 5, header::PARAM1);
+    check_header!(record, 7, header::PARAM2);
+    check_header!(record, 9, header::PARAM3);
+    check_header!(record, 11, header::PARAM4);
+    check_header!(record, 13, header::PARAM5);
+    check_header!(record, 15, header::PARAM6);
     let component = unescape(&record[0]);
     Ok(component)
 }
 
-fn check_third_header(record: StringRecord) -> Result<()> {
+fn check_third_header(record: &StringRecord) -> Result<()> {
     ensure!(record.len() >= 17, "the number of columns is mismatch");
-    check_header!(&record[0], header::COMMENT);
-    check_header!(&record[5], header::PARAM_TYPE);
-    check_header!(&record[6], header::PARAM_DESCRIPTION);
-    check_header!(&record[7], header::PARAM_TYPE);
-    check_header!(&record[8], header::PARAM_DESCRIPTION);
-    check_header!(&record[9], header::PARAM_TYPE);
-    check_header!(&record[10], header::PARAM_DESCRIPTION);
-    check_header!(&record[11], header::PARAM_TYPE);
-    check_header!(&record[12], header::PARAM_DESCRIPTION);
-    check_header!(&record[13], header::PARAM_TYPE);
-    check_header!(&record[14], header::PARAM_DESCRIPTION);
-    check_header!(&record[15], header::PARAM_TYPE);
-    check_header!(&record[16], header::PARAM_DESCRIPTION);
+    check_header!(record, 0, header::COMMENT);
+    check_header!(record, 5, header::PARAM_TYPE);
+    check_header!(record, 6, header::PARAM_DESCRIPTION);
+    check_header!(record, 7, header::PARAM_TYPE);
+    check_header!(record, 8, header::PARAM_DESCRIPTION);
+    check_header!(record, 9, header::PARAM_TYPE);
+    check_header!(record, 10, header::PARAM_DESCRIPTION);
+    check_header!(record, 11, header::PARAM_TYPE);
+    check_header!(record, 12, header::PARAM_DESCRIPTION);
+    check_header!(record, 13, header::PARAM_TYPE);
+    check_header!(record, 14, header::PARAM_DESCRIPTION);
+    check_header!(record, 15, header::PARAM_TYPE);
+    check_header!(record, 16, header::PARAM_DESCRIPTION);
     Ok(())
 }
 
 fn build_comment(record: StringRecord) -> model::Comment {
-    let mut text = String::new();
-    for col in record.iter() {
-        text.push_str(&unescape(col));
-        text.push(',');
-    }
-    text.truncate(text.len() - 1); // trim last comma
+    let text = util::comment_text(&record);
     model::Comment { text }
 }
 
-fn parse_body<I, E>(mut iter: I) -> Result<Vec<model::Entry>>
+/// 行ごとのエラーは `report` に渡す。`report` がエラーを返した場合はそこで中断する
+fn parse_body<I, E>(
+    mut iter: I,
+    mut report: impl FnMut(anyhow::Error) -> Result<()>,
+) -> Result<Vec<model::Entry>>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
     let mut entries = vec![];
     while let Some(record) = util::try_next_record(&mut iter)? {
-        ensure!(record.len() >= 21, "the number of columns is mismatch");
-        if record[0].is_empty() {
-            let line: Line = record.deserialize(None)?;
-            let command = line.try_into()?;
-            entries.push(model::Entry::Command(command));
-        } else {
-            entries.push(model::Entry::Comment(build_comment(record)));
+        match parse_line(&record) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => report(at_record(err, &record, column_header))?,
         }
     }
     Ok(entries)
 }
 
+/// serde の独自エラーとなりうる列のうち、解釈できない値をもつ最初の列
+fn invalid_column(record: &StringRecord) -> Option<usize> {
+    let code: value::StrDeserializer<value::Error> = record[column::CODE].into_deserializer();
+    if deserialize_hex_with_0x(code).is_err() {
+        return Some(column::CODE);
+    }
+    (column::PARAM1_TYPE..=column::PARAM6_TYPE)
+        .step_by(2)
+        .find(|&column| !is_valid_value::<model::DataType>(&record[column]))
+        .or_else(|| {
+            (!is_valid_value::<DangerFlag>(&record[column::DANGER_FLAG]))
+                .then_some(column::DANGER_FLAG)
+        })
+        .or_else(|| {
+            (!is_valid_value::<IsRestricted>(&record[column::IS_RESTRICTED]))
+                .then_some(column::IS_RESTRICTED)
+        })
+}
+
+fn parse_line(record: &StringRecord) -> Result<model::Entry> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch: expected {}, but found {}",
+        NUM_COLUMNS,
+        record.len()
+    );
+    if record[0].is_empty() {
+        let line: Line = record
+            .deserialize(None)
+            .map_err(|err| deserialize_error(err, record, invalid_column))?;
+        let command = line.try_into()?;
+        Ok(model::Entry::Command(command))
+    } else {
+        Ok(model::Entry::Comment(build_comment(record.clone())))
+    }
+}
+
 pub fn parse<I, E>(mut iter: I) -> Result<(String, model::Database)>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    check_first_header(util::next_record(&mut iter)?)?;
-    let component = parse_second_header(util::next_record(&mut iter)?)?;
-    check_third_header(util::next_record(&mut iter)?)?;
-    let entries = parse_body(&mut iter)?;
+    let component = parse_headers(&mut iter)?;
+    let entries = parse_body(&mut iter, Err)?;
     Ok((component, model::Database { entries }))
 }
 
+/// 不正な行を読み飛ばしながら読み込み、読み込めたコマンド定義と各行の [Diagnostic] を返す
+///
+/// ヘッダが不正な場合など、読み込みを続けられない場合はエラーを返す。
+pub fn parse_recovering<I, E>(mut iter: I) -> Result<(String, model::Database, Vec<Diagnostic>)>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let component = parse_headers(&mut iter)?;
+    let mut diagnostics = vec![];
+    let entries = parse_body(&mut iter, |err| {
+        diagnostics.push(err.into());
+        Ok(())
+    })?;
+    Ok((component, model::Database { entries }, diagnostics))
+}
+
+fn parse_headers<I, E>(mut iter: I) -> Result<String>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let record = util::next_record(&mut iter)?;
+    check_first_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let record = util::next_record(&mut iter)?;
+    let component =
+        parse_second_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let record = util::next_record(&mut iter)?;
+    check_third_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    Ok(component)
+}
+
 pub fn parse_csv<R: Read>(rdr: R) -> Result<(String, model::Database)> {
     let mut csv = crate::csv_reader_builder().from_reader(rdr);
     let mut iter = csv.records();
     parse(&mut iter)
 }
 
+/// CMD DB CSV を、不正な行を読み飛ばしながら読み込む
+pub fn parse_csv_recovering<R: Read>(rdr: R) -> Result<(String, model::Database, Vec<Diagnostic>)> {
+    // 列数の異なる行はパーサが行ごとに報告する
+    let mut csv = crate::csv_reader_builder().flexible(true).from_reader(rdr);
+    let mut iter = csv.records();
+    parse_recovering(&mut iter)
+}
+
+fn write_headers<W: Write>(wtr: &mut csv::Writer<W>, component: &str) -> Result<()> {
+    let mut first = vec![""; NUM_COLUMNS];
+    first[0] = header::COMPONENT;
+    first[1] = header::NAME;
+    first[2] = header::TARGET;
+    first[3] = header::CODE;
+    first[4] = header::PARAMS;
+    first[17] = header::DANGER_FLAG;
+    first[18] = header::IS_RESTRICTED;
+    first[19] = header::DESCRIPTION;
+    first[20] = header::NOTE;
+    util::write_padded_record(wtr, first, NUM_COLUMNS)?;
+
+    let component = escape(component);
+    let mut second = vec![""; NUM_COLUMNS];
+    second[0] = &component;
+    second[4] = header::NUM_PARAMS;
+    second[5] = header::PARAM1;
+    second[7] = header::PARAM2;
+    second[9] = header::PARAM3;
+    second[11] = header::PARAM4;
+    second[13] = header::PARAM5;
+    second[15] = header::PARAM6;
+    util::write_padded_record(wtr, second, NUM_COLUMNS)?;
+
+    let mut third = vec![""; NUM_COLUMNS];
+    third[0] = header::COMMENT;
+    for i in 0..6 {
+        third[5 + i * 2] = header::PARAM_TYPE;
+        third[6 + i * 2] = header::PARAM_DESCRIPTION;
+    }
+    util::write_padded_record(wtr, third, NUM_COLUMNS)?;
+    Ok(())
+}
+
+fn write_command<W: Write>(wtr: &mut csv::Writer<W>, command: &model::Command) -> Result<()> {
+    ensure!(
+        command.parameters.len() <= 6,
+        "the number of parameters must be less than or equal to 6"
+    );
+    let mut record = vec![
+        String::new(),
+        escape(&command.name),
+        escape(&command.target),
+        format!("0x{:04X}", command.code),
+        command.parameters.len().to_string(),
+    ];
+    for i in 0..6 {
+        if let Some(parameter) = command.parameters.get(i) {
+            record.push(parameter.data_type.as_str().to_string());
+            record.push(escape(&parameter.description));
+        } else {
+            record.push(String::new());
+            record.push(String::new());
+        }
+    }
+    record.push(if command.is_danger { "danger" } else { "" }.to_string());
+    record.push(
+        if command.is_restricted {
+            "restricted"
+        } else {
+            ""
+        }
+        .to_string(),
+    );
+    record.push(escape(&command.description));
+    record.push(escape(&command.note));
+    util::write_padded_record(wtr, record, NUM_COLUMNS)
+}
+
+/// [parse] で読み込める形式で、ヘッダとエントリを書き出す
+pub fn write<W: Write>(
+    wtr: &mut csv::Writer<W>,
+    component: &str,
+    database: &model::Database,
+) -> Result<()> {
+    write_headers(wtr, component)?;
+    for entry in &database.entries {
+        match entry {
+            model::Entry::Command(command) => write_command(wtr, command)?,
+            model::Entry::Comment(comment) => util::write_comment(wtr, &comment.text, NUM_COLUMNS)?,
+        }
+    }
+    Ok(())
+}
+
+pub fn write_csv<W: Write>(component: &str, database: &model::Database, wtr: W) -> Result<()> {
+    let mut csv = crate::csv_writer_builder().from_writer(wtr);
+    write(&mut csv, component, database)?;
+    csv.flush()?;
+    Ok(())
+}
+
 #[derive(Debug, Deserialize)]
 struct Line {
     _comment_mark: String,
@@ -162,13 +382,16 @@ impl TryFrom<Line> for model::Command {
 
     fn try_from(line: Line) -> Result<Self, Self::Error> {
         let mut parameters = vec![];
-        ensure!(
+        ensure_column!(
             line.num_params <= 6,
+            column::NUM_PARAMS,
             "Num Params must be less than or equal to 6"
         );
         if line.num_params >= 1 {
             let Some(data_type) = line.param1_type else {
-                return Err(anyhow!("Param1 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM1_TYPE, "Param1 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -177,7 +400,9 @@ impl TryFrom<Line> for model::Command {
         }
         if line.num_params >= 2 {
             let Some(data_type) = line.param2_type else {
-                return Err(anyhow!("Param2 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM2_TYPE, "Param2 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -186,7 +411,9 @@ impl TryFrom<Line> for model::Command {
         }
         if line.num_params >= 3 {
             let Some(data_type) = line.param3_type else {
-                return Err(anyhow!("Param3 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM3_TYPE, "Param3 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -195,7 +422,9 @@ impl TryFrom<Line> for model::Command {
         }
         if line.num_params >= 4 {
             let Some(data_type) = line.param4_type else {
-                return Err(anyhow!("Param4 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM4_TYPE, "Param4 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -204,7 +433,9 @@ impl TryFrom<Line> for model::Command {
         }
         if line.num_params >= 5 {
             let Some(data_type) = line.param5_type else {
-                return Err(anyhow!("Param5 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM5_TYPE, "Param5 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -213,7 +444,9 @@ impl TryFrom<Line> for model::Command {
         }
         if line.num_params >= 6 {
             let Some(data_type) = line.param6_type else {
-                return Err(anyhow!("Param6 Type is missing"));
+                return Err(
+                    Diagnostic::at_column(column::PARAM6_TYPE, "Param6 Type is missing").into(),
+                );
             };
             parameters.push(model::Parameter {
                 data_type,
@@ -288,4 +521,76 @@ mod tests {
         let (_component, actual) = parse(&mut iter).unwrap();
         assert_eq!(expected, actual);
     }
+
+    #[test]
+    fn test_write_csv_roundtrip() {
+        let csv = include_bytes!("../fixtures/CMD_DB/valid.csv");
+        let (component, expected) = parse_csv(csv.as_slice()).unwrap();
+
+        let mut written = vec![];
+        write_csv(&component, &expected, &mut written).unwrap();
+        let (actual_component, actual) = parse_csv(written.as_slice()).unwrap();
+
+        assert_eq!(component, actual_component);
+        assert_eq!(expected, actual);
+        // ヘッダはそのまま書き出される
+        let expected_headers = std::str::from_utf8(csv).unwrap().lines().take(3);
+        let written = String::from_utf8(written).unwrap();
+        assert!(expected_headers.eq(written.lines().take(3)));
+    }
+
+    #[test]
+    fn test_write_csv_comment_roundtrip() {
+        let csv = include_bytes!("../fixtures/CMD_DB/valid.csv");
+        let (component, mut expected) = parse_csv(csv.as_slice()).unwrap();
+        // CSV から読み込んでいないコメントも、列数の埋め合わせで末尾に `,` が増えない
+        for text in ["* NEW SECTION", "*,列が,少ない", "*,途中の,,空の列"] {
+            expected.entries.push(model::Entry::Comment(model::Comment {
+                text: text.to_string(),
+            }));
+        }
+
+        let mut written = vec![];
+        write_csv(&component, &expected, &mut written).unwrap();
+        let (_component, actual) = parse_csv(written.as_slice()).unwrap();
+
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn test_diagnostic() {
+        let csv = std::str::from_utf8(include_bytes!("../fixtures/CMD_DB/valid.csv"))
+            .unwrap()
+            .replace("0x0001,1,uint32_t", "0x0001,1,uint33_t");
+        let err = parse_csv(csv.as_bytes()).unwrap_err();
+        let diagnostic = err.downcast_ref::<Diagnostic>().unwrap();
+        assert_eq!(Some(7), diagnostic.row);
+        assert_eq!(Some("F".to_string()), diagnostic.column_name());
+        assert_eq!(Some("Param1 Type"), diagnostic.header.as_deref());
+        assert!(diagnostic.line.as_ref().unwrap().contains("uint33_t"));
+    }
+
+    #[test]
+    fn test_parse_csv_recovering() {
+        let valid = include_bytes!("../fixtures/CMD_DB/valid.csv");
+        let (_component, expected) = parse_csv(valid.as_slice()).unwrap();
+        let csv = std::str::from_utf8(valid)
+            .unwrap()
+            .replace(
+                ",Cmd_NOP,OBC,0x0000,0,,,,,,,,,,,,,,,ダミーコマンド,",
+                ",Cmd_NOP,OBC",
+            )
+            .replace("0x0001,1,uint32_t", "0x0001,1,uint33_t")
+            .replace("0x0002,3,double", "0xZZZZ,3,double");
+        assert!(parse_csv(csv.as_bytes()).is_err());
+
+        let (_component, actual, diagnostics) = parse_csv_recovering(csv.as_bytes()).unwrap();
+        let rows: Vec<_> = diagnostics.iter().map(|d| d.row).collect();
+        assert_eq!(vec![Some(6), Some(7), Some(8)], rows);
+        assert_eq!(
+            "the number of columns is mismatch: expected 21, but found 3",
+            diagnostics[0].message
+        );
+        assert_eq!(expected.entries.len() - 3, actual.entries.len());
+    }
 }
@@ -0,0 +1,300 @@
+//! CSV 上の位置情報をもつエラー
+//!
+//! パーサが返す [`anyhow::Error`] は、位置が特定できる場合は [Diagnostic] を保持している。
+//! `err.downcast_ref::<Diagnostic>()` で取り出し、[`Diagnostic::render`] で該当行を表示できる。
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use csv::StringRecord;
+use serde::{
+    de::{value, IntoDeserializer},
+    Deserialize,
+};
+
+/// CSV 上の位置情報をもつエラー
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// CSV ファイルのパス
+    pub path: Option<PathBuf>,
+    /// スプレッドシート上の行番号（1始まり）
+    pub row: Option<usize>,
+    /// 列番号（0始まり）。表示には [`Diagnostic::column_name`] を用いる
+    pub column: Option<usize>,
+    /// 列のヘッダ名
+    pub header: Option<String>,
+    /// 該当行を CSV として書き出したもの
+    pub line: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            path: None,
+            row: None,
+            column: None,
+            header: None,
+            line: None,
+            message: message.into(),
+        }
+    }
+
+    pub fn at_column(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column: Some(column),
+            ..Self::new(message)
+        }
+    }
+
+    /// スプレッドシート上の列名（`A`, `B`, ..., `Z`, `AA`, ...）
+    pub fn column_name(&self) -> Option<String> {
+        self.column.map(column_name)
+    }
+
+    /// 該当行とその列を指すキャレットを含めて整形する
+    pub fn render(&self) -> String {
+        let mut out = format!("error: {}\n", self.message);
+        let location = self.location();
+        if !location.is_empty() {
+            out.push_str(&format!("  --> {}\n", location));
+        }
+        if let (Some(row), Some(line)) = (self.row, &self.line) {
+            let gutter = row.to_string().len();
+            out.push_str(&format!("{:gutter$} |\n", ""));
+            out.push_str(&format!("{} | {}\n", row, line));
+            if let Some(column) = self.column {
+                let (offset, width) = column_span(line, column);
+                out.push_str(&format!(
+                    "{:gutter$} | {:offset$}{}\n",
+                    "",
+                    "",
+                    "^".repeat(width.max(1))
+                ));
+            }
+        }
+        out
+    }
+
+    fn location(&self) -> String {
+        let mut location = String::new();
+        if let Some(path) = &self.path {
+            location.push_str(&path.display().to_string());
+        }
+        if let Some(row) = self.row {
+            if !location.is_empty() {
+                location.push(':');
+            }
+            location.push_str(&row.to_string());
+            if let Some(column) = self.column_name() {
+                location.push(':');
+                location.push_str(&column);
+            }
+        } else if let Some(column) = self.column_name() {
+            if !location.is_empty() {
+                location.push(' ');
+            }
+            location.push_str(&format!("column {}", column));
+        }
+        if let Some(header) = &self.header {
+            location.push_str(&format!(" ({})", header));
+        }
+        location
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let location = self.location();
+        if location.is_empty() {
+            f.write_str(&self.message)
+        } else {
+            write!(f, "{}: {}", location, self.message)
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+impl From<anyhow::Error> for Diagnostic {
+    fn from(err: anyhow::Error) -> Self {
+        err.downcast()
+            .unwrap_or_else(|err| Diagnostic::new(format!("{:#}", err)))
+    }
+}
+
+pub(crate) fn column_name(mut index: usize) -> String {
+    let mut name = vec![];
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap()
+}
+
+fn record_to_line(fields: &[&str]) -> String {
+    let mut wtr = crate::csv_writer_builder()
+        .flexible(true)
+        .from_writer(vec![]);
+    // Vec への書き込みは失敗しない
+    let _ = wtr.write_record(fields);
+    let bytes = wtr.into_inner().unwrap_or_default();
+    String::from_utf8_lossy(&bytes).trim_end().to_string()
+}
+
+/// CSV として書き出した行における `column` 列目の開始位置（文字数）と幅
+fn column_span(line: &str, column: usize) -> (usize, usize) {
+    let mut rdr = crate::csv_reader_builder()
+        .flexible(true)
+        .from_reader(line.as_bytes());
+    let Some(Ok(record)) = rdr.records().next() else {
+        return (0, 1);
+    };
+    let fields: Vec<_> = record.iter().collect();
+    if column >= fields.len() {
+        return (line.chars().count(), 1);
+    }
+    let offset = if column == 0 {
+        0
+    } else {
+        record_to_line(&fields[..column]).chars().count() + 1
+    };
+    let width = record_to_line(&fields[column..=column]).chars().count();
+    (offset, width)
+}
+
+/// `err` が [Diagnostic] であれば `f` を適用し、そうでなければ [Diagnostic] に変換してから適用する
+pub(crate) fn map_diagnostic(err: anyhow::Error, f: impl FnOnce(&mut Diagnostic)) -> anyhow::Error {
+    let mut diagnostic = match err.downcast::<Diagnostic>() {
+        Ok(diagnostic) => diagnostic,
+        Err(err) => {
+            let csv_error = err.downcast_ref::<csv::Error>();
+            let column = csv_error
+                .and_then(|err| match err.kind() {
+                    csv::ErrorKind::Deserialize { err, .. } => err.field(),
+                    _ => None,
+                })
+                .map(|field| field as usize);
+            let row = csv_error
+                .and_then(csv::Error::position)
+                .map(|position| position.record() as usize + 1);
+            Diagnostic {
+                row,
+                column,
+                ..Diagnostic::new(format!("{:#}", err))
+            }
+        }
+    };
+    f(&mut diagnostic);
+    diagnostic.into()
+}
+
+/// `err` に `record` の行番号と内容を付与する
+///
+/// `header_of` は列番号からヘッダ名を求めるのに用いる。
+pub(crate) fn at_record(
+    err: anyhow::Error,
+    record: &StringRecord,
+    header_of: fn(usize) -> Option<&'static str>,
+) -> anyhow::Error {
+    map_diagnostic(err, |diagnostic| {
+        if diagnostic.row.is_none() {
+            diagnostic.row = record
+                .position()
+                .map(|position| position.record() as usize + 1);
+        }
+        if diagnostic.line.is_none() {
+            diagnostic.line = Some(record_to_line(&record.iter().collect::<Vec<_>>()));
+        }
+        if diagnostic.header.is_none() {
+            diagnostic.header = diagnostic
+                .column
+                .and_then(header_of)
+                .map(ToString::to_string);
+        }
+    })
+}
+
+/// `record.deserialize` の失敗を列番号つきの [Diagnostic] に変換する
+///
+/// csv は serde の独自エラー（列挙型の未知のバリアントなど）に列番号を付与しないため、
+/// その場合は `invalid_column` で該当する列を探す。
+pub(crate) fn deserialize_error(
+    err: csv::Error,
+    record: &StringRecord,
+    invalid_column: fn(&StringRecord) -> Option<usize>,
+) -> anyhow::Error {
+    let csv::ErrorKind::Deserialize { err: de_err, .. } = err.kind() else {
+        return err.into();
+    };
+    let column = de_err
+        .field()
+        .map(|field| field as usize)
+        .or_else(|| invalid_column(record));
+    Diagnostic {
+        column,
+        ..Diagnostic::new(de_err.kind().to_string())
+    }
+    .into()
+}
+
+/// 空欄か、`T` として解釈できる値であれば `true`
+pub(crate) fn is_valid_value<'de, T: Deserialize<'de>>(value: &'de str) -> bool {
+    let de: value::StrDeserializer<value::Error> = value.into_deserializer();
+    value.is_empty() || T::deserialize(de).is_ok()
+}
+
+/// `err` に CSV ファイルのパスを付与する
+pub fn with_path(err: anyhow::Error, path: impl AsRef<Path>) -> anyhow::Error {
+    map_diagnostic(err, |diagnostic| {
+        diagnostic.path = Some(path.as_ref().to_path_buf())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!("A", column_name(0));
+        assert_eq!("Z", column_name(25));
+        assert_eq!("AA", column_name(26));
+        assert_eq!("AZ", column_name(51));
+        assert_eq!("BA", column_name(52));
+    }
+
+    #[test]
+    fn test_render() {
+        let diagnostic = Diagnostic {
+            path: Some(PathBuf::from("CMD_DB.csv")),
+            row: Some(12),
+            column: Some(2),
+            header: Some("Target".to_string()),
+            line: Some(r#",Cmd_NOP,"a,b",0x0000"#.to_string()),
+            message: "invalid target".to_string(),
+        };
+        assert_eq!(
+            "CMD_DB.csv:12:C (Target): invalid target",
+            diagnostic.to_string()
+        );
+        assert_eq!(
+            [
+                "error: invalid target",
+                "  --> CMD_DB.csv:12:C (Target)",
+                "   |",
+                r#"12 | ,Cmd_NOP,"a,b",0x0000"#,
+                "   |          ^^^^^",
+                "",
+            ]
+            .join("\n"),
+            diagnostic.render()
+        );
+    }
+}
@@ -0,0 +1,609 @@
+//! Excel の R1C1 形式の数式の評価器
+//!
+//! xlsm から直接書き出された TLM DB CSV には、オクテット位置やビット位置が
+//! `=R[-1]C+INT((R[-1]C[1]+R[-1]C[2])/8)` のような数式のまま保存されている。
+//! ここではそのシートで用いられている範囲の数式（相対・絶対参照、四則演算、
+//! `INT`, `MOD`, `IF`, `OR`, `EXACT`）のみを評価する。
+
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail, ensure, Context, Result};
+use csv::StringRecord;
+
+use crate::{
+    diagnostic::{self, Diagnostic},
+    escape::{escape, unescape},
+};
+
+/// `records` のうち `columns` 列目にある数式のセルを評価し、その結果で置き換える
+///
+/// 数式は `=` で始まるセルとする。参照先のセルが数式であれば再帰的に評価する。
+///
+/// 評価に失敗した場合は [Diagnostic] を返す。`header_of` はそのヘッダ名を求めるのに用いる。
+pub fn evaluate_columns(
+    records: &mut [StringRecord],
+    columns: &[usize],
+    header_of: fn(usize) -> Option<&'static str>,
+) -> Result<()> {
+    let mut sheet = Sheet::new(records);
+    let mut results = vec![];
+    for (row, record) in records.iter().enumerate() {
+        for &col in columns {
+            if sheet.is_formula(row, col) {
+                let value = sheet.evaluate_cell(row, col).map_err(|err| {
+                    let err = Diagnostic::at_column(
+                        col,
+                        format!("failed to evaluate formula: {:#}", err),
+                    );
+                    diagnostic::at_record(err.into(), record, header_of)
+                })?;
+                results.push((row, col, value));
+            }
+        }
+    }
+    for (row, col, value) in results {
+        let record = &mut records[row];
+        let mut fields: Vec<String> = record.iter().map(str::to_string).collect();
+        fields[col] = escape(&value.to_string());
+        let mut evaluated = StringRecord::from(fields);
+        evaluated.set_position(record.position().cloned());
+        *record = evaluated;
+    }
+    Ok(())
+}
+
+fn cell_name(row: usize, col: usize) -> String {
+    format!("R{}C{}", row + 1, col + 1)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Empty,
+    Number(f64),
+    Text(String),
+    Bool(bool),
+}
+
+impl Value {
+    fn from_literal(s: &str) -> Self {
+        if s.is_empty() {
+            Value::Empty
+        } else if let Ok(n) = s.trim().parse::<f64>() {
+            Value::Number(n)
+        } else {
+            match s {
+                "TRUE" => Value::Bool(true),
+                "FALSE" => Value::Bool(false),
+                _ => Value::Text(s.to_string()),
+            }
+        }
+    }
+
+    fn as_number(&self) -> Result<f64> {
+        match self {
+            Value::Empty => Ok(0.0),
+            Value::Number(n) => Ok(*n),
+            Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+            Value::Text(s) => s
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("expected a number, but got {:?}", s)),
+        }
+    }
+
+    fn as_bool(&self) -> Result<bool> {
+        match self {
+            Value::Empty => Ok(false),
+            Value::Number(n) => Ok(*n != 0.0),
+            Value::Bool(b) => Ok(*b),
+            Value::Text(s) => match s.to_ascii_uppercase().as_str() {
+                "TRUE" => Ok(true),
+                "FALSE" => Ok(false),
+                _ => Err(anyhow!("expected a boolean, but got {:?}", s)),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Text(s) => f.write_str(s),
+            Value::Bool(true) => f.write_str("TRUE"),
+            Value::Bool(false) => f.write_str("FALSE"),
+        }
+    }
+}
+
+enum CellState {
+    Evaluating,
+    Done(Value),
+}
+
+struct Sheet {
+    cells: Vec<Vec<String>>,
+    states: HashMap<(usize, usize), CellState>,
+}
+
+impl Sheet {
+    fn new(records: &[StringRecord]) -> Self {
+        let cells = records
+            .iter()
+            .map(|record| record.iter().map(unescape).collect())
+            .collect();
+        Self {
+            cells,
+            states: HashMap::new(),
+        }
+    }
+
+    fn raw(&self, row: usize, col: usize) -> &str {
+        self.cells
+            .get(row)
+            .and_then(|cols| cols.get(col))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    fn is_formula(&self, row: usize, col: usize) -> bool {
+        self.raw(row, col).starts_with('=')
+    }
+
+    fn evaluate_cell(&mut self, row: usize, col: usize) -> Result<Value> {
+        match self.states.get(&(row, col)) {
+            Some(CellState::Done(value)) => return Ok(value.clone()),
+            Some(CellState::Evaluating) => {
+                bail!("circular reference at {}", cell_name(row, col))
+            }
+            None => {}
+        }
+        let raw = self.raw(row, col);
+        let Some(formula) = raw.strip_prefix('=') else {
+            return Ok(Value::from_literal(raw));
+        };
+        let expr = Parser::new(formula)?
+            .parse()
+            .with_context(|| format!("parsing formula {:?}", raw))?;
+        self.states.insert((row, col), CellState::Evaluating);
+        let value = self.evaluate(&expr, row, col);
+        match &value {
+            Ok(value) => {
+                self.states
+                    .insert((row, col), CellState::Done(value.clone()));
+            }
+            Err(_) => {
+                self.states.remove(&(row, col));
+            }
+        }
+        value
+    }
+
+    fn evaluate(&mut self, expr: &Expr, row: usize, col: usize) -> Result<Value> {
+        match expr {
+            Expr::Number(n) => Ok(Value::Number(*n)),
+            Expr::Text(s) => Ok(Value::Text(s.clone())),
+            Expr::Ref(r, c) => {
+                let target_row = r.resolve(row)?;
+                let target_col = c.resolve(col)?;
+                self.evaluate_cell(target_row, target_col)
+            }
+            Expr::Neg(operand) => Ok(Value::Number(
+                -self.evaluate(operand, row, col)?.as_number()?,
+            )),
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs = self.evaluate(lhs, row, col)?.as_number()?;
+                let rhs = self.evaluate(rhs, row, col)?.as_number()?;
+                let value = match op {
+                    BinaryOp::Add => lhs + rhs,
+                    BinaryOp::Sub => lhs - rhs,
+                    BinaryOp::Mul => lhs * rhs,
+                    BinaryOp::Div => {
+                        ensure!(rhs != 0.0, "division by zero");
+                        lhs / rhs
+                    }
+                    BinaryOp::Pow => lhs.powf(rhs),
+                };
+                Ok(Value::Number(value))
+            }
+            Expr::Call(name, args) => self.call(name, args, row, col),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[Expr], row: usize, col: usize) -> Result<Value> {
+        match name {
+            "INT" => {
+                ensure!(args.len() == 1, "INT takes exactly 1 argument");
+                let x = self.evaluate(&args[0], row, col)?.as_number()?;
+                Ok(Value::Number(x.floor()))
+            }
+            "MOD" => {
+                ensure!(args.len() == 2, "MOD takes exactly 2 arguments");
+                let x = self.evaluate(&args[0], row, col)?.as_number()?;
+                let y = self.evaluate(&args[1], row, col)?.as_number()?;
+                ensure!(y != 0.0, "division by zero");
+                // Excel の MOD は除数と同じ符号を返す
+                Ok(Value::Number(x - y * (x / y).floor()))
+            }
+            "IF" => {
+                ensure!(
+                    args.len() == 2 || args.len() == 3,
+                    "IF takes 2 or 3 arguments"
+                );
+                if self.evaluate(&args[0], row, col)?.as_bool()? {
+                    self.evaluate(&args[1], row, col)
+                } else if let Some(otherwise) = args.get(2) {
+                    self.evaluate(otherwise, row, col)
+                } else {
+                    Ok(Value::Bool(false))
+                }
+            }
+            "OR" => {
+                ensure!(!args.is_empty(), "OR takes at least 1 argument");
+                let mut result = false;
+                for arg in args {
+                    result |= self.evaluate(arg, row, col)?.as_bool()?;
+                }
+                Ok(Value::Bool(result))
+            }
+            "EXACT" => {
+                ensure!(args.len() == 2, "EXACT takes exactly 2 arguments");
+                let lhs = self.evaluate(&args[0], row, col)?.to_string();
+                let rhs = self.evaluate(&args[1], row, col)?.to_string();
+                Ok(Value::Bool(lhs == rhs))
+            }
+            _ => Err(anyhow!("unsupported function: {}", name)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Offset {
+    Relative(isize),
+    Absolute(usize),
+}
+
+impl Offset {
+    fn resolve(self, base: usize) -> Result<usize> {
+        match self {
+            Offset::Relative(delta) => base
+                .checked_add_signed(delta)
+                .ok_or_else(|| anyhow!("reference out of the sheet")),
+            Offset::Absolute(index) => index
+                .checked_sub(1)
+                .ok_or_else(|| anyhow!("reference out of the sheet")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Number(f64),
+    Text(String),
+    Ref(Offset, Offset),
+    Neg(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Text(String),
+    Ident(String),
+    Ref(Offset, Offset),
+    LParen,
+    RParen,
+    Comma,
+    Op(char),
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            ' ' => i += 1,
+            '(' => {
+                tokens.push(Token::LParen);
+                i += 1;
+            }
+            ')' => {
+                tokens.push(Token::RParen);
+                i += 1;
+            }
+            ',' => {
+                tokens.push(Token::Comma);
+                i += 1;
+            }
+            '+' | '-' | '*' | '/' | '^' => {
+                tokens.push(Token::Op(c));
+                i += 1;
+            }
+            '"' => {
+                let mut text = String::new();
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        None => bail!("unterminated string literal"),
+                        // "" は文字列中の " を表す
+                        Some('"') if chars.get(i + 1) == Some(&'"') => {
+                            text.push('"');
+                            i += 2;
+                        }
+                        Some('"') => {
+                            i += 1;
+                            break;
+                        }
+                        Some(&c) => {
+                            text.push(c);
+                            i += 1;
+                        }
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            c if c.is_ascii_digit() || c == '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let literal: String = chars[start..i].iter().collect();
+                let n = literal
+                    .parse()
+                    .map_err(|_| anyhow!("invalid number: {}", literal))?;
+                tokens.push(Token::Number(n));
+            }
+            c if c.is_ascii_alphabetic() => {
+                if let Some((reference, next)) = lex_reference(&chars, i) {
+                    tokens.push(reference);
+                    i = next;
+                    continue;
+                }
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '.') {
+                    i += 1;
+                }
+                let ident: String = chars[start..i].iter().collect();
+                tokens.push(Token::Ident(ident.to_ascii_uppercase()));
+            }
+            _ => bail!("unexpected character: {:?}", c),
+        }
+    }
+    Ok(tokens)
+}
+
+/// `R[-1]C[2]`, `RC`, `R3C4` などの参照を読む
+fn lex_reference(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    fn offset(chars: &[char], mut i: usize) -> Option<(Offset, usize)> {
+        match chars.get(i) {
+            Some('[') => {
+                let begin = i + 1;
+                i = begin;
+                while chars.get(i).is_some_and(|&c| c != ']') {
+                    i += 1;
+                }
+                let delta: String = chars.get(begin..i)?.iter().collect();
+                Some((Offset::Relative(delta.parse().ok()?), i + 1))
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let begin = i;
+                while chars.get(i).is_some_and(char::is_ascii_digit) {
+                    i += 1;
+                }
+                let index: String = chars[begin..i].iter().collect();
+                Some((Offset::Absolute(index.parse().ok()?), i))
+            }
+            _ => Some((Offset::Relative(0), i)),
+        }
+    }
+
+    if chars.get(start) != Some(&'R') {
+        return None;
+    }
+    let (row, i) = offset(chars, start + 1)?;
+    if chars.get(i) != Some(&'C') {
+        return None;
+    }
+    let (col, i) = offset(chars, i + 1)?;
+    if chars
+        .get(i)
+        .is_some_and(|&c| c.is_ascii_alphanumeric() || c == '(')
+    {
+        // 関数名などの識別子
+        return None;
+    }
+    Some((Token::Ref(row, col), i))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Result<Self> {
+        Ok(Self {
+            tokens: tokenize(s)?,
+            pos: 0,
+        })
+    }
+
+    fn parse(mut self) -> Result<Expr> {
+        let expr = self.expr()?;
+        ensure!(
+            self.pos == self.tokens.len(),
+            "unexpected token: {:?}",
+            self.tokens[self.pos]
+        );
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .ok_or_else(|| anyhow!("unexpected end of formula"))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        let token = self.next()?;
+        ensure!(
+            token == expected,
+            "expected {:?}, but got {:?}",
+            expected,
+            token
+        );
+        Ok(())
+    }
+
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        while let Some(Token::Op(c @ ('+' | '-'))) = self.peek() {
+            let op = if *c == '+' {
+                BinaryOp::Add
+            } else {
+                BinaryOp::Sub
+            };
+            self.pos += 1;
+            let rhs = self.term()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.power()?;
+        while let Some(Token::Op(c @ ('*' | '/'))) = self.peek() {
+            let op = if *c == '*' {
+                BinaryOp::Mul
+            } else {
+                BinaryOp::Div
+            };
+            self.pos += 1;
+            let rhs = self.power()?;
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn power(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        while let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            lhs = Expr::Binary(BinaryOp::Pow, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.unary()?)))
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.unary()
+            }
+            _ => self.primary(),
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr> {
+        match self.next()? {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Text(s) => Ok(Expr::Text(s)),
+            Token::Ref(row, col) => Ok(Expr::Ref(row, col)),
+            Token::LParen => {
+                let expr = self.expr()?;
+                self.expect(Token::RParen)?;
+                Ok(expr)
+            }
+            Token::Ident(name) => {
+                self.expect(Token::LParen)?;
+                let mut args = vec![];
+                if self.peek() == Some(&Token::RParen) {
+                    self.pos += 1;
+                    return Ok(Expr::Call(name, args));
+                }
+                loop {
+                    args.push(self.expr()?);
+                    match self.next()? {
+                        Token::Comma => continue,
+                        Token::RParen => break,
+                        token => bail!("expected , or ), but got {:?}", token),
+                    }
+                }
+                Ok(Expr::Call(name, args))
+            }
+            token => Err(anyhow!("unexpected token: {:?}", token)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(rows: &[&[&str]], columns: &[usize]) -> Vec<StringRecord> {
+        let mut records: Vec<_> = rows.iter().map(|row| StringRecord::from(*row)).collect();
+        evaluate_columns(&mut records, columns, |_| None).unwrap();
+        records
+    }
+
+    #[test]
+    fn test_positions() {
+        const OCTET: &str = "=R[-1]C+INT((R[-1]C[1]+R[-1]C[2])/8)";
+        const BIT: &str = "=MOD((R[-1]C+R[-1]C[1])@@8)";
+        const LEN: &str = r#"=IF(OR(EXACT(RC[-3]@@"uint8_t")@@EXACT(RC[-3]@@"int8_t"))@@8@@IF(EXACT(RC[-3]@@"double")@@64))"#;
+        let records = evaluate(
+            &[
+                &["uint16_t", "0", "0", "3"],
+                &["||", OCTET, BIT, "13"],
+                &["uint8_t", OCTET, BIT, LEN],
+                &["double", OCTET, BIT, LEN],
+            ],
+            &[1, 2, 3],
+        );
+        assert_eq!(&records[1], vec!["||", "0", "3", "13"]);
+        assert_eq!(&records[2], vec!["uint8_t", "2", "0", "8"]);
+        assert_eq!(&records[3], vec!["double", "3", "0", "64"]);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let records = evaluate(&[&["=-7+2*3^2/4", "=MOD(-3@@8)", "=INT(-0.5)"]], &[0, 1, 2]);
+        assert_eq!(&records[0], vec!["-2.5", "5", "-1"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut records = vec![StringRecord::from(vec!["=RC"])];
+        assert!(evaluate_columns(&mut records, &[0], |_| None).is_err());
+        let mut records = vec![StringRecord::from(vec!["=R[-1]C"])];
+        assert!(evaluate_columns(&mut records, &[0], |_| None).is_err());
+        let mut records = vec![StringRecord::from(vec!["=SUM(1)"])];
+        assert!(evaluate_columns(&mut records, &[0], |_| None).is_err());
+    }
+}
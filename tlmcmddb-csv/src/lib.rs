@@ -1,12 +1,22 @@
 mod macros;
 mod util;
 
+pub mod bct;
 pub mod cmd;
+pub mod diagnostic;
 pub mod escape;
+pub mod formula;
 pub mod tlm;
+pub mod xlsx;
 
 pub fn csv_reader_builder() -> csv::ReaderBuilder {
     let mut builder = csv::ReaderBuilder::new();
     builder.has_headers(false);
     builder
 }
+
+pub fn csv_writer_builder() -> csv::WriterBuilder {
+    let mut builder = csv::WriterBuilder::new();
+    builder.has_headers(false);
+    builder
+}
@@ -1,11 +1,29 @@
 macro_rules! check_header {
-    ($actual:expr, $expected:expr) => {
-        anyhow::ensure!(
-            $actual == $expected,
-            "invalid header: expected: {}, but got: {}",
-            $expected,
-            $actual
-        );
+    ($record:expr, $index:expr, $expected:expr) => {
+        if &$record[$index] != $expected {
+            return Err($crate::diagnostic::Diagnostic {
+                header: Some($expected.to_string()),
+                ..$crate::diagnostic::Diagnostic::at_column(
+                    $index,
+                    format!(
+                        "invalid header: expected: {}, but got: {}",
+                        $expected, &$record[$index]
+                    ),
+                )
+            }
+            .into());
+        }
     };
 }
 pub(crate) use check_header;
+
+macro_rules! ensure_column {
+    ($cond:expr, $column:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err(
+                $crate::diagnostic::Diagnostic::at_column($column, format!($($arg)+)).into(),
+            );
+        }
+    };
+}
+pub(crate) use ensure_column;
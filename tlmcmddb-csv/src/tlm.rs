@@ -6,14 +6,65 @@ pub mod telemetry;
 pub use filename::Filename;
 
 use anyhow::Result;
-use std::io::Read;
+use std::io::{Read, Write};
 
+use crate::diagnostic::Diagnostic;
+
+/// TLM DB CSV を読み込む
+///
+/// xlsm から直接書き出された、位置指定の列が数式のままの CSV も受け付ける。
 pub fn parse_csv<R: Read>(telemetry_name: String, rdr: R) -> Result<tlmcmddb::tlm::Telemetry> {
-    let mut csv = crate::csv_reader_builder().from_reader(rdr);
-    let mut iter = csv.records();
+    let records = read_records(rdr, false)?;
+    let mut iter = records.into_iter().map(Ok::<_, csv::Error>);
     telemetry::parse(telemetry_name, &mut iter)
 }
 
+/// TLM DB CSV を、不正な行を読み飛ばしながら読み込む
+///
+/// 読み込めた部分のテレメトリ定義と、読み飛ばした行の [Diagnostic] を返す。
+pub fn parse_csv_recovering<R: Read>(
+    telemetry_name: String,
+    rdr: R,
+) -> Result<(tlmcmddb::tlm::Telemetry, Vec<Diagnostic>)> {
+    let records = read_records(rdr, true)?;
+    let mut iter = records.into_iter().map(Ok::<_, csv::Error>);
+    telemetry::parse_recovering(telemetry_name, &mut iter)
+}
+
+/// TLM DB CSV のうち、Octet Pos. と bit Pos. が FieldGroup の並びから計算した位置と食い違っている行を返す
+///
+/// 行を挿入した後に位置を直し忘れた CSV を見つけるために用いる。
+pub fn check_positions<R: Read>(rdr: R) -> Result<Vec<Diagnostic>> {
+    let records = read_records(rdr, false)?;
+    let mut iter = records.into_iter().map(Ok::<_, csv::Error>);
+    telemetry::check_positions(&mut iter)
+}
+
+/// すべての行を読み込み、位置指定の列の数式を評価する
+///
+/// `flexible` の場合は、列数が他の行と異なる行も読み込む。そのような行の扱いはパーサに任せる。
+fn read_records<R: Read>(rdr: R, flexible: bool) -> Result<Vec<csv::StringRecord>> {
+    let mut csv = crate::csv_reader_builder()
+        .flexible(flexible)
+        .from_reader(rdr);
+    let mut records = csv
+        .records()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(crate::util::read_error)?;
+    crate::formula::evaluate_columns(&mut records, &body::POSITION_COLUMNS, body::column_header)?;
+    Ok(records)
+}
+
+/// テレメトリ定義を TLM DB CSV として書き出す
+///
+/// テレメトリの名前はファイル名で表現されるため、書き出されない。
+pub fn write_csv<W: Write>(telemetry: &tlmcmddb::tlm::Telemetry, wtr: W) -> Result<()> {
+    let mut csv = crate::csv_writer_builder().from_writer(wtr);
+    telemetry::write(&mut csv, telemetry)?;
+    csv.flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +116,139 @@ mod tests {
 
         assert_eq!(expected, actual)
     }
+
+    #[test]
+    fn test_read_raw_csv() {
+        for name in ["HK", "MOBC"] {
+            let raw =
+                std::fs::read(format!("../tlm-cmd-db/TLM_DB/SAMPLE_TLM_DB_{}.csv", name)).unwrap();
+            let calced = std::fs::read(format!(
+                "../tlm-cmd-db/TLM_DB/calced_data/SAMPLE_TLM_DB_{}.csv",
+                name
+            ))
+            .unwrap();
+            let expected = parse_csv(name.to_string(), calced.as_slice()).unwrap();
+            let actual = parse_csv(name.to_string(), raw.as_slice()).unwrap();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_check_positions() {
+        let header = include_str!("../fixtures/TLM_DB/valid_metadata.csv");
+        let body = include_str!("../fixtures/TLM_DB/valid_body.csv");
+        let csv = format!("{}{}", header, body);
+        assert_eq!(
+            Vec::<Diagnostic>::new(),
+            check_positions(csv.as_bytes()).unwrap()
+        );
+
+        // SH.VER の後に行を挿入し、以降の位置を直し忘れた状態
+        let csv = csv.replace(
+            ",SH.VER,uint8_t,,PACKET,6,0,8,NONE,,,,,,,,,\n",
+            ",SH.VER,uint8_t,,PACKET,6,0,8,NONE,,,,,,,,,\n,INSERTED,uint16_t,,PACKET,7,0,16,NONE,,,,,,,,,\n",
+        );
+        let diagnostics = check_positions(csv.as_bytes()).unwrap();
+        assert_eq!(Some(18), diagnostics[0].row);
+        assert_eq!(Some("Octet Pos."), diagnostics[0].header.as_deref());
+        assert!(diagnostics[0]
+            .message
+            .starts_with("SH.TI is at octet 7 bit 0, "));
+    }
+
+    #[test]
+    fn test_write_csv_roundtrip() {
+        let expected = parse_testdata().unwrap();
+
+        let mut csv = vec![];
+        write_csv(&expected, &mut csv).unwrap();
+        let actual = parse_csv("".to_string(), csv.as_slice()).unwrap();
+
+        assert_eq!(expected, actual)
+    }
+
+    #[test]
+    fn test_write_csv_layout() {
+        let tlm = parse_testdata().unwrap();
+
+        let mut csv = vec![];
+        write_csv(&tlm, &mut csv).unwrap();
+        let actual = String::from_utf8(csv).unwrap();
+
+        let header = include_str!("../fixtures/TLM_DB/valid_metadata.csv");
+        assert!(actual.starts_with(header));
+        let body_headers = include_str!("../fixtures/TLM_DB/valid_body.csv")
+            .lines()
+            .take(3)
+            .collect::<Vec<_>>();
+        let actual_headers = actual.lines().skip(5).take(3).collect::<Vec<_>>();
+        assert_eq!(body_headers, actual_headers)
+    }
+
+    #[test]
+    fn test_parse_csv_recovering() {
+        let calced =
+            std::fs::read_to_string("../tlm-cmd-db/TLM_DB/calced_data/SAMPLE_TLM_DB_HK.csv")
+                .unwrap();
+        let expected = parse_csv("HK".to_string(), calced.as_bytes()).unwrap();
+        let broken = calced.replacen(",PH.VER,uint16_t,", ",PH.VER,uint17_t,", 1);
+        assert!(parse_csv("HK".to_string(), broken.as_bytes()).is_err());
+
+        let (actual, diagnostics) =
+            parse_csv_recovering("HK".to_string(), broken.as_bytes()).unwrap();
+        // FieldGroup の先頭行が不正な場合、そのフィールドはまとめて読み飛ばされる
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Some(9), diagnostics[0].row);
+        let (tlmcmddb::tlm::Content::Struct(expected), tlmcmddb::tlm::Content::Struct(actual)) =
+            (expected.content, actual.content)
+        else {
+            unreachable!();
+        };
+        assert_eq!(expected[1..], actual[..]);
+    }
+
+    #[test]
+    fn test_parse_csv_ragged_row() {
+        let calced =
+            std::fs::read_to_string("../tlm-cmd-db/TLM_DB/calced_data/SAMPLE_TLM_DB_HK.csv")
+                .unwrap();
+        let ragged = calced.replacen(",PH.APID,,,PACKET,0,5,11,NONE,,,,,,,,,", ",PH.APID,,", 1);
+        let err = parse_csv("HK".to_string(), ragged.as_bytes()).unwrap_err();
+        let diagnostic = err.downcast::<Diagnostic>().unwrap();
+        assert_eq!(Some(12), diagnostic.row);
+
+        // 列数の異なる行だけが読み飛ばされ、ファイル全体の読み込みは続く
+        let (actual, diagnostics) =
+            parse_csv_recovering("HK".to_string(), ragged.as_bytes()).unwrap();
+        assert_eq!(1, diagnostics.len());
+        assert_eq!(Some(12), diagnostics[0].row);
+        assert_eq!(
+            "the number of columns is mismatch: expected 18, but found 4",
+            diagnostics[0].message
+        );
+        let fields: Vec<_> = actual.fields().map(|(_, field)| &field.name).collect();
+        assert_eq!(
+            ["PH.VER", "PH.TYPE", "PH.SH_FLAG", "PH.SEQ_FLAG"],
+            fields[..4]
+        );
+    }
+
+    #[test]
+    fn test_blob_csv() {
+        let csv = include_str!("../fixtures/TLM_DB/valid_blob.csv");
+        let tlm = parse_csv("FILE_DL".to_string(), csv.as_bytes()).unwrap();
+        assert_eq!(0xf1, tlm.metadata.packet_id);
+        assert_eq!(tlmcmddb::tlm::Content::Blob, tlm.content);
+
+        let mut written = vec![];
+        write_csv(&tlm, &mut written).unwrap();
+        assert_eq!(csv, String::from_utf8(written).unwrap());
+
+        // blob テレメトリは本体をもたない
+        let body = include_str!("../fixtures/TLM_DB/valid_body.csv");
+        let with_body = format!("{}{}", csv, body);
+        let err = parse_csv("FILE_DL".to_string(), with_body.as_bytes()).unwrap_err();
+        let diagnostic = err.downcast::<Diagnostic>().unwrap();
+        assert_eq!(Some(7), diagnostic.row);
+    }
 }
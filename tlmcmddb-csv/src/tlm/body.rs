@@ -5,7 +5,12 @@ use csv::StringRecord;
 use serde::Deserialize;
 use tlmcmddb::tlm::{self as model};
 
-use crate::{escape::unescape, macros::check_header, util};
+use crate::{
+    diagnostic::{at_record, deserialize_error, is_valid_value, Diagnostic},
+    escape::{escape, unescape},
+    macros::{check_header, ensure_column},
+    util,
+};
 
 /*
 +-------+--------+---------------+---------------------------------------------+------------------------------------------------------+--------+-------+
@@ -15,6 +20,11 @@ use crate::{escape::unescape, macros::check_header, util};
 |       |        | Type  |       | Type  |-------------+-----------+-----------+ Type   +-----+-----+-----+-----+-----+-----+         |        |       |
 |       |        |       |       |       | Octet Pos.  | bit Pos.  | bit Len.  |        | a0  | a1  | a2  | a3  | a4  | a5  |         |        |       |
 +-------+--------+-------+-------+-------+-------------+-----------+-----------+--------+-----+-----+-----+-----+-----+-----+---------+--------+-------+
+
+Note の後ろに、表示用の情報として Label, Unit, Format の列を置くこともできる（省略可）
++-------+-------+--------+
+| Label | Unit  | Format |
++-------+-------+--------+
 */
 
 mod header {
@@ -25,6 +35,9 @@ mod header {
     pub const CONVERSION_INFO: &str = "Conversion Info.";
     pub const DESCRIPTION: &str = "Description";
     pub const NOTE: &str = "Note";
+    pub const LABEL: &str = "Label";
+    pub const UNIT: &str = "Unit";
+    pub const FORMAT: &str = "Format";
     pub const NAME: &str = "Name";
     pub const VARIABLE_OR_FUNCTION_NAME: &str = "Variable or Function Name";
     pub const POS_DESIGNATOR: &str = "Pos. Desiginator";
@@ -36,104 +49,215 @@ mod header {
     pub const A3: &str = "a3";
     pub const A4: &str = "a4";
     pub const A5: &str = "a5";
+    pub const VAR_TYPE: &str = "Var.%%##Type";
+    pub const EXT_TYPE: &str = "Ext.%%##Type";
+    pub const CONV_TYPE: &str = "Conv.%%##Type";
+    pub const OCTET_POS: &str = "Octet%%##Pos.";
+    pub const BIT_POS: &str = "bit%%##Pos.";
+    pub const BIT_LEN: &str = "bit%%##Len.";
 }
 
-fn check_first_header(record: StringRecord) -> Result<()> {
-    ensure!(record.len() >= 18, "the number of columns is mismatch");
-    check_header!(&record[0], header::COMMENT);
-    check_header!(&record[1], header::TLM_ENTRY);
-    check_header!(&record[2], header::ONBOARD_SOFTWARE_INFO);
-    check_header!(&record[4], header::EXTRACTION_INFO);
-    check_header!(&record[8], header::CONVERSION_INFO);
-    check_header!(&record[16], header::DESCRIPTION);
-    check_header!(&record[17], header::NOTE);
-    Ok(())
+/// TLM DB CSV の列数
+pub const NUM_COLUMNS: usize = 18;
+
+/// Label, Unit, Format の列を含む場合の TLM DB CSV の列数
+pub const NUM_COLUMNS_WITH_DISPLAY_INFO: usize = 21;
+
+/// Octet Pos., bit Pos., bit Len. の列。xlsm から直接書き出された CSV では数式になっている
+pub const POSITION_COLUMNS: [usize; 3] = [5, 6, 7];
+
+/// xlsm で上のセルと結合されていることを示すマーカー
+const MERGED_CELL_MARKER: &str = "||";
+
+mod column {
+    pub const VAR_TYPE: usize = 2;
+    pub const EXPRESSION: usize = 3;
+    pub const OCTET_POS: usize = 5;
+    pub const BIT_POS: usize = 6;
+    pub const CONV_TYPE: usize = 8;
+    pub const A0: usize = 9;
+    pub const A1: usize = 10;
+    pub const A2: usize = 11;
+    pub const A3: usize = 12;
+    pub const A4: usize = 13;
+    pub const A5: usize = 14;
+    pub const STATUS: usize = 15;
+    pub const LABEL: usize = 18;
+    pub const UNIT: usize = 19;
+    pub const FORMAT: usize = 20;
+}
+
+/// エラー表示に用いる列のヘッダ名
+pub(crate) fn column_header(column: usize) -> Option<&'static str> {
+    const HEADERS: [&str; NUM_COLUMNS_WITH_DISPLAY_INFO] = [
+        "Comment",
+        "Name",
+        "Var. Type",
+        "Variable or Function Name",
+        "Ext. Type",
+        "Octet Pos.",
+        "bit Pos.",
+        "bit Len.",
+        "Conv. Type",
+        "a0",
+        "a1",
+        "a2",
+        "a3",
+        "a4",
+        "a5",
+        "Status",
+        "Description",
+        "Note",
+        "Label",
+        "Unit",
+        "Format",
+    ];
+    HEADERS.get(column).copied()
+}
+
+/// Label, Unit, Format の列がある場合は `true` を返す
+fn check_first_header(record: &StringRecord) -> Result<bool> {
+    ensure!(
+        record.len() >= NUM_COLUMNS,
+        "the number of columns is mismatch"
+    );
+    check_header!(record, 0, header::COMMENT);
+    check_header!(record, 1, header::TLM_ENTRY);
+    check_header!(record, 2, header::ONBOARD_SOFTWARE_INFO);
+    check_header!(record, 4, header::EXTRACTION_INFO);
+    check_header!(record, 8, header::CONVERSION_INFO);
+    check_header!(record, 16, header::DESCRIPTION);
+    check_header!(record, 17, header::NOTE);
+    if record.get(column::LABEL).map_or(true, str::is_empty) {
+        return Ok(false);
+    }
+    ensure!(
+        record.len() >= NUM_COLUMNS_WITH_DISPLAY_INFO,
+        "the number of columns is mismatch"
+    );
+    check_header!(record, column::LABEL, header::LABEL);
+    check_header!(record, column::UNIT, header::UNIT);
+    check_header!(record, column::FORMAT, header::FORMAT);
+    Ok(true)
 }
 
-fn check_second_header(record: StringRecord) -> Result<()> {
+fn check_second_header(record: &StringRecord) -> Result<()> {
     ensure!(record.len() >= 16, "the number of columns is mismatch");
-    check_header!(&record[1], header::NAME);
-    //check_header!(&record[2], header::VAR_TYPE);
-    check_header!(&record[3], header::VARIABLE_OR_FUNCTION_NAME);
-    //check_header!(&record[4], header::EXT_TYPE);
-    check_header!(&record[5], header::POS_DESIGNATOR);
-    //check_header!(&record[8], header::CONV_TYPE);
-    check_header!(&record[9], header::POLY);
-    check_header!(&record[15], header::STATUS);
+    check_header!(record, 1, header::NAME);
+    //check_header!(record, 2, header::VAR_TYPE);
+    check_header!(record, 3, header::VARIABLE_OR_FUNCTION_NAME);
+    //check_header!(record, 4, header::EXT_TYPE);
+    check_header!(record, 5, header::POS_DESIGNATOR);
+    //check_header!(record, 8, header::CONV_TYPE);
+    check_header!(record, 9, header::POLY);
+    check_header!(record, 15, header::STATUS);
     Ok(())
 }
 
-fn check_third_header(record: StringRecord) -> Result<()> {
+fn check_third_header(record: &StringRecord) -> Result<()> {
     ensure!(record.len() >= 15, "the number of columns is mismatch");
-    //check_header!(&record[5], header::OCTET_POS);
-    //check_header!(&record[6], header::BIT_POS);
-    //check_header!(&record[7], header::BIT_LEN);
-    check_header!(&record[9], header::A0);
-    check_header!(&record[10], header::A1);
-    check_header!(&record[11], header::A2);
-    check_header!(&record[12], header::A3);
-    check_header!(&record[13], header::A4);
-    check_header!(&record[14], header::A5);
+    //check_header!(record, 5, header::OCTET_POS);
+    //check_header!(record, 6, header::BIT_POS);
+    //check_header!(record, 7, header::BIT_LEN);
+    check_header!(record, 9, header::A0);
+    check_header!(record, 10, header::A1);
+    check_header!(record, 11, header::A2);
+    check_header!(record, 12, header::A3);
+    check_header!(record, 13, header::A4);
+    check_header!(record, 14, header::A5);
     Ok(())
 }
 
-fn check_headers<I, E>(mut iter: I) -> Result<()>
+/// Label, Unit, Format の列がある場合は `true` を返す
+fn check_headers<I, E>(mut iter: I) -> Result<bool>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    check_first_header(util::next_record(&mut iter)?)?;
-    check_second_header(util::next_record(&mut iter)?)?;
-    check_third_header(util::next_record(&mut iter)?)?;
-    Ok(())
+    let record = util::next_record(&mut iter)?;
+    let has_display_info =
+        check_first_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let record = util::next_record(&mut iter)?;
+    check_second_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    let record = util::next_record(&mut iter)?;
+    check_third_header(&record).map_err(|err| at_record(err, &record, column_header))?;
+    Ok(has_display_info)
 }
 
 fn build_comment(record: StringRecord) -> model::Comment {
-    let mut text = String::new();
-    for col in record.iter() {
-        text.push_str(&unescape(col));
-        text.push(',');
-    }
-    text.truncate(text.len() - 1); // trim last comma
+    let text = util::comment_text(&record);
     model::Comment { text }
 }
 
-fn parse_entries<I, E>(mut iter: I) -> Result<Vec<model::Entry>>
+/// Var. Type と Variable or Function Name の結合セルマーカーを空欄として扱う
+///
+/// Label, Unit, Format の列がない場合は、Note より後ろの列を取り除く。
+fn strip_merged_cell_markers(record: &StringRecord, has_display_info: bool) -> StringRecord {
+    let width = body_width(has_display_info);
+    let mut stripped: StringRecord = record
+        .iter()
+        .take(width)
+        .enumerate()
+        .map(|(i, col)| match i {
+            column::VAR_TYPE | column::EXPRESSION if col == MERGED_CELL_MARKER => "",
+            _ => col,
+        })
+        .collect();
+    stripped.set_position(record.position().cloned());
+    stripped
+}
+
+/// フィールドの行がもつべき列数
+fn body_width(has_display_info: bool) -> usize {
+    if has_display_info {
+        NUM_COLUMNS_WITH_DISPLAY_INFO
+    } else {
+        NUM_COLUMNS
+    }
+}
+
+/// Var. Type が書かれている、すなわち FieldGroup の先頭の行であれば `true`
+fn starts_field_group(record: &StringRecord) -> bool {
+    record
+        .get(column::VAR_TYPE)
+        .is_some_and(|col| !col.is_empty() && col != MERGED_CELL_MARKER)
+}
+
+/// 行ごとのエラーは `report` に渡す。`report` がエラーを返した場合はそこで中断する
+fn parse_entries<I, E>(
+    mut iter: I,
+    has_display_info: bool,
+    mut report: impl FnMut(anyhow::Error) -> Result<()>,
+) -> Result<Vec<model::Entry>>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
     let mut entries = vec![];
     let mut current_bit_field_group = None;
+    // FieldGroup の先頭行を読み飛ばした場合、後続のフィールドを直前の FieldGroup に含めないよう、それらも読み飛ばす
+    let mut skipping_bit_fields = false;
     while let Some(record) = util::try_next_record(&mut iter)? {
+        let is_bit_field = record[0].is_empty() && !starts_field_group(&record);
+        if skipping_bit_fields && is_bit_field {
+            continue;
+        }
         if record[0].is_empty() {
-            let line = record.deserialize::<Line>(None)?;
-            match line.try_into()? {
-                LineModel::BitFieldGroup(bit_field_group) => {
-                    if let Some(bit_field_group) = current_bit_field_group.take() {
-                        entries.push(model::Entry::FieldGroup(bit_field_group));
-                    }
-                    current_bit_field_group = Some(bit_field_group);
-                }
-                LineModel::BitField(bit_field) => {
-                    if let Some(bit_field_group) = current_bit_field_group.as_mut() {
-                        bit_field_group
-                            .sub_entries
-                            .push(model::SubEntry::Field(bit_field));
-                    } else {
-                        return Err(anyhow!("unexpected bit field"));
-                    }
+            skipping_bit_fields = false;
+        }
+        if let Err(err) = parse_line(
+            &record,
+            has_display_info,
+            &mut entries,
+            &mut current_bit_field_group,
+        ) {
+            if !is_bit_field {
+                if let Some(bit_field_group) = current_bit_field_group.take() {
+                    entries.push(model::Entry::FieldGroup(bit_field_group));
                 }
+                skipping_bit_fields = record[0].is_empty();
             }
-        } else {
-            let comment = build_comment(record);
-            if let Some(bit_field_group) = current_bit_field_group.as_mut() {
-                bit_field_group
-                    .sub_entries
-                    .push(model::SubEntry::Comment(comment));
-            } else {
-                entries.push(model::Entry::Comment(comment));
-            }
+            report(at_record(err, &record, column_header))?;
         }
     }
     if let Some(bit_field_group) = current_bit_field_group.take() {
@@ -142,13 +266,303 @@ where
     Ok(entries)
 }
 
+/// serde の独自エラーとなりうる列のうち、解釈できない値をもつ最初の列
+fn invalid_column(record: &StringRecord) -> Option<usize> {
+    if !is_valid_value::<model::VariableType>(&record[column::VAR_TYPE]) {
+        Some(column::VAR_TYPE)
+    } else if !is_valid_value::<ConversionType>(&record[column::CONV_TYPE]) {
+        Some(column::CONV_TYPE)
+    } else {
+        None
+    }
+}
+
+fn parse_line(
+    record: &StringRecord,
+    has_display_info: bool,
+    entries: &mut Vec<model::Entry>,
+    current_bit_field_group: &mut Option<model::FieldGroup>,
+) -> Result<()> {
+    if record[0].is_empty() {
+        let width = body_width(has_display_info);
+        ensure!(
+            record.len() >= width,
+            "the number of columns is mismatch: expected {}, but found {}",
+            width,
+            record.len()
+        );
+        let record = strip_merged_cell_markers(record, has_display_info);
+        let line = record
+            .deserialize::<Line>(None)
+            .map_err(|err| deserialize_error(err, &record, invalid_column))?;
+        match line.try_into()? {
+            LineModel::BitFieldGroup(bit_field_group) => {
+                if let Some(bit_field_group) = current_bit_field_group.take() {
+                    entries.push(model::Entry::FieldGroup(bit_field_group));
+                }
+                *current_bit_field_group = Some(bit_field_group);
+            }
+            LineModel::BitField(bit_field) => {
+                if let Some(bit_field_group) = current_bit_field_group.as_mut() {
+                    bit_field_group
+                        .sub_entries
+                        .push(model::SubEntry::Field(bit_field));
+                } else {
+                    return Err(Diagnostic::at_column(
+                        column::VAR_TYPE,
+                        "unexpected bit field: the first field must have Var. Type",
+                    )
+                    .into());
+                }
+            }
+        }
+    } else {
+        let comment = build_comment(record.clone());
+        if let Some(bit_field_group) = current_bit_field_group.as_mut() {
+            bit_field_group
+                .sub_entries
+                .push(model::SubEntry::Comment(comment));
+        } else {
+            entries.push(model::Entry::Comment(comment));
+        }
+    }
+    Ok(())
+}
+
 pub fn parse<I, E>(mut iter: I) -> Result<Vec<model::Entry>>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    check_headers(&mut iter)?;
-    parse_entries(&mut iter)
+    let has_display_info = check_headers(&mut iter)?;
+    parse_entries(&mut iter, has_display_info, Err)
+}
+
+/// 不正な行を読み飛ばしながら読み込み、読み込めたエントリと各行の [Diagnostic] を返す
+///
+/// ヘッダが不正な場合など、読み込みを続けられない場合はエラーを返す。
+pub fn parse_recovering<I, E>(mut iter: I) -> Result<(Vec<model::Entry>, Vec<Diagnostic>)>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let has_display_info = check_headers(&mut iter)?;
+    let mut diagnostics = vec![];
+    let entries = parse_entries(&mut iter, has_display_info, |err| {
+        diagnostics.push(err.into());
+        Ok(())
+    })?;
+    Ok((entries, diagnostics))
+}
+
+/// Octet Pos. と bit Pos. が、FieldGroup の並びから計算した位置と食い違っている行の [Diagnostic] を返す
+///
+/// 位置の計算は [`model::position`] による。読み込めない行がある場合はエラーを返す。
+pub fn check_positions<I, E>(mut iter: I) -> Result<Vec<Diagnostic>>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let has_display_info = check_headers(&mut iter)?;
+    let mut records = vec![];
+    while let Some(record) = util::try_next_record(&mut iter)? {
+        records.push(record);
+    }
+    let entries = parse_entries(
+        records.iter().cloned().map(Ok::<_, csv::Error>),
+        has_display_info,
+        Err,
+    )?;
+    // コメント行以外の各行がちょうど1つのフィールドになる
+    let field_records: Vec<_> = records
+        .iter()
+        .filter(|record| record[0].is_empty())
+        .collect();
+    let diagnostics = model::position::check(&entries)
+        .into_iter()
+        .map(|mismatch| {
+            let column = if mismatch.stored.octet_position != mismatch.computed.octet_position {
+                column::OCTET_POS
+            } else {
+                column::BIT_POS
+            };
+            let err = Diagnostic::at_column(
+                column,
+                format!(
+                    "{} is at {}, but the layout computed from the preceding fields gives {}",
+                    mismatch.name, mismatch.stored, mismatch.computed
+                ),
+            );
+            at_record(err.into(), field_records[mismatch.index], column_header).into()
+        })
+        .collect();
+    Ok(diagnostics)
+}
+
+/// `entries` を書き出す際の列数
+///
+/// [`DisplayInfo`](model::DisplayInfo) をもつフィールドがある場合のみ Label, Unit, Format の列を含める。
+pub fn num_columns(entries: &[model::Entry]) -> usize {
+    let has_display_info = model::field_groups(entries)
+        .flat_map(model::FieldGroup::fields)
+        .any(|field| field.display_info.is_some());
+    if has_display_info {
+        NUM_COLUMNS_WITH_DISPLAY_INFO
+    } else {
+        NUM_COLUMNS
+    }
+}
+
+fn write_headers<W: std::io::Write>(wtr: &mut csv::Writer<W>, width: usize) -> Result<()> {
+    let mut first = vec![""; width];
+    first[0] = header::COMMENT;
+    first[1] = header::TLM_ENTRY;
+    first[2] = header::ONBOARD_SOFTWARE_INFO;
+    first[4] = header::EXTRACTION_INFO;
+    first[8] = header::CONVERSION_INFO;
+    first[16] = header::DESCRIPTION;
+    first[17] = header::NOTE;
+    if width == NUM_COLUMNS_WITH_DISPLAY_INFO {
+        first[column::LABEL] = header::LABEL;
+        first[column::UNIT] = header::UNIT;
+        first[column::FORMAT] = header::FORMAT;
+    }
+    util::write_padded_record(wtr, first, width)?;
+
+    let mut second = vec![""; width];
+    second[1] = header::NAME;
+    second[2] = header::VAR_TYPE;
+    second[3] = header::VARIABLE_OR_FUNCTION_NAME;
+    second[4] = header::EXT_TYPE;
+    second[5] = header::POS_DESIGNATOR;
+    second[8] = header::CONV_TYPE;
+    second[9] = header::POLY;
+    second[15] = header::STATUS;
+    util::write_padded_record(wtr, second, width)?;
+
+    let mut third = vec![""; width];
+    third[5] = header::OCTET_POS;
+    third[6] = header::BIT_POS;
+    third[7] = header::BIT_LEN;
+    third[9] = header::A0;
+    third[10] = header::A1;
+    third[11] = header::A2;
+    third[12] = header::A3;
+    third[13] = header::A4;
+    third[14] = header::A5;
+    util::write_padded_record(wtr, third, width)?;
+    Ok(())
+}
+
+fn format_status_map(status: &model::conversion::Status) -> String {
+    let mut rules = status
+        .variants
+        .iter()
+        .map(|variant| format!("{}={}", variant.key, variant.value))
+        .collect::<Vec<_>>();
+    if let Some(default_value) = &status.default_value {
+        rules.push(format!("*={}", default_value));
+    }
+    rules.join(",")
+}
+
+fn write_field<W: std::io::Write>(
+    wtr: &mut csv::Writer<W>,
+    field: &model::Field,
+    onboard_software_info: Option<&model::OnboardSoftwareInfo>,
+    width: usize,
+) -> Result<()> {
+    let (variable_type, expression) = match onboard_software_info {
+        Some(info) => (
+            info.variable_type.as_str().to_string(),
+            escape(&info.expression),
+        ),
+        None => (String::new(), String::new()),
+    };
+    let extraction_info = &field.extraction_info;
+    let mut record = vec![
+        String::new(),
+        escape(&field.name),
+        variable_type,
+        expression,
+        escape(&extraction_info.extraction_type),
+        extraction_info.octet_position.to_string(),
+        extraction_info.bit_position.to_string(),
+        extraction_info.bit_length.to_string(),
+    ];
+    let mut coefficients = vec![String::new(); 6];
+    let mut status = String::new();
+    let conversion_type = match &field.conversion_info {
+        model::ConversionInfo::None => "NONE",
+        model::ConversionInfo::Hex => "HEX",
+        model::ConversionInfo::Status(status_map) => {
+            status = escape(&format_status_map(status_map));
+            "STATUS"
+        }
+        model::ConversionInfo::Polynomial(poly) => {
+            coefficients = [poly.a0, poly.a1, poly.a2, poly.a3, poly.a4, poly.a5]
+                .iter()
+                .map(f64::to_string)
+                .collect();
+            "POLY"
+        }
+    };
+    record.push(conversion_type.to_string());
+    record.extend(coefficients);
+    record.push(status);
+    record.push(escape(&field.description));
+    record.push(escape(&field.note));
+    if let Some(display_info) = &field.display_info {
+        record.push(escape(&display_info.label));
+        record.push(escape(&display_info.unit));
+        record.push(escape(&display_info.format));
+    }
+    util::write_padded_record(wtr, record, width)
+}
+
+fn write_field_group<W: std::io::Write>(
+    wtr: &mut csv::Writer<W>,
+    field_group: &model::FieldGroup,
+    width: usize,
+) -> Result<()> {
+    // Var. Type と Variable or Function Name は先頭のフィールドの行にのみ書かれる
+    let mut onboard_software_info = Some(&field_group.onboard_software_info);
+    for sub_entry in &field_group.sub_entries {
+        match sub_entry {
+            model::SubEntry::Field(field) => {
+                write_field(wtr, field, onboard_software_info.take(), width)?;
+            }
+            model::SubEntry::Comment(comment) => {
+                ensure!(
+                    onboard_software_info.is_none(),
+                    "the first sub entry of a field group must be a field"
+                );
+                util::write_comment(wtr, &comment.text, width)?;
+            }
+        }
+    }
+    ensure!(
+        onboard_software_info.is_none(),
+        "a field group must contain at least one field"
+    );
+    Ok(())
+}
+
+/// [parse] で読み込める形式で、ヘッダとエントリを書き出す
+///
+/// 最初の [`FieldGroup`](model::FieldGroup) より後ろにある [`Entry::Comment`](model::Entry::Comment) は、
+/// 読み込み時には直前の [`FieldGroup`](model::FieldGroup) の [`SubEntry::Comment`](model::SubEntry::Comment) として扱われる。
+/// 列数は [num_columns] による。
+pub fn write<W: std::io::Write>(wtr: &mut csv::Writer<W>, entries: &[model::Entry]) -> Result<()> {
+    let width = num_columns(entries);
+    write_headers(wtr, width)?;
+    for entry in entries {
+        match entry {
+            model::Entry::FieldGroup(field_group) => write_field_group(wtr, field_group, width)?,
+            model::Entry::Comment(comment) => util::write_comment(wtr, &comment.text, width)?,
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
@@ -171,9 +585,27 @@ struct Line {
     status: Option<String>,
     description: String,
     note: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    unit: String,
+    #[serde(default)]
+    format: String,
 }
 
 impl Line {
+    /// Label, Unit, Format がすべて空欄であれば `None` を返す
+    fn take_display_info(&mut self) -> Option<model::DisplayInfo> {
+        if self.label.is_empty() && self.unit.is_empty() && self.format.is_empty() {
+            return None;
+        }
+        Some(model::DisplayInfo {
+            label: unescape(&std::mem::take(&mut self.label)),
+            unit: unescape(&std::mem::take(&mut self.unit)),
+            format: unescape(&std::mem::take(&mut self.format)),
+        })
+    }
+
     fn take_conversion_info(&mut self) -> LineConversionInfo {
         LineConversionInfo {
             conversion_type: self.conversion_type,
@@ -205,85 +637,126 @@ impl TryFrom<LineConversionInfo> for model::ConversionInfo {
     fn try_from(info: LineConversionInfo) -> Result<Self, Self::Error> {
         match info.conversion_type {
             ConversionType::None => {
-                ensure!(
+                ensure_column!(
                     info.a0.is_none(),
+                    column::A0,
                     "a0 must be empty when Conv. Type is NONE"
                 );
-                ensure!(
+                ensure_column!(
                     info.a1.is_none(),
+                    column::A1,
                     "a1 must be empty when Conv. Type is NONE"
                 );
-                ensure!(
+                ensure_column!(
                     info.a2.is_none(),
+                    column::A2,
                     "a2 must be empty when Conv. Type is NONE"
                 );
-                ensure!(
+                ensure_column!(
                     info.a3.is_none(),
+                    column::A3,
                     "a3 must be empty when Conv. Type is NONE"
                 );
-                ensure!(
+                ensure_column!(
                     info.a4.is_none(),
+                    column::A4,
                     "a4 must be empty when Conv. Type is NONE"
                 );
-                ensure!(
+                ensure_column!(
                     info.a5.is_none(),
+                    column::A5,
                     "a5 must be empty when Conv. Type is NONE"
                 );
                 /*
-                ensure!(
-                    info.status.is_none(),
-                    "Status must be empty when Conv. Type is NONE"
+                ensure_column!(info.status.is_none(), column::STATUS, "Status must be empty when Conv. Type is NONE"
                 );
                 */
                 Ok(model::ConversionInfo::None)
             }
             ConversionType::Hex => {
-                ensure!(info.a0.is_none(), "a0 must be empty when Conv. Type is HEX");
-                ensure!(info.a1.is_none(), "a1 must be empty when Conv. Type is HEX");
-                ensure!(info.a2.is_none(), "a2 must be empty when Conv. Type is HEX");
-                ensure!(info.a3.is_none(), "a3 must be empty when Conv. Type is HEX");
-                ensure!(info.a4.is_none(), "a4 must be empty when Conv. Type is HEX");
-                ensure!(info.a5.is_none(), "a5 must be empty when Conv. Type is HEX");
-                ensure!(
+                ensure_column!(
+                    info.a0.is_none(),
+                    column::A0,
+                    "a0 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
+                    info.a1.is_none(),
+                    column::A1,
+                    "a1 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
+                    info.a2.is_none(),
+                    column::A2,
+                    "a2 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
+                    info.a3.is_none(),
+                    column::A3,
+                    "a3 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
+                    info.a4.is_none(),
+                    column::A4,
+                    "a4 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
+                    info.a5.is_none(),
+                    column::A5,
+                    "a5 must be empty when Conv. Type is HEX"
+                );
+                ensure_column!(
                     info.status.is_none(),
+                    column::STATUS,
                     "Status must be empty when Conv. Type is HEX"
                 );
                 Ok(model::ConversionInfo::Hex)
             }
             ConversionType::Status => {
-                ensure!(
+                ensure_column!(
                     info.a0.is_none(),
+                    column::A0,
                     "a0 must be empty when Conv. Type is STATUS"
                 );
-                ensure!(
+                ensure_column!(
                     info.a1.is_none(),
+                    column::A1,
                     "a1 must be empty when Conv. Type is STATUS"
                 );
-                ensure!(
+                ensure_column!(
                     info.a2.is_none(),
+                    column::A2,
                     "a2 must be empty when Conv. Type is STATUS"
                 );
-                ensure!(
+                ensure_column!(
                     info.a3.is_none(),
+                    column::A3,
                     "a3 must be empty when Conv. Type is STATUS"
                 );
-                ensure!(
+                ensure_column!(
                     info.a4.is_none(),
+                    column::A4,
                     "a4 must be empty when Conv. Type is STATUS"
                 );
-                ensure!(
+                ensure_column!(
                     info.a5.is_none(),
+                    column::A5,
                     "a5 must be empty when Conv. Type is STATUS"
                 );
                 let Some(status) = info.status else {
-                    return Err(anyhow!("Conv. Type is STATUS but Status is missing"));
+                    return Err(Diagnostic::at_column(
+                        column::STATUS,
+                        "Conv. Type is STATUS but Status is missing",
+                    )
+                    .into());
                 };
-                let status = parse_status_map(&unescape(&status))?;
+                let status = parse_status_map(&unescape(&status))
+                    .map_err(|err| Diagnostic::at_column(column::STATUS, format!("{:#}", err)))?;
                 Ok(model::ConversionInfo::Status(status))
             }
             ConversionType::Poly => {
-                ensure!(
+                ensure_column!(
                     info.status.is_none(),
+                    column::STATUS,
                     "Status must be empty when Conv. Type is POLY"
                 );
                 let polynomial = model::conversion::Polynomial {
@@ -370,7 +843,7 @@ impl TryFrom<Line> for model::FieldGroup {
 
     fn try_from(mut line: Line) -> Result<Self, Self::Error> {
         let Some(variable_type) = line.variable_type.take() else {
-            return Err(anyhow!("Var. Type is missing"));
+            return Err(Diagnostic::at_column(column::VAR_TYPE, "Var. Type is missing").into());
         };
         let expression = unescape(&line.expression.take().unwrap_or_default());
         let onboard_software_info = model::OnboardSoftwareInfo {
@@ -390,10 +863,14 @@ impl TryFrom<Line> for model::Field {
 
     fn try_from(mut line: Line) -> Result<Self, Self::Error> {
         if line.variable_type.is_some() {
-            return Err(anyhow!("Var. Type is present"));
+            return Err(Diagnostic::at_column(column::VAR_TYPE, "Var. Type is present").into());
         };
         if line.expression.is_some() {
-            return Err(anyhow!("Variable or Function Name is present"));
+            return Err(Diagnostic::at_column(
+                column::EXPRESSION,
+                "Variable or Function Name is present",
+            )
+            .into());
         };
         let extraction_info = model::FieldExtractionInfo {
             extraction_type: unescape(&line.extraction_type),
@@ -407,7 +884,7 @@ impl TryFrom<Line> for model::Field {
             extraction_info,
             conversion_info: conversion_info.try_into()?,
             description: unescape(&line.description),
-            display_info: Default::default(),
+            display_info: line.take_display_info(),
             note: unescape(&line.note),
         })
     }
@@ -444,4 +921,45 @@ mod tests {
         // make snapshot:
         // serde_json::to_writer_pretty(std::fs::OpenOptions::new().write(true).truncate(true).open("fixtures/TLM_DB/valid_body.json").unwrap(), &actual).unwrap();
     }
+
+    #[test]
+    fn test_display_info() {
+        let csv = include_bytes!("../../fixtures/TLM_DB/valid_body.csv");
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(csv.as_slice());
+        let mut entries = parse(&mut rdr.records()).unwrap();
+        assert_eq!(NUM_COLUMNS, num_columns(&entries));
+
+        let model::Entry::FieldGroup(field_group) = entries
+            .iter_mut()
+            .find(|entry| matches!(entry, model::Entry::FieldGroup(_)))
+            .unwrap()
+        else {
+            unreachable!()
+        };
+        let model::SubEntry::Field(field) = &mut field_group.sub_entries[0] else {
+            unreachable!()
+        };
+        field.display_info = Some(model::DisplayInfo {
+            label: "Packet, Version".to_string(),
+            unit: String::new(),
+            format: "%d".to_string(),
+        });
+
+        let mut wtr = csv::WriterBuilder::new()
+            .has_headers(false)
+            .from_writer(vec![]);
+        write(&mut wtr, &entries).unwrap();
+        let written = wtr.into_inner().unwrap();
+        let mut rdr = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(written.as_slice());
+        let records = rdr.records().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(NUM_COLUMNS_WITH_DISPLAY_INFO, records[0].len());
+        assert_eq!(header::LABEL, &records[0][column::LABEL]);
+
+        let actual = parse(records.into_iter().map(Ok::<_, csv::Error>)).unwrap();
+        assert_eq!(entries, actual);
+    }
 }
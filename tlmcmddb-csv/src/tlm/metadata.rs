@@ -2,7 +2,12 @@ use anyhow::{anyhow, ensure, Context, Result};
 use csv::StringRecord;
 use tlmcmddb::tlm as model;
 
-use crate::{escape::unescape, macros::check_header, util};
+use crate::{
+    diagnostic::{at_record, Diagnostic},
+    escape::{escape, unescape},
+    macros::check_header,
+    util,
+};
 
 /*
 +---+-----------------+---------+-------------------+
@@ -14,6 +19,11 @@ use crate::{escape::unescape, macros::check_header, util};
 +---+-----------------+---------+-------------------+
 |   | IsRestricted    | FALSE   |                   |
 +---+-----------------+---------+-------------------+
+|   | Content         | BLOB    |                   |  (省略可)
++---+-----------------+---------+-------------------+
+
+Content が BLOB の場合、メタデータの後ろには何も書かない（ヘッダも含め、本体をもたない）。
+Content の行を省略した場合は STRUCT として扱う。
 */
 
 mod header {
@@ -21,13 +31,48 @@ mod header {
     pub const PACKET_ID: &str = "PacketID";
     pub const ENABLE_DISABLE: &str = "Enable/Disable";
     pub const IS_RESTRICTED: &str = "IsRestricted";
+    pub const CONTENT: &str = "Content";
     pub const LOCAL_VAR: &str = "Local Var";
 }
 
-fn parse_first_line(record: StringRecord) -> Result<String> {
+/// 値が書かれている列
+const VALUE_COLUMN: usize = 2;
+
+/// 値の列のエラー。ヘッダ名は行によって異なるため、ここで指定する
+fn value_error(header: &str, err: anyhow::Error) -> anyhow::Error {
+    Diagnostic {
+        header: Some(header.to_string()),
+        ..Diagnostic::at_column(VALUE_COLUMN, format!("{:#}", err))
+    }
+    .into()
+}
+
+/// テレメトリの本体の種類。[`model::Content`] に対応する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    /// メタデータの後ろに、エントリのリストが書かれる
+    Struct,
+    /// メタデータのみで、本体をもたない
+    Blob,
+}
+
+impl ContentType {
+    pub fn of(content: &model::Content) -> Self {
+        match content {
+            model::Content::Struct(_) => Self::Struct,
+            model::Content::Blob => Self::Blob,
+        }
+    }
+}
+
+fn no_column_header(_column: usize) -> Option<&'static str> {
+    None
+}
+
+fn parse_first_line(record: &StringRecord) -> Result<String> {
     ensure!(record.len() >= 4, "the number of columns is mismatch");
-    check_header!(&record[1], header::TARGET);
-    check_header!(&record[3], header::LOCAL_VAR);
+    check_header!(record, 1, header::TARGET);
+    check_header!(record, 3, header::LOCAL_VAR);
     let target = &record[2];
     Ok(unescape(target))
 }
@@ -39,64 +84,151 @@ fn parse_packet_id(hex_with_0x: &str) -> Result<u8> {
     u8::from_str_radix(hex, 16).context("parsing PacketID")
 }
 
-fn parse_second_line(record: StringRecord) -> Result<(u8, String)> {
+fn parse_second_line(record: &StringRecord) -> Result<(u8, String)> {
     ensure!(record.len() >= 4, "the number of columns is mismatch");
-    check_header!(&record[1], header::PACKET_ID);
+    check_header!(record, 1, header::PACKET_ID);
     let packet_id_hex_with_0x = &record[2];
-    let packet_id = parse_packet_id(packet_id_hex_with_0x)?;
+    let packet_id = parse_packet_id(packet_id_hex_with_0x)
+        .map_err(|err| value_error(header::PACKET_ID, err))?;
     let local_var = &record[3];
     Ok((packet_id, unescape(local_var)))
 }
 
-fn parse_third_line(record: StringRecord) -> Result<bool> {
+fn parse_third_line(record: &StringRecord) -> Result<bool> {
     ensure!(record.len() >= 3, "the number of columns is mismatch");
-    check_header!(&record[1], header::ENABLE_DISABLE);
+    check_header!(record, 1, header::ENABLE_DISABLE);
     let is_enabled_str = &record[2];
     let is_enabled = match is_enabled_str {
         "ENABLE" => true,
         "DISABLE" => false,
         _ => {
-            return Err(anyhow!(
-                "the value of Enable/Disable must be either ENABLE or DISABLE"
+            return Err(value_error(
+                header::ENABLE_DISABLE,
+                anyhow!("the value of Enable/Disable must be either ENABLE or DISABLE"),
             ))
         }
     };
     Ok(is_enabled)
 }
 
-fn parse_fourth_line(record: StringRecord) -> Result<bool> {
+fn parse_fourth_line(record: &StringRecord) -> Result<bool> {
     ensure!(record.len() >= 3, "the number of columns is mismatch");
-    check_header!(&record[1], header::IS_RESTRICTED);
+    check_header!(record, 1, header::IS_RESTRICTED);
     let is_restricted_str = &record[2];
     let is_restricted = match is_restricted_str {
         "TRUE" => true,
         "FALSE" => false,
         _ => {
-            return Err(anyhow!(
-                "the value of IsRestricted must be either TRUE or FALSE"
+            return Err(value_error(
+                header::IS_RESTRICTED,
+                anyhow!("the value of IsRestricted must be either TRUE or FALSE"),
             ))
         }
     };
     Ok(is_restricted)
 }
 
-pub fn parse<I, E>(mut iter: I) -> Result<model::Metadata>
+/// Content の行。省略されている場合は `None` を返す
+fn parse_fifth_line(record: &StringRecord) -> Result<Option<ContentType>> {
+    if record.get(1) != Some(header::CONTENT) {
+        return Ok(None);
+    }
+    ensure!(record.len() >= 3, "the number of columns is mismatch");
+    let content_type = match &record[2] {
+        "STRUCT" => ContentType::Struct,
+        "BLOB" => ContentType::Blob,
+        _ => {
+            return Err(value_error(
+                header::CONTENT,
+                anyhow!("the value of Content must be either STRUCT or BLOB"),
+            ))
+        }
+    };
+    Ok(Some(content_type))
+}
+
+pub fn parse<I, E>(mut iter: I) -> Result<(model::Metadata, ContentType)>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    let target = parse_first_line(util::next_record(&mut iter)?)?;
-    let (packet_id, local_variables) = parse_second_line(util::next_record(&mut iter)?)?;
-    let is_enabled = parse_third_line(util::next_record(&mut iter)?)?;
-    let is_restricted = parse_fourth_line(util::next_record(&mut iter)?)?;
-    let _padding_line = util::next_record(&mut iter)?;
-    Ok(model::Metadata {
+    let record = util::next_record(&mut iter)?;
+    let target =
+        parse_first_line(&record).map_err(|err| at_record(err, &record, no_column_header))?;
+    let record = util::next_record(&mut iter)?;
+    let (packet_id, local_variables) =
+        parse_second_line(&record).map_err(|err| at_record(err, &record, no_column_header))?;
+    let record = util::next_record(&mut iter)?;
+    let is_enabled =
+        parse_third_line(&record).map_err(|err| at_record(err, &record, no_column_header))?;
+    let record = util::next_record(&mut iter)?;
+    let is_restricted =
+        parse_fourth_line(&record).map_err(|err| at_record(err, &record, no_column_header))?;
+    let record = util::next_record(&mut iter)?;
+    let content_type =
+        parse_fifth_line(&record).map_err(|err| at_record(err, &record, no_column_header))?;
+    let content_type = match content_type {
+        Some(content_type) => {
+            let _padding_line = util::next_record(&mut iter)?;
+            content_type
+        }
+        // Content の行が省略されている場合、この行がパディング行
+        None => ContentType::Struct,
+    };
+    let metadata = model::Metadata {
         target,
         packet_id,
         is_enabled,
         is_restricted,
         local_variables,
-    })
+    };
+    Ok((metadata, content_type))
+}
+
+pub fn write<W: std::io::Write>(
+    wtr: &mut csv::Writer<W>,
+    metadata: &model::Metadata,
+    content_type: ContentType,
+    width: usize,
+) -> Result<()> {
+    util::write_padded_record(
+        wtr,
+        [
+            "",
+            header::TARGET,
+            &escape(&metadata.target),
+            header::LOCAL_VAR,
+        ],
+        width,
+    )?;
+    util::write_padded_record(
+        wtr,
+        [
+            "",
+            header::PACKET_ID,
+            &format!("0x{:02x}", metadata.packet_id),
+            &escape(&metadata.local_variables),
+        ],
+        width,
+    )?;
+    let is_enabled = if metadata.is_enabled {
+        "ENABLE"
+    } else {
+        "DISABLE"
+    };
+    util::write_padded_record(wtr, ["", header::ENABLE_DISABLE, is_enabled], width)?;
+    let is_restricted = if metadata.is_restricted {
+        "TRUE"
+    } else {
+        "FALSE"
+    };
+    util::write_padded_record(wtr, ["", header::IS_RESTRICTED, is_restricted], width)?;
+    // STRUCT の場合は、従来の形式と同じになるよう Content の行を省略する
+    if content_type == ContentType::Blob {
+        util::write_padded_record(wtr, ["", header::CONTENT, "BLOB"], width)?;
+    }
+    util::write_padded_record(wtr, Vec::<String>::new(), width)?; // padding line
+    Ok(())
 }
 
 #[cfg(test)]
@@ -110,11 +242,12 @@ mod tests {
             .has_headers(false)
             .from_reader(csv.as_slice());
         let mut iter = rdr.records();
-        let metadata = parse(&mut iter).unwrap();
+        let (metadata, content_type) = parse(&mut iter).unwrap();
         assert_eq!("OBC", metadata.target);
         assert_eq!(0xf0, metadata.packet_id);
         assert!(metadata.is_enabled);
         assert!(!metadata.is_restricted);
+        assert_eq!(ContentType::Struct, content_type);
         assert!(iter.next().is_none());
     }
 }
@@ -1,19 +1,112 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use csv::StringRecord;
 use tlmcmddb::tlm as model;
 
-use super::{body, metadata};
+use super::{
+    body,
+    metadata::{self, ContentType},
+};
+use crate::{
+    diagnostic::{at_record, Diagnostic},
+    util,
+};
+
+/// blob テレメトリのメタデータの後ろに、何も書かれていないことを確かめる
+fn ensure_no_body<I, E>(mut iter: I) -> Result<()>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match util::try_next_record(&mut iter)? {
+        Some(record) => Err(at_record(
+            anyhow!("a blob telemetry must not have a body"),
+            &record,
+            body::column_header,
+        )),
+        None => Ok(()),
+    }
+}
 
 pub fn parse<I, E>(telemetry_name: String, mut iter: I) -> Result<model::Telemetry>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    let metadata = metadata::parse(&mut iter)?;
-    let entries = body::parse(&mut iter)?;
+    let (metadata, content_type) = metadata::parse(&mut iter)?;
+    let content = match content_type {
+        ContentType::Struct => model::Content::Struct(body::parse(&mut iter)?),
+        ContentType::Blob => {
+            ensure_no_body(&mut iter)?;
+            model::Content::Blob
+        }
+    };
     Ok(model::Telemetry {
         name: telemetry_name,
         metadata,
-        content: model::Content::Struct(entries),
+        content,
     })
 }
+
+/// [`body::parse_recovering`] を用いて、不正な行を読み飛ばしながら読み込む
+pub fn parse_recovering<I, E>(
+    telemetry_name: String,
+    mut iter: I,
+) -> Result<(model::Telemetry, Vec<Diagnostic>)>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    let (metadata, content_type) = metadata::parse(&mut iter)?;
+    let (content, diagnostics) = match content_type {
+        ContentType::Struct => {
+            let (entries, diagnostics) = body::parse_recovering(&mut iter)?;
+            (model::Content::Struct(entries), diagnostics)
+        }
+        ContentType::Blob => {
+            ensure_no_body(&mut iter)?;
+            (model::Content::Blob, vec![])
+        }
+    };
+    let telemetry = model::Telemetry {
+        name: telemetry_name,
+        metadata,
+        content,
+    };
+    Ok((telemetry, diagnostics))
+}
+
+/// [`body::check_positions`] を用いて、位置の食い違っている行を返す
+///
+/// blob テレメトリにはフィールドがないため、常に空となる。
+pub fn check_positions<I, E>(mut iter: I) -> Result<Vec<Diagnostic>>
+where
+    I: Iterator<Item = Result<StringRecord, E>>,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    match metadata::parse(&mut iter)? {
+        (_, ContentType::Struct) => body::check_positions(&mut iter),
+        (_, ContentType::Blob) => Ok(vec![]),
+    }
+}
+
+pub fn write<W: std::io::Write>(
+    wtr: &mut csv::Writer<W>,
+    telemetry: &model::Telemetry,
+) -> Result<()> {
+    let content_type = ContentType::of(&telemetry.content);
+    match &telemetry.content {
+        model::Content::Struct(entries) => {
+            metadata::write(
+                wtr,
+                &telemetry.metadata,
+                content_type,
+                body::num_columns(entries),
+            )?;
+            body::write(wtr, entries)?;
+        }
+        model::Content::Blob => {
+            metadata::write(wtr, &telemetry.metadata, content_type, body::NUM_COLUMNS)?;
+        }
+    }
+    Ok(())
+}
@@ -1,14 +1,16 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, ensure, Result};
 use csv::StringRecord;
 
+use crate::diagnostic::map_diagnostic;
+
 pub fn next_record<I, E>(iter: &mut I) -> Result<StringRecord>
 where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    Ok(iter
-        .next()
-        .ok_or_else(|| anyhow!("unexpected end of data"))??)
+    iter.next()
+        .ok_or_else(|| anyhow!("unexpected end of data"))?
+        .map_err(read_error)
 }
 
 pub fn try_next_record<I, E>(iter: &mut I) -> Result<Option<StringRecord>>
@@ -16,7 +18,7 @@ where
     I: Iterator<Item = Result<StringRecord, E>>,
     E: std::error::Error + Send + Sync + 'static,
 {
-    let Some(record) = iter.next().transpose()? else {
+    let Some(record) = iter.next().transpose().map_err(read_error)? else {
         return Ok(None);
     };
     let is_empty = record.is_empty() || record.iter().all(|col| col.is_empty());
@@ -25,3 +27,54 @@ where
     }
     Ok(Some(record))
 }
+
+/// 行の読み込みエラーを、読み込めなかった行の行番号をもつ [Diagnostic](crate::diagnostic::Diagnostic) にする
+pub fn read_error<E>(err: E) -> anyhow::Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    map_diagnostic(err.into(), |_| {})
+}
+
+pub fn write_padded_record<W, I, T>(wtr: &mut csv::Writer<W>, fields: I, width: usize) -> Result<()>
+where
+    W: std::io::Write,
+    I: IntoIterator<Item = T>,
+    T: Into<String>,
+{
+    let mut record: Vec<String> = fields.into_iter().map(Into::into).collect();
+    ensure!(record.len() <= width, "too many columns to write");
+    record.resize(width, String::new());
+    wtr.write_record(&record)?;
+    Ok(())
+}
+
+/// コメント行の各列を `,` で連結したテキストを返す
+///
+/// 末尾の空の列は書き出し時の列数の埋め合わせと区別できないため、連結に含めない。
+pub fn comment_text(record: &StringRecord) -> String {
+    let trailing_empty = record.iter().rev().take_while(|col| col.is_empty()).count();
+    record
+        .iter()
+        .take(record.len() - trailing_empty)
+        .map(crate::escape::unescape)
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// コメント行のテキストを `width` 列に分割して書き出す
+///
+/// パース時は全列が `,` で連結されるため、`width` 列目以降に相当する部分は最後の列にエスケープして詰める。
+/// 足りない列は空の列で埋めるが、パース時には [`comment_text`] が取り除く。
+pub fn write_comment<W: std::io::Write>(
+    wtr: &mut csv::Writer<W>,
+    text: &str,
+    width: usize,
+) -> Result<()> {
+    ensure!(
+        !text.is_empty() && !text.starts_with(','),
+        "the first column of a comment must not be empty"
+    );
+    let columns = text.splitn(width, ',').map(crate::escape::escape);
+    write_padded_record(wtr, columns, width)
+}
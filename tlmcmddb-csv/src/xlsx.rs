@@ -0,0 +1,137 @@
+//! xlsx / xlsm ワークブックの読み込み
+//!
+//! 各シートを、xlsm の「CSV 書き出し」で書き出したものと同じ [StringRecord] の列として読み込む。
+//! そのため、[`tlm::telemetry::parse`](crate::tlm::telemetry::parse) や [`cmd::parse`](crate::cmd::parse) をそのまま適用できる。
+//! 数式のセルは、ワークブックに保存されている計算結果を用いる。
+
+use std::{
+    fs::File,
+    io::{BufReader, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use calamine::{Data, Reader, Xlsx};
+use csv::{Position, StringRecord};
+
+use crate::escape::escape;
+
+/// TLM DB の xlsm に含まれる、テレメトリ定義ではないシート
+pub const TLM_DB_NON_TELEMETRY_SHEETS: [&str; 3] = ["CONV_TABLE", "TEMPLATE", "CHECK"];
+
+/// xlsx / xlsm ワークブック
+pub struct Workbook<RS> {
+    xlsx: Xlsx<RS>,
+}
+
+/// `path` のワークブックを開く
+pub fn open(path: &Path) -> Result<Workbook<BufReader<File>>> {
+    let file = File::open(path).with_context(|| format!("opening {:?}", path))?;
+    Workbook::new(BufReader::new(file))
+}
+
+/// [`crate::diagnostic::Diagnostic`] の `path` に用いる、シートを指すパス（`book.xlsm[HK]`）
+pub fn sheet_path(path: &Path, sheet: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(format!("[{}]", sheet));
+    path.into()
+}
+
+impl<RS: Read + Seek> Workbook<RS> {
+    pub fn new(reader: RS) -> Result<Self> {
+        let xlsx = Xlsx::new(reader).map_err(|err| anyhow!("reading workbook: {}", err))?;
+        Ok(Self { xlsx })
+    }
+
+    /// シート名を、ワークブック内の順に返す
+    pub fn sheet_names(&self) -> Vec<String> {
+        self.xlsx.sheet_names()
+    }
+
+    /// シートのすべての行を返す
+    ///
+    /// 行と列は A1 セルから数え、各 [StringRecord] にはシート上の行番号を位置として設定する。
+    /// 列数は、シート上で値が書かれている最も右の列に揃える。
+    /// セルの値は CSV 書き出しと同じくエスケープする。
+    pub fn records(&mut self, sheet: &str) -> Result<Vec<StringRecord>> {
+        let range = self
+            .xlsx
+            .worksheet_range(sheet)
+            .map_err(|err| anyhow!("reading sheet {}: {}", sheet, err))?;
+        let Some((end_row, end_column)) = range.end() else {
+            return Ok(vec![]);
+        };
+        let mut records = vec![];
+        for row in 0..=end_row {
+            let mut record: StringRecord = (0..=end_column)
+                .map(|column| {
+                    range
+                        .get_value((row, column))
+                        .map(cell_to_string)
+                        .unwrap_or_default()
+                })
+                .collect();
+            let mut position = Position::new();
+            position.set_line(row as u64 + 1).set_record(row as u64);
+            record.set_position(Some(position));
+            records.push(record);
+        }
+        Ok(records)
+    }
+}
+
+/// セルの値を、Excel が CSV に書き出すのと同じ文字列にする
+fn cell_to_string(data: &Data) -> String {
+    match data {
+        Data::Empty => String::new(),
+        Data::String(s) => escape(s),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(true) => "TRUE".to_string(),
+        Data::Bool(false) => "FALSE".to_string(),
+        Data::DateTime(datetime) => datetime.as_f64().to_string(),
+        Data::DateTimeIso(s) | Data::DurationIso(s) => s.clone(),
+        Data::Error(err) => err.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 位置の列が数式のまま保存され、計算結果がキャッシュされているワークブック
+    #[test]
+    fn test_read_tlm_db_sheets() {
+        let mut workbook = open(Path::new("fixtures/TLM_DB/valid.xlsx")).unwrap();
+        assert_eq!(vec!["HK", "MOBC"], workbook.sheet_names());
+        for name in ["HK", "MOBC"] {
+            let records = workbook.records(name).unwrap();
+            let actual = crate::tlm::telemetry::parse(
+                name.to_string(),
+                records.into_iter().map(Ok::<_, csv::Error>),
+            )
+            .unwrap();
+            let calced = std::fs::read(format!(
+                "../tlm-cmd-db/TLM_DB/calced_data/SAMPLE_TLM_DB_{}.csv",
+                name
+            ))
+            .unwrap();
+            let expected = crate::tlm::parse_csv(name.to_string(), calced.as_slice()).unwrap();
+            assert_eq!(expected, actual);
+        }
+    }
+
+    #[test]
+    fn test_read_cmd_db_sheet() {
+        let mut workbook = open(Path::new("../tlm-cmd-db/CMD_DB/SAMPLE_CMD_DB.xlsm")).unwrap();
+        assert!(workbook.sheet_names().iter().any(|name| name == "CMD_DB"));
+        let records = workbook.records("CMD_DB").unwrap();
+        let (component, actual) =
+            crate::cmd::parse(records.into_iter().map(Ok::<_, csv::Error>)).unwrap();
+
+        let csv = std::fs::read("../tlm-cmd-db/CMD_DB/SAMPLE_CMD_DB_CMD_DB.csv").unwrap();
+        let (expected_component, expected) = crate::cmd::parse_csv(csv.as_slice()).unwrap();
+        assert_eq!(expected_component, component);
+        assert_eq!(expected, actual);
+    }
+}
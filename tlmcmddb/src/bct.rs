@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// あるコンポーネントのブロックコマンドテーブル（BCT）の定義のデータベース
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Database {
+    /// この BCT に含まれる [Entry] のリスト
+    pub entries: Vec<Entry>,
+}
+
+impl Database {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE", tag = "type")]
+pub enum Entry {
+    /// ブロックコマンド定義行
+    BlockCommand(BlockCommand),
+    /// コメント行
+    Comment(Comment),
+}
+
+/// ブロックコマンド定義
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockCommand {
+    /// ブロックコマンド名
+    pub name: String,
+    /// 短縮名。エイリアスコマンドの名前に用いられる
+    pub short_name: String,
+    /// ブロックコマンドのID
+    pub bcid: u16,
+    /// 生成するエイリアスコマンド
+    pub aliases: Aliases,
+    pub is_danger: bool,
+    /// ブロックコマンドの説明（衛星運用者向け）
+    pub description: String,
+    /// ブロックコマンドの説明（衛星開発者向け）
+    pub note: String,
+}
+
+/// ブロックコマンドに対して生成するエイリアスコマンドの有無
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Aliases {
+    pub deploy: bool,
+    pub set_block_position: bool,
+    pub clear: bool,
+    pub activate: bool,
+    pub inactivate: bool,
+}
+
+/// コメント行
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Comment {
+    /// コメントの内容
+    pub text: String,
+}
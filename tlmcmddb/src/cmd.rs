@@ -1,5 +1,9 @@
 use serde::{Deserialize, Serialize};
 
+pub mod encode;
+#[cfg(test)]
+pub(crate) mod testing;
+
 /// あるコンポーネントのコマンド定義のデータベース
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Database {
@@ -7,6 +11,16 @@ pub struct Database {
     pub entries: Vec<Entry>,
 }
 
+impl Database {
+    /// [Command] を現れる順に返す
+    pub fn commands(&self) -> impl Iterator<Item = &Command> {
+        self.entries.iter().filter_map(|entry| match entry {
+            Entry::Command(command) => Some(command),
+            Entry::Comment(_) => None,
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE", tag = "type")]
 pub enum Entry {
@@ -74,6 +88,34 @@ pub enum DataType {
     Raw,
 }
 
+impl DataType {
+    /// CMD DB CSV や JSON で用いる型名（`uint8_t` など）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataType::Int8 => "int8_t",
+            DataType::Int16 => "int16_t",
+            DataType::Int32 => "int32_t",
+            DataType::Uint8 => "uint8_t",
+            DataType::Uint16 => "uint16_t",
+            DataType::Uint32 => "uint32_t",
+            DataType::Float => "float",
+            DataType::Double => "double",
+            DataType::Raw => "raw",
+        }
+    }
+
+    /// オクテット幅。raw は可変長のため [None]
+    pub fn octet_width(&self) -> Option<usize> {
+        match self {
+            DataType::Int8 | DataType::Uint8 => Some(1),
+            DataType::Int16 | DataType::Uint16 => Some(2),
+            DataType::Int32 | DataType::Uint32 | DataType::Float => Some(4),
+            DataType::Double => Some(8),
+            DataType::Raw => None,
+        }
+    }
+}
+
 /// コメント行
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Comment {
@@ -0,0 +1,372 @@
+//! [Command] と引数の値からコマンドのパラメータ部（およびパケット）を組み立てる
+
+use anyhow::{anyhow, ensure, Context, Result};
+
+use super::{Command, DataType};
+
+/// コマンドの引数の値
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentValue {
+    /// 整数値。整数型のパラメータに用いる（浮動小数型にも用いることができる）
+    Integer(i64),
+    /// 実数値。浮動小数型のパラメータに用いる
+    Float(f64),
+    /// バイト列。可変長の `raw` パラメータに用いる
+    Raw(Vec<u8>),
+}
+
+/// `command` のパラメータ定義に従って `args` をビッグエンディアンで連結する
+///
+/// `raw` 型のパラメータは可変長であるため、最後のパラメータでなければならない。
+pub fn encode_parameters(command: &Command, args: &[ArgumentValue]) -> Result<Vec<u8>> {
+    ensure!(
+        command.parameters.len() == args.len(),
+        "{} takes {} parameters, but {} arguments were given",
+        command.name,
+        command.parameters.len(),
+        args.len()
+    );
+    let mut payload = vec![];
+    for (i, (parameter, arg)) in command.parameters.iter().zip(args).enumerate() {
+        let is_last = i + 1 == command.parameters.len();
+        encode_parameter(&mut payload, parameter.data_type, arg, is_last)
+            .with_context(|| format!("encoding Param{} of {}", i + 1, command.name))?;
+    }
+    Ok(payload)
+}
+
+fn encode_parameter(
+    payload: &mut Vec<u8>,
+    data_type: DataType,
+    arg: &ArgumentValue,
+    is_last: bool,
+) -> Result<()> {
+    match (data_type, arg) {
+        (DataType::Int8, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<i8>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Int16, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<i16>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Int32, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<i32>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Uint8, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<u8>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Uint16, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<u16>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Uint32, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_integer::<u32>(*v, data_type)?.to_be_bytes())
+        }
+        (DataType::Float, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&checked_float(*v as f64)?.to_be_bytes())
+        }
+        (DataType::Float, ArgumentValue::Float(v)) => {
+            payload.extend_from_slice(&checked_float(*v)?.to_be_bytes())
+        }
+        (DataType::Double, ArgumentValue::Integer(v)) => {
+            payload.extend_from_slice(&(*v as f64).to_be_bytes())
+        }
+        (DataType::Double, ArgumentValue::Float(v)) => {
+            ensure!(v.is_finite(), "double value must be finite, but got {}", v);
+            payload.extend_from_slice(&v.to_be_bytes())
+        }
+        (DataType::Raw, ArgumentValue::Raw(bytes)) => {
+            ensure!(
+                is_last,
+                "raw parameter must be the last parameter because it has variable length"
+            );
+            payload.extend_from_slice(bytes)
+        }
+        (data_type, arg) => {
+            return Err(anyhow!(
+                "argument {:?} does not match the parameter type {:?}",
+                arg,
+                data_type
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn checked_integer<T: TryFrom<i64>>(value: i64, data_type: DataType) -> Result<T> {
+    T::try_from(value).map_err(|_| anyhow!("{} is out of range of {:?}", value, data_type))
+}
+
+fn checked_float(value: f64) -> Result<f32> {
+    ensure!(
+        value.is_finite(),
+        "float value must be finite, but got {}",
+        value
+    );
+    let single = value as f32;
+    ensure!(
+        single.is_finite(),
+        "{} is out of range of single precision float",
+        value
+    );
+    Ok(single)
+}
+
+/*
++----------------------------------------------------------------+-----------------------------------------------------------------------------------+------------+
+|                       Primary Header (6)                       |                             Secondary Header (9)                                  |            |
++-------+-------+---------+--------+----------+-------+----------+-------------+----------+---------+-------------+-------------+--------------------+ Parameters |
+| Ver.  | Type  | SH Flag | APID   | Seq.Flag | Seq.  | Pkt Len. | Ver.        | Cmd Type | Cmd ID  | Dest. Type  | Exec. Type  | Time Indicator     |            |
+| (3)   | (1)=1 | (1)=1   | (11)   | (2)=0b11 | (14)  | (16)     | (8)=1       | (8)      | (16)    | (4)         | (4)         | (32)               |            |
++-------+-------+---------+--------+----------+-------+----------+-------------+----------+---------+-------------+-------------+--------------------+------------+
+*/
+
+/// C2A のコマンド Space Packet のヘッダに設定する値
+///
+/// Cmd ID には [`Command::code`] が用いられる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SpacePacketHeader {
+    /// 11bit
+    pub apid: u16,
+    /// 14bit
+    pub sequence_count: u16,
+    pub command_type: u8,
+    /// 4bit
+    pub destination_type: u8,
+    /// 4bit
+    pub execution_type: u8,
+    pub time_indicator: u32,
+}
+
+const SECONDARY_HEADER_VERSION: u8 = 1;
+
+/// Space Packet の Primary Header のオクテット数
+pub const PRIMARY_HEADER_LEN: usize = 6;
+/// C2A のコマンド Space Packet の Secondary Header のオクテット数
+pub const SECONDARY_HEADER_LEN: usize = 9;
+/// C2A のコマンド Space Packet における Cmd ID（[`Command::code`]、16bit）の先頭のオクテット位置
+pub const CMD_ID_OFFSET: usize = PRIMARY_HEADER_LEN + 2;
+
+/// C2A のコマンド Space Packet のヘッダの1フィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HeaderField {
+    /// 地上局ソフトウェアの定義で用いる名前
+    pub name: &'static str,
+    pub bit_length: usize,
+    /// 既定値。固定のフィールドではその値
+    pub default_value: u64,
+    /// どのパケットでも `default_value` をとるフィールドであれば `true`
+    pub is_fixed: bool,
+    pub description: &'static str,
+}
+
+/// Cmd ID の [HeaderField] の名前
+pub const CMD_ID_FIELD: &str = "CMD_CODE";
+/// Packet Data Length の [HeaderField] の名前
+pub const PACKET_LENGTH_FIELD: &str = "CCSDS_LENGTH";
+
+/// C2A のコマンド Space Packet のヘッダ（Primary Header と Secondary Header）のフィールドを先頭から並べたもの
+pub const HEADER_FIELDS: [HeaderField; 13] = [
+    fixed_header_field("CCSDS_VERSION", 3, 0, "CCSDS primary header version"),
+    fixed_header_field("CCSDS_TYPE", 1, 1, "CCSDS packet type (telecommand)"),
+    fixed_header_field("CCSDS_SH_FLAG", 1, 1, "CCSDS secondary header flag"),
+    header_field("CCSDS_APID", 11, 0, "CCSDS application process ID"),
+    fixed_header_field("CCSDS_SEQ_FLAGS", 2, 3, "CCSDS sequence flags (standalone)"),
+    header_field("CCSDS_SEQ_COUNT", 14, 0, "CCSDS sequence count"),
+    header_field(PACKET_LENGTH_FIELD, 16, 0, "CCSDS packet data length - 1"),
+    fixed_header_field(
+        "SH_VER",
+        8,
+        SECONDARY_HEADER_VERSION as u64,
+        "secondary header version",
+    ),
+    header_field("CMD_TYPE", 8, 0, "command type"),
+    header_field(CMD_ID_FIELD, 16, 0, "command ID"),
+    header_field("DEST_TYPE", 4, 0, "destination type"),
+    header_field("EXEC_TYPE", 4, 0, "execution type"),
+    header_field("TI", 32, 0, "time indicator"),
+];
+
+const fn header_field(
+    name: &'static str,
+    bit_length: usize,
+    default_value: u64,
+    description: &'static str,
+) -> HeaderField {
+    HeaderField {
+        name,
+        bit_length,
+        default_value,
+        is_fixed: false,
+        description,
+    }
+}
+
+const fn fixed_header_field(
+    name: &'static str,
+    bit_length: usize,
+    value: u64,
+    description: &'static str,
+) -> HeaderField {
+    HeaderField {
+        is_fixed: true,
+        ..header_field(name, bit_length, value, description)
+    }
+}
+
+/// `command` のパケットの Packet Data Length（パケットデータ長 - 1）。raw パラメータの長さは数えない
+pub fn packet_data_length(command: &Command) -> usize {
+    let parameters_len: usize = command
+        .parameters
+        .iter()
+        .filter_map(|parameter| parameter.data_type.octet_width())
+        .sum();
+    SECONDARY_HEADER_LEN + parameters_len - 1
+}
+
+/// パラメータ部を組み立て、C2A のコマンド Space Packet として包む
+pub fn encode_space_packet(
+    command: &Command,
+    args: &[ArgumentValue],
+    header: &SpacePacketHeader,
+) -> Result<Vec<u8>> {
+    ensure!(header.apid <= 0x7FF, "APID must be 11 bits");
+    ensure!(
+        header.sequence_count <= 0x3FFF,
+        "sequence count must be 14 bits"
+    );
+    ensure!(
+        header.destination_type <= 0xF,
+        "destination type must be 4 bits"
+    );
+    ensure!(
+        header.execution_type <= 0xF,
+        "execution type must be 4 bits"
+    );
+    let parameters = encode_parameters(command, args)?;
+    let data_len = SECONDARY_HEADER_LEN + parameters.len();
+    let packet_len = u16::try_from(data_len - 1).context("command packet is too long")?;
+
+    let mut packet = Vec::with_capacity(PRIMARY_HEADER_LEN + data_len);
+    // version = 0, type = 1 (telecommand), secondary header flag = 1
+    packet.extend_from_slice(&(0x1800 | header.apid).to_be_bytes());
+    // sequence flags = 0b11 (standalone)
+    packet.extend_from_slice(&(0xC000 | header.sequence_count).to_be_bytes());
+    packet.extend_from_slice(&packet_len.to_be_bytes());
+    packet.push(SECONDARY_HEADER_VERSION);
+    packet.push(header.command_type);
+    packet.extend_from_slice(&command.code.to_be_bytes());
+    packet.push(header.destination_type << 4 | header.execution_type);
+    packet.extend_from_slice(&header.time_indicator.to_be_bytes());
+    packet.extend_from_slice(&parameters);
+    Ok(packet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cmd::Parameter;
+
+    fn command(types: &[DataType]) -> Command {
+        Command {
+            name: "Cmd_TEST".to_string(),
+            target: "OBC".to_string(),
+            code: 0x1234,
+            parameters: types
+                .iter()
+                .map(|&data_type| Parameter {
+                    data_type,
+                    description: String::new(),
+                })
+                .collect(),
+            is_danger: false,
+            is_restricted: false,
+            description: String::new(),
+            note: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_encode_parameters() {
+        let command = command(&[
+            DataType::Int8,
+            DataType::Uint16,
+            DataType::Uint32,
+            DataType::Float,
+            DataType::Double,
+            DataType::Raw,
+        ]);
+        let payload = encode_parameters(
+            &command,
+            &[
+                ArgumentValue::Integer(-1),
+                ArgumentValue::Integer(0xABCD),
+                ArgumentValue::Integer(0xFFFF_FFFF),
+                ArgumentValue::Float(1.5),
+                ArgumentValue::Integer(2),
+                ArgumentValue::Raw(vec![0xDE, 0xAD]),
+            ],
+        )
+        .unwrap();
+        let mut expected = vec![0xFF, 0xAB, 0xCD, 0xFF, 0xFF, 0xFF, 0xFF];
+        expected.extend_from_slice(&1.5f32.to_be_bytes());
+        expected.extend_from_slice(&2.0f64.to_be_bytes());
+        expected.extend_from_slice(&[0xDE, 0xAD]);
+        assert_eq!(expected, payload);
+    }
+
+    #[test]
+    fn test_validation() {
+        let encode = |data_type, arg| encode_parameters(&command(&[data_type]), &[arg]);
+        assert!(encode(DataType::Uint8, ArgumentValue::Integer(256)).is_err());
+        assert!(encode(DataType::Uint8, ArgumentValue::Integer(-1)).is_err());
+        assert!(encode(DataType::Int16, ArgumentValue::Integer(-32769)).is_err());
+        assert!(encode(DataType::Int16, ArgumentValue::Integer(-32768)).is_ok());
+        assert!(encode(DataType::Uint32, ArgumentValue::Float(1.0)).is_err());
+        assert!(encode(DataType::Float, ArgumentValue::Float(f64::NAN)).is_err());
+        assert!(encode(DataType::Float, ArgumentValue::Float(1e300)).is_err());
+        assert!(encode(DataType::Double, ArgumentValue::Float(f64::INFINITY)).is_err());
+
+        let command = command(&[DataType::Raw, DataType::Uint8]);
+        let args = [ArgumentValue::Raw(vec![0]), ArgumentValue::Integer(0)];
+        assert!(encode_parameters(&command, &args).is_err());
+        assert!(encode_parameters(&command, &args[..1]).is_err());
+    }
+
+    #[test]
+    fn test_encode_space_packet() {
+        let command = command(&[DataType::Uint8]);
+        let header = SpacePacketHeader {
+            apid: 0x210,
+            sequence_count: 1,
+            command_type: 0,
+            destination_type: 0,
+            execution_type: 1,
+            time_indicator: 0x0000_0100,
+        };
+        let packet =
+            encode_space_packet(&command, &[ArgumentValue::Integer(0x42)], &header).unwrap();
+        assert_eq!(
+            vec![
+                0x1A, 0x10, 0xC0, 0x01, 0x00, 0x09, 0x01, 0x00, 0x12, 0x34, 0x01, 0x00, 0x00, 0x01,
+                0x00, 0x42
+            ],
+            packet
+        );
+        assert_eq!(
+            command.code.to_be_bytes(),
+            packet[CMD_ID_OFFSET..CMD_ID_OFFSET + 2]
+        );
+        assert_eq!(
+            packet_data_length(&command),
+            u16::from_be_bytes([packet[4], packet[5]]) as usize
+        );
+
+        let cmd_id_position: usize = HEADER_FIELDS
+            .iter()
+            .take_while(|field| field.name != CMD_ID_FIELD)
+            .map(|field| field.bit_length)
+            .sum();
+        assert_eq!(CMD_ID_OFFSET * 8, cmd_id_position);
+        let header_len: usize = HEADER_FIELDS.iter().map(|field| field.bit_length).sum();
+        assert_eq!((PRIMARY_HEADER_LEN + SECONDARY_HEADER_LEN) * 8, header_len);
+    }
+}
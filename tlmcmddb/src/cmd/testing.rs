@@ -0,0 +1,23 @@
+//! テストで用いるコマンド定義の生成
+
+use super::{Command, DataType, Entry, Parameter};
+
+/// ターゲット `OBC` のコマンド
+pub fn command(name: &str, code: u16, data_types: &[DataType], is_danger: bool) -> Entry {
+    Entry::Command(Command {
+        name: name.to_string(),
+        target: "OBC".to_string(),
+        code,
+        parameters: data_types
+            .iter()
+            .map(|&data_type| Parameter {
+                data_type,
+                description: String::new(),
+            })
+            .collect(),
+        is_danger,
+        is_restricted: false,
+        description: String::new(),
+        note: String::new(),
+    })
+}
@@ -0,0 +1,555 @@
+//! 2つの [Database] の構造的な差分
+//!
+//! component・テレメトリ・フィールド・コマンドは名前で対応づける。
+//! 各変更は、配備済みの地上局ソフトウェアと搭載ソフトウェアがそのまま使い続けられるかどうかで分類する。
+
+use std::{collections::HashMap, fmt};
+
+use serde::Serialize;
+
+use crate::{cmd, tlm, Component, Database};
+
+/// 変更の互換性
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Compatibility {
+    /// 既存の定義の解釈は変わらない
+    Compatible,
+    /// 既存の定義の解釈が変わるため、地上局ソフトウェアや搭載ソフトウェアの更新が必要
+    Breaking,
+}
+
+impl fmt::Display for Compatibility {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Compatibility::Compatible => "compatible",
+            Compatibility::Breaking => "breaking",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 変更の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Kind {
+    ComponentAdded,
+    ComponentRemoved,
+    TelemetryAdded,
+    TelemetryRemoved,
+    PacketIdChanged,
+    /// blob テレメトリと struct テレメトリが入れ替わった
+    TelemetryContentChanged,
+    FieldAdded,
+    FieldRemoved,
+    /// [`tlm::FieldExtractionInfo`] が変わった
+    FieldMoved,
+    /// フィールドが所属する [`tlm::FieldGroup`] の変数型が変わった
+    VariableTypeChanged,
+    ConversionChanged,
+    CommandAdded,
+    CommandRemoved,
+    CommandCodeChanged,
+    ParametersChanged,
+    /// Danger Flag か Is Restricted が変わった
+    CommandFlagsChanged,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Kind::ComponentAdded => "component-added",
+            Kind::ComponentRemoved => "component-removed",
+            Kind::TelemetryAdded => "telemetry-added",
+            Kind::TelemetryRemoved => "telemetry-removed",
+            Kind::PacketIdChanged => "packet-id-changed",
+            Kind::TelemetryContentChanged => "telemetry-content-changed",
+            Kind::FieldAdded => "field-added",
+            Kind::FieldRemoved => "field-removed",
+            Kind::FieldMoved => "field-moved",
+            Kind::VariableTypeChanged => "variable-type-changed",
+            Kind::ConversionChanged => "conversion-changed",
+            Kind::CommandAdded => "command-added",
+            Kind::CommandRemoved => "command-removed",
+            Kind::CommandCodeChanged => "command-code-changed",
+            Kind::ParametersChanged => "parameters-changed",
+            Kind::CommandFlagsChanged => "command-flags-changed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 1つの変更
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Change {
+    pub kind: Kind,
+    pub compatibility: Compatibility,
+    /// 変更された箇所。`COMPONENT.TELEMETRY.FIELD` のような形式
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{}] {} {}: {}",
+            self.compatibility, self.kind, self.location, self.message
+        )
+    }
+}
+
+/// `old` から `new` への変更をすべて返す
+pub fn diff(old: &Database, new: &Database) -> Vec<Change> {
+    let mut changes = Changes::default();
+    let (removed, common, added) = match_by_name(&old.components, &new.components, |c| &c.name);
+    for component in removed {
+        changes.push(
+            Kind::ComponentRemoved,
+            Compatibility::Breaking,
+            component.name.clone(),
+            "component is removed".to_string(),
+        );
+    }
+    for (old, new) in common {
+        diff_component(old, new, &mut changes);
+    }
+    for component in added {
+        changes.push(
+            Kind::ComponentAdded,
+            Compatibility::Compatible,
+            component.name.clone(),
+            "component is added".to_string(),
+        );
+    }
+    changes.0
+}
+
+#[derive(Default)]
+struct Changes(Vec<Change>);
+
+impl Changes {
+    fn push(
+        &mut self,
+        kind: Kind,
+        compatibility: Compatibility,
+        location: String,
+        message: String,
+    ) {
+        self.0.push(Change {
+            kind,
+            compatibility,
+            location,
+            message,
+        });
+    }
+}
+
+/// 名前で対応づけ、`old` にのみあるもの、両方にあるもの、`new` にのみあるものに分ける
+#[allow(clippy::type_complexity)]
+fn match_by_name<'a, T>(
+    old: impl IntoIterator<Item = &'a T>,
+    new: impl IntoIterator<Item = &'a T>,
+    name: impl Fn(&T) -> &str,
+) -> (Vec<&'a T>, Vec<(&'a T, &'a T)>, Vec<&'a T>) {
+    let old: Vec<_> = old.into_iter().collect();
+    let new: Vec<_> = new.into_iter().collect();
+    let old_names: HashMap<_, _> = old.iter().map(|&item| (name(item), item)).collect();
+    let new_names: HashMap<_, _> = new.iter().map(|&item| (name(item), item)).collect();
+    let mut removed = vec![];
+    let mut common = vec![];
+    for &item in &old {
+        match new_names.get(name(item)) {
+            Some(&new_item) => common.push((item, new_item)),
+            None => removed.push(item),
+        }
+    }
+    let added = new
+        .into_iter()
+        .filter(|&item| !old_names.contains_key(name(item)))
+        .collect();
+    (removed, common, added)
+}
+
+fn diff_component(old: &Component, new: &Component, changes: &mut Changes) {
+    let (removed, common, added) =
+        match_by_name(&old.tlm.telemetries, &new.tlm.telemetries, |telemetry| {
+            &telemetry.name
+        });
+    for telemetry in removed {
+        changes.push(
+            Kind::TelemetryRemoved,
+            Compatibility::Breaking,
+            format!("{}.{}", old.name, telemetry.name),
+            format!(
+                "telemetry 0x{:02X} is removed",
+                telemetry.metadata.packet_id
+            ),
+        );
+    }
+    for (old_telemetry, new_telemetry) in common {
+        let location = format!("{}.{}", old.name, old_telemetry.name);
+        diff_telemetry(&location, old_telemetry, new_telemetry, changes);
+    }
+    for telemetry in added {
+        changes.push(
+            Kind::TelemetryAdded,
+            Compatibility::Compatible,
+            format!("{}.{}", new.name, telemetry.name),
+            format!("telemetry 0x{:02X} is added", telemetry.metadata.packet_id),
+        );
+    }
+
+    let (removed, common, added) =
+        match_by_name(old.cmd.commands(), new.cmd.commands(), |command| {
+            &command.name
+        });
+    for command in removed {
+        changes.push(
+            Kind::CommandRemoved,
+            Compatibility::Breaking,
+            format!("{}.{}", old.name, command.name),
+            format!("command 0x{:04X} is removed", command.code),
+        );
+    }
+    for (old_command, new_command) in common {
+        let location = format!("{}.{}", old.name, old_command.name);
+        diff_command(&location, old_command, new_command, changes);
+    }
+    for command in added {
+        changes.push(
+            Kind::CommandAdded,
+            Compatibility::Compatible,
+            format!("{}.{}", new.name, command.name),
+            format!("command 0x{:04X} is added", command.code),
+        );
+    }
+}
+
+/// テレメトリの各フィールドと、それが所属する [`tlm::FieldGroup`] の変数型
+fn fields(telemetry: &tlm::Telemetry) -> Vec<(tlm::VariableType, &tlm::Field)> {
+    telemetry
+        .fields()
+        .map(|(field_group, field)| (field_group.onboard_software_info.variable_type, field))
+        .collect()
+}
+
+fn diff_telemetry(
+    location: &str,
+    old: &tlm::Telemetry,
+    new: &tlm::Telemetry,
+    changes: &mut Changes,
+) {
+    if old.metadata.packet_id != new.metadata.packet_id {
+        changes.push(
+            Kind::PacketIdChanged,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "packet ID is changed from 0x{:02X} to 0x{:02X}",
+                old.metadata.packet_id, new.metadata.packet_id
+            ),
+        );
+    }
+    let is_blob = |telemetry: &tlm::Telemetry| matches!(telemetry.content, tlm::Content::Blob);
+    if is_blob(old) != is_blob(new) {
+        let describe =
+            |telemetry: &tlm::Telemetry| if is_blob(telemetry) { "blob" } else { "struct" };
+        changes.push(
+            Kind::TelemetryContentChanged,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "telemetry is changed from {} to {}",
+                describe(old),
+                describe(new)
+            ),
+        );
+        return;
+    }
+
+    let old_fields = fields(old);
+    let new_fields = fields(new);
+    let (removed, common, added) =
+        match_by_name(&old_fields, &new_fields, |(_, field)| &field.name);
+    for (_, field) in removed {
+        changes.push(
+            Kind::FieldRemoved,
+            Compatibility::Breaking,
+            format!("{}.{}", location, field.name),
+            format!(
+                "field at {} is removed",
+                describe_position(&field.extraction_info)
+            ),
+        );
+    }
+    for ((old_type, old_field), (new_type, new_field)) in common {
+        let location = format!("{}.{}", location, old_field.name);
+        diff_field(
+            &location,
+            (*old_type, old_field),
+            (*new_type, new_field),
+            changes,
+        );
+    }
+    for (_, field) in added {
+        changes.push(
+            Kind::FieldAdded,
+            Compatibility::Compatible,
+            format!("{}.{}", location, field.name),
+            format!(
+                "field at {} is added",
+                describe_position(&field.extraction_info)
+            ),
+        );
+    }
+}
+
+fn describe_position(info: &tlm::FieldExtractionInfo) -> String {
+    format!(
+        "octet {} bit {} ({} bits)",
+        info.octet_position, info.bit_position, info.bit_length
+    )
+}
+
+fn diff_field(
+    location: &str,
+    (old_type, old): (tlm::VariableType, &tlm::Field),
+    (new_type, new): (tlm::VariableType, &tlm::Field),
+    changes: &mut Changes,
+) {
+    if old.extraction_info != new.extraction_info {
+        changes.push(
+            Kind::FieldMoved,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "field is moved from {} to {}",
+                describe_position(&old.extraction_info),
+                describe_position(&new.extraction_info)
+            ),
+        );
+    }
+    if old_type != new_type {
+        changes.push(
+            Kind::VariableTypeChanged,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "variable type is changed from {} to {}",
+                old_type.as_str(),
+                new_type.as_str()
+            ),
+        );
+    }
+    if old.conversion_info != new.conversion_info {
+        changes.push(
+            Kind::ConversionChanged,
+            conversion_compatibility(&old.conversion_info, &new.conversion_info),
+            location.to_string(),
+            format!(
+                "conversion is changed from {} to {}",
+                describe_conversion(&old.conversion_info),
+                describe_conversion(&new.conversion_info)
+            ),
+        );
+    }
+}
+
+/// 表示形式のみの変更と、ステータス変換の値の追加は互換とする
+fn conversion_compatibility(old: &tlm::ConversionInfo, new: &tlm::ConversionInfo) -> Compatibility {
+    use tlm::ConversionInfo::*;
+    match (old, new) {
+        (None | Hex, None | Hex) => Compatibility::Compatible,
+        (Status(old), Status(new))
+            if old.default_value == new.default_value
+                && old
+                    .variants
+                    .iter()
+                    .all(|variant| new.variants.contains(variant)) =>
+        {
+            Compatibility::Compatible
+        }
+        _ => Compatibility::Breaking,
+    }
+}
+
+fn describe_conversion(conversion_info: &tlm::ConversionInfo) -> String {
+    match conversion_info {
+        tlm::ConversionInfo::None => "NONE".to_string(),
+        tlm::ConversionInfo::Hex => "HEX".to_string(),
+        tlm::ConversionInfo::Status(status) => {
+            format!("STATUS ({} variants)", status.variants.len())
+        }
+        tlm::ConversionInfo::Polynomial(p) => format!(
+            "POLYNOMIAL ({}, {}, {}, {}, {}, {})",
+            p.a0, p.a1, p.a2, p.a3, p.a4, p.a5
+        ),
+    }
+}
+
+fn diff_command(location: &str, old: &cmd::Command, new: &cmd::Command, changes: &mut Changes) {
+    if old.code != new.code {
+        changes.push(
+            Kind::CommandCodeChanged,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "code is changed from 0x{:04X} to 0x{:04X}",
+                old.code, new.code
+            ),
+        );
+    }
+    let data_types = |command: &cmd::Command| {
+        command
+            .parameters
+            .iter()
+            .map(|parameter| parameter.data_type)
+            .collect::<Vec<_>>()
+    };
+    let (old_types, new_types) = (data_types(old), data_types(new));
+    if old_types != new_types {
+        changes.push(
+            Kind::ParametersChanged,
+            Compatibility::Breaking,
+            location.to_string(),
+            format!(
+                "parameter types are changed from {} to {}",
+                describe_data_types(&old_types),
+                describe_data_types(&new_types)
+            ),
+        );
+    }
+    if (old.is_danger, old.is_restricted) != (new.is_danger, new.is_restricted) {
+        changes.push(
+            Kind::CommandFlagsChanged,
+            Compatibility::Compatible,
+            location.to_string(),
+            format!(
+                "flags are changed from (danger: {}, restricted: {}) to (danger: {}, restricted: {})",
+                old.is_danger, old.is_restricted, new.is_danger, new.is_restricted
+            ),
+        );
+    }
+}
+
+/// `(uint8_t, double)` のように型名を並べる
+fn describe_data_types(data_types: &[cmd::DataType]) -> String {
+    let names: Vec<_> = data_types.iter().map(cmd::DataType::as_str).collect();
+    format!("({})", names.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cmd::testing::command, tlm::testing};
+
+    fn field(
+        name: &str,
+        octet_position: usize,
+        conversion_info: tlm::ConversionInfo,
+    ) -> tlm::Field {
+        tlm::Field {
+            conversion_info,
+            ..testing::field(name, octet_position, 0, 8)
+        }
+    }
+
+    /// 各フィールドを1つずつ uint8_t の [`tlm::FieldGroup`] に収めたテレメトリ
+    fn telemetry(packet_id: u8, fields: Vec<tlm::Field>) -> tlm::Telemetry {
+        let entries = fields
+            .into_iter()
+            .map(|field| testing::group(tlm::VariableType::Uint8, vec![field]))
+            .collect();
+        testing::telemetry("HK", packet_id, entries)
+    }
+
+    fn database(telemetry: tlm::Telemetry, entries: Vec<cmd::Entry>) -> Database {
+        Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![telemetry],
+                },
+                cmd: cmd::Database { entries },
+                bct: Default::default(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        use cmd::DataType::*;
+        let old = database(
+            telemetry(
+                0xf0,
+                vec![
+                    field("A", 0, tlm::ConversionInfo::None),
+                    field("B", 1, tlm::ConversionInfo::None),
+                    field("C", 2, tlm::ConversionInfo::None),
+                ],
+            ),
+            vec![
+                command("Cmd_NOP", 0, &[], false),
+                command("Cmd_SET", 1, &[Uint8], false),
+                command("Cmd_OLD", 2, &[], false),
+            ],
+        );
+        let new = database(
+            telemetry(
+                0xf1,
+                vec![
+                    field("A", 0, tlm::ConversionInfo::Hex),
+                    field("B", 2, tlm::ConversionInfo::None),
+                    field("D", 3, tlm::ConversionInfo::None),
+                ],
+            ),
+            vec![
+                command("Cmd_NOP", 0, &[], false),
+                command("Cmd_SET", 3, &[Uint16], false),
+                command("Cmd_NEW", 4, &[], false),
+            ],
+        );
+        let changes = diff(&old, &new);
+        let messages: HashMap<_, _> = changes
+            .iter()
+            .map(|change| (change.kind, change.message.as_str()))
+            .collect();
+        assert_eq!(
+            "packet ID is changed from 0xF0 to 0xF1",
+            messages[&Kind::PacketIdChanged]
+        );
+        assert_eq!(
+            "parameter types are changed from (uint8_t) to (uint16_t)",
+            messages[&Kind::ParametersChanged]
+        );
+        let changes: Vec<_> = changes
+            .into_iter()
+            .map(|change| (change.kind, change.compatibility, change.location))
+            .collect();
+        use Compatibility::*;
+        assert_eq!(
+            vec![
+                (Kind::PacketIdChanged, Breaking, "MOBC.HK".to_string()),
+                (Kind::FieldRemoved, Breaking, "MOBC.HK.C".to_string()),
+                (Kind::ConversionChanged, Compatible, "MOBC.HK.A".to_string()),
+                (Kind::FieldMoved, Breaking, "MOBC.HK.B".to_string()),
+                (Kind::FieldAdded, Compatible, "MOBC.HK.D".to_string()),
+                (Kind::CommandRemoved, Breaking, "MOBC.Cmd_OLD".to_string()),
+                (
+                    Kind::CommandCodeChanged,
+                    Breaking,
+                    "MOBC.Cmd_SET".to_string()
+                ),
+                (
+                    Kind::ParametersChanged,
+                    Breaking,
+                    "MOBC.Cmd_SET".to_string()
+                ),
+                (Kind::CommandAdded, Compatible, "MOBC.Cmd_NEW".to_string()),
+            ],
+            changes
+        );
+        assert_eq!(Vec::<Change>::new(), diff(&old, &old));
+    }
+}
@@ -0,0 +1,241 @@
+//! [Database] を検索するための索引
+//!
+//! [Database] はコンポーネント・テレメトリ・エントリを [Vec] で保持するため、名前などで探すには線形探索が必要になる。
+//! [Index] はこれらへの参照を [HashMap] にまとめ、定数時間で引けるようにする。
+//!
+//! 同じキーをもつものが複数ある場合は、最初に現れたものを返す。
+//! 重複は [`validate`](crate::validate) で検出できる。
+
+use std::collections::{hash_map, HashMap};
+
+use crate::{cmd, tlm, Component, Database};
+
+/// フィールドと、それを含むコンポーネント・テレメトリ・[`FieldGroup`](tlm::FieldGroup)
+#[derive(Debug, Clone, Copy)]
+pub struct FieldRef<'a> {
+    pub component: &'a Component,
+    pub telemetry: &'a tlm::Telemetry,
+    pub field_group: &'a tlm::FieldGroup,
+    pub field: &'a tlm::Field,
+}
+
+impl FieldRef<'_> {
+    /// `COMPONENT.TLM.FIELD` 形式のパス
+    pub fn path(&self) -> String {
+        format!(
+            "{}.{}.{}",
+            self.component.name, self.telemetry.name, self.field.name
+        )
+    }
+}
+
+/// [Database] の索引
+#[derive(Debug, Clone)]
+pub struct Index<'a> {
+    components: HashMap<&'a str, &'a Component>,
+    commands_by_name: HashMap<(&'a str, &'a str), &'a cmd::Command>,
+    commands_by_code: HashMap<(&'a str, u16), &'a cmd::Command>,
+    telemetries_by_name: HashMap<(&'a str, &'a str), &'a tlm::Telemetry>,
+    telemetries_by_packet_id: HashMap<(&'a str, u8), &'a tlm::Telemetry>,
+    /// すべてのフィールドを現れる順に保持する
+    fields: Vec<FieldRef<'a>>,
+    /// `COMPONENT.TLM.FIELD` 形式のパスから [`Index::fields`] の添字への対応
+    field_paths: HashMap<String, usize>,
+}
+
+/// 既にキーがある場合は上書きしない
+fn insert_first<K: Eq + std::hash::Hash, V>(map: &mut HashMap<K, V>, key: K, value: V) {
+    if let hash_map::Entry::Vacant(vacant) = map.entry(key) {
+        vacant.insert(value);
+    }
+}
+
+impl<'a> Index<'a> {
+    pub fn new(database: &'a Database) -> Self {
+        let mut index = Self {
+            components: HashMap::new(),
+            commands_by_name: HashMap::new(),
+            commands_by_code: HashMap::new(),
+            telemetries_by_name: HashMap::new(),
+            telemetries_by_packet_id: HashMap::new(),
+            fields: vec![],
+            field_paths: HashMap::new(),
+        };
+        for component in &database.components {
+            let name = component.name.as_str();
+            insert_first(&mut index.components, name, component);
+            for command in component.cmd.commands() {
+                insert_first(
+                    &mut index.commands_by_name,
+                    (name, command.name.as_str()),
+                    command,
+                );
+                insert_first(&mut index.commands_by_code, (name, command.code), command);
+            }
+            for telemetry in &component.tlm.telemetries {
+                insert_first(
+                    &mut index.telemetries_by_name,
+                    (name, telemetry.name.as_str()),
+                    telemetry,
+                );
+                let metadata = &telemetry.metadata;
+                insert_first(
+                    &mut index.telemetries_by_packet_id,
+                    (metadata.target.as_str(), metadata.packet_id),
+                    telemetry,
+                );
+                for (field_group, field) in telemetry.fields() {
+                    let field_ref = FieldRef {
+                        component,
+                        telemetry,
+                        field_group,
+                        field,
+                    };
+                    insert_first(&mut index.field_paths, field_ref.path(), index.fields.len());
+                    index.fields.push(field_ref);
+                }
+            }
+        }
+        index
+    }
+
+    pub fn component(&self, name: &str) -> Option<&'a Component> {
+        self.components.get(name).copied()
+    }
+
+    /// コンポーネント名とコマンド名でコマンドを引く
+    pub fn command(&self, component: &str, name: &str) -> Option<&'a cmd::Command> {
+        self.commands_by_name.get(&(component, name)).copied()
+    }
+
+    /// コンポーネント名とコマンドのコードでコマンドを引く
+    pub fn command_by_code(&self, component: &str, code: u16) -> Option<&'a cmd::Command> {
+        self.commands_by_code.get(&(component, code)).copied()
+    }
+
+    /// コンポーネント名とテレメトリ名でテレメトリを引く
+    pub fn telemetry(&self, component: &str, name: &str) -> Option<&'a tlm::Telemetry> {
+        self.telemetries_by_name.get(&(component, name)).copied()
+    }
+
+    /// ターゲットと PacketID でテレメトリを引く
+    pub fn telemetry_by_packet_id(
+        &self,
+        target: &str,
+        packet_id: u8,
+    ) -> Option<&'a tlm::Telemetry> {
+        self.telemetries_by_packet_id
+            .get(&(target, packet_id))
+            .copied()
+    }
+
+    /// `COMPONENT.TLM.FIELD` 形式のパスでフィールドを引く
+    ///
+    /// フィールド名が `.` を含む場合（`PH.VER` など）も、そのまま連結したパスで引ける。
+    pub fn field(&self, path: &str) -> Option<FieldRef<'a>> {
+        self.field_paths.get(path).map(|&i| self.fields[i])
+    }
+
+    /// すべてのフィールドを、コンポーネント・テレメトリ・[`FieldGroup`](tlm::FieldGroup) とともに現れる順に返す
+    pub fn fields(&self) -> impl Iterator<Item = FieldRef<'a>> + '_ {
+        self.fields.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::testing::command,
+        tlm::testing::{field, telemetry},
+    };
+
+    #[test]
+    fn test_index() {
+        let hk = telemetry(
+            "HK",
+            0xf0,
+            vec![
+                tlm::Entry::Comment(tlm::Comment {
+                    text: "**header".to_string(),
+                }),
+                tlm::Entry::FieldGroup(tlm::FieldGroup {
+                    onboard_software_info: tlm::OnboardSoftwareInfo {
+                        variable_type: tlm::VariableType::Uint16,
+                        expression: String::new(),
+                    },
+                    sub_entries: vec![
+                        tlm::SubEntry::Field(field("PH.VER", 0, 0, 8)),
+                        tlm::SubEntry::Comment(tlm::Comment {
+                            text: "*".to_string(),
+                        }),
+                        tlm::SubEntry::Field(field("PH.TYPE", 1, 0, 8)),
+                    ],
+                }),
+            ],
+        );
+        let file_dl = tlm::Telemetry {
+            content: tlm::Content::Blob,
+            ..telemetry("FILE_DL", 0xf1, vec![])
+        };
+        let database = Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![hk, file_dl],
+                },
+                cmd: cmd::Database {
+                    entries: vec![
+                        cmd::Entry::Comment(cmd::Comment {
+                            text: "**".to_string(),
+                        }),
+                        command("Cmd_NOP", 0x0000, &[], false),
+                        command("Cmd_TMGR_SET_TIME", 0x0001, &[], false),
+                    ],
+                },
+                bct: Default::default(),
+            }],
+        };
+        let index = Index::new(&database);
+
+        assert_eq!(
+            Some(0x0001),
+            index
+                .command("MOBC", "Cmd_TMGR_SET_TIME")
+                .map(|command| command.code)
+        );
+        assert_eq!(
+            Some("Cmd_NOP"),
+            index
+                .command_by_code("MOBC", 0x0000)
+                .map(|command| command.name.as_str())
+        );
+        assert!(index.command("AOBC", "Cmd_NOP").is_none());
+
+        assert_eq!(
+            Some(0xf1),
+            index
+                .telemetry("MOBC", "FILE_DL")
+                .map(|telemetry| telemetry.metadata.packet_id)
+        );
+        assert_eq!(
+            Some("HK"),
+            index
+                .telemetry_by_packet_id("OBC", 0xf0)
+                .map(|telemetry| telemetry.name.as_str())
+        );
+
+        let field_ref = index.field("MOBC.HK.PH.TYPE").unwrap();
+        assert_eq!(1, field_ref.field.extraction_info.octet_position);
+        assert_eq!(
+            tlm::VariableType::Uint16,
+            field_ref.field_group.onboard_software_info.variable_type
+        );
+        assert!(index.field("MOBC.HK.PH").is_none());
+
+        assert_eq!(
+            vec!["MOBC.HK.PH.VER", "MOBC.HK.PH.TYPE"],
+            index.fields().map(|f| f.path()).collect::<Vec<_>>()
+        );
+    }
+}
@@ -1,7 +1,11 @@
 use serde::{Deserialize, Serialize};
 
+pub mod bct;
 pub mod cmd;
+pub mod diff;
+pub mod index;
 pub mod tlm;
+pub mod validate;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Database {
@@ -13,4 +17,9 @@ pub struct Component {
     pub name: String,
     pub tlm: tlm::Database,
     pub cmd: cmd::Database,
+    /// ブロックコマンド定義（BCT）。BCT をもたない JSON との互換性のため、空の場合は書き出さない
+    ///
+    /// tlmcmddb-cli の XTCE・COSMOS・Yamcs・リファレンスドキュメントの出力には含まれない。
+    #[serde(default, skip_serializing_if = "bct::Database::is_empty")]
+    pub bct: bct::Database,
 }
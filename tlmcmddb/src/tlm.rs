@@ -1,5 +1,11 @@
 use serde::{Deserialize, Serialize};
 
+pub mod convert;
+pub mod decode;
+pub mod position;
+#[cfg(test)]
+pub(crate) mod testing;
+
 /// あるコンポーネントのテレメトリ定義のデータベース
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Database {
@@ -20,6 +26,39 @@ pub struct Telemetry {
     pub content: Content,
 }
 
+impl Telemetry {
+    /// [FieldGroup] を現れる順に返す。blob の場合は空
+    pub fn field_groups(&self) -> impl Iterator<Item = &FieldGroup> {
+        let entries = match &self.content {
+            Content::Struct(entries) => entries.as_slice(),
+            Content::Blob => &[],
+        };
+        field_groups(entries)
+    }
+
+    /// 各 [Field] を、それが所属する [FieldGroup] とともに現れる順に返す。blob の場合は空
+    pub fn fields(&self) -> impl Iterator<Item = (&FieldGroup, &Field)> {
+        self.field_groups()
+            .flat_map(|field_group| field_group.fields().map(move |field| (field_group, field)))
+    }
+}
+
+/// `entries` に含まれる [FieldGroup] を現れる順に返す
+pub fn field_groups(entries: &[Entry]) -> impl Iterator<Item = &FieldGroup> {
+    entries.iter().filter_map(|entry| match entry {
+        Entry::FieldGroup(field_group) => Some(field_group),
+        Entry::Comment(_) => None,
+    })
+}
+
+/// [`field_groups`] の可変参照版
+pub fn field_groups_mut(entries: &mut [Entry]) -> impl Iterator<Item = &mut FieldGroup> {
+    entries.iter_mut().filter_map(|entry| match entry {
+        Entry::FieldGroup(field_group) => Some(field_group),
+        Entry::Comment(_) => None,
+    })
+}
+
 /// テレメトリ定義のメタデータ
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Metadata {
@@ -62,6 +101,28 @@ pub struct FieldGroup {
     pub sub_entries: Vec<SubEntry>,
 }
 
+impl FieldGroup {
+    /// この [`FieldGroup`] に含まれる [Field] を現れる順に返す
+    pub fn fields(&self) -> impl Iterator<Item = &Field> {
+        self.sub_entries
+            .iter()
+            .filter_map(|sub_entry| match sub_entry {
+                SubEntry::Field(field) => Some(field),
+                SubEntry::Comment(_) => None,
+            })
+    }
+
+    /// [`FieldGroup::fields`] の可変参照版
+    pub fn fields_mut(&mut self) -> impl Iterator<Item = &mut Field> {
+        self.sub_entries
+            .iter_mut()
+            .filter_map(|sub_entry| match sub_entry {
+                SubEntry::Field(field) => Some(field),
+                SubEntry::Comment(_) => None,
+            })
+    }
+}
+
 /// [FieldGroup] 内のエントリ
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE", tag = "type")]
@@ -190,6 +251,20 @@ pub enum VariableType {
 }
 
 impl VariableType {
+    /// TLM DB CSV や JSON で用いる型名（`uint8_t` など）
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VariableType::Int8 => "int8_t",
+            VariableType::Int16 => "int16_t",
+            VariableType::Int32 => "int32_t",
+            VariableType::Uint8 => "uint8_t",
+            VariableType::Uint16 => "uint16_t",
+            VariableType::Uint32 => "uint32_t",
+            VariableType::Float => "float",
+            VariableType::Double => "double",
+        }
+    }
+
     /// オクテット幅
     pub fn octet_width(&self) -> usize {
         match self {
@@ -0,0 +1,224 @@
+//! [ConversionInfo] に従って生値を工学値に変換する
+
+use std::fmt;
+
+use super::{
+    conversion::{Polynomial, Status},
+    decode::{DecodedField, RawValue},
+    ConversionInfo,
+};
+
+/// 工学値
+#[derive(Debug, Clone, PartialEq)]
+pub enum EngineeringValue {
+    /// 変換なし。生値をそのまま工学値とする
+    Raw(RawValue),
+    /// 16進数で表示する値。`bit_length` は表示する桁数の決定に用いる
+    Hex { value: u64, bit_length: usize },
+    /// ステータス変換の結果の文字列
+    Status(String),
+    /// 多項式変換の結果
+    Polynomial(f64),
+}
+
+impl fmt::Display for EngineeringValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineeringValue::Raw(RawValue::Unsigned(value)) => write!(f, "{}", value),
+            EngineeringValue::Raw(RawValue::Signed(value)) => write!(f, "{}", value),
+            EngineeringValue::Raw(RawValue::Float(value)) => write!(f, "{}", value),
+            EngineeringValue::Hex { value, bit_length } => {
+                let digits = (bit_length + 3) / 4;
+                write!(f, "0x{:0digits$X}", value, digits = digits)
+            }
+            EngineeringValue::Status(value) => f.write_str(value),
+            EngineeringValue::Polynomial(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl RawValue {
+    /// 多項式変換などに用いる実数値
+    pub fn as_f64(&self) -> f64 {
+        match *self {
+            RawValue::Unsigned(value) => value as f64,
+            RawValue::Signed(value) => value as f64,
+            RawValue::Float(value) => value,
+        }
+    }
+
+    /// ステータス変換のキーとして用いる整数値。整数として表せない場合は `None`
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            RawValue::Unsigned(value) => i64::try_from(value).ok(),
+            RawValue::Signed(value) => Some(value),
+            RawValue::Float(value) => {
+                if value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64 {
+                    Some(value as i64)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// `bit_length` ビットのビット列としての値
+    fn to_bits(self, bit_length: usize) -> u64 {
+        let mask = if bit_length >= 64 {
+            u64::MAX
+        } else {
+            (1 << bit_length) - 1
+        };
+        match self {
+            RawValue::Unsigned(value) => value & mask,
+            RawValue::Signed(value) => value as u64 & mask,
+            RawValue::Float(value) if bit_length == 32 => (value as f32).to_bits() as u64,
+            RawValue::Float(value) => value.to_bits() & mask,
+        }
+    }
+}
+
+impl Polynomial {
+    /// Σa_i * x^i を計算する
+    pub fn evaluate(&self, x: f64) -> f64 {
+        [self.a5, self.a4, self.a3, self.a2, self.a1, self.a0]
+            .iter()
+            .fold(0.0, |acc, a| acc * x + a)
+    }
+}
+
+impl Status {
+    /// `key` に対応する文字列を返す。`variants` にない場合は `default_value` を返す
+    pub fn lookup(&self, key: i64) -> Option<&str> {
+        self.variants
+            .iter()
+            .find(|variant| variant.key == key)
+            .map(|variant| variant.value.as_str())
+            .or(self.default_value.as_deref())
+    }
+}
+
+impl ConversionInfo {
+    /// 生値 `raw` を工学値に変換する
+    ///
+    /// `bit_length` はフィールドのビット幅で、16進数表示の桁数に用いる。
+    /// ステータス変換で対応する文字列も `default_value` もない場合は、生値をそのまま返す。
+    pub fn convert(&self, raw: RawValue, bit_length: usize) -> EngineeringValue {
+        match self {
+            ConversionInfo::None => EngineeringValue::Raw(raw),
+            ConversionInfo::Hex => EngineeringValue::Hex {
+                value: raw.to_bits(bit_length),
+                bit_length,
+            },
+            ConversionInfo::Status(status) => {
+                let value = match raw.as_i64() {
+                    Some(key) => status.lookup(key),
+                    None => status.default_value.as_deref(),
+                };
+                match value {
+                    Some(value) => EngineeringValue::Status(value.to_string()),
+                    None => EngineeringValue::Raw(raw),
+                }
+            }
+            ConversionInfo::Polynomial(polynomial) => {
+                EngineeringValue::Polynomial(polynomial.evaluate(raw.as_f64()))
+            }
+        }
+    }
+}
+
+impl DecodedField<'_> {
+    /// このフィールドの工学値
+    pub fn engineering_value(&self) -> EngineeringValue {
+        self.field
+            .conversion_info
+            .convert(self.value, self.field.extraction_info.bit_length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlm::conversion::Variant;
+
+    #[test]
+    fn test_polynomial() {
+        let polynomial = ConversionInfo::Polynomial(Polynomial {
+            a0: 1.0,
+            a1: 2.0,
+            a2: 0.5,
+            a3: 0.0,
+            a4: 0.0,
+            a5: 0.0,
+        });
+        assert_eq!(
+            EngineeringValue::Polynomial(1.0 + 2.0 * -4.0 + 0.5 * 16.0),
+            polynomial.convert(RawValue::Signed(-4), 8)
+        );
+    }
+
+    #[test]
+    fn test_status() {
+        let status = Status {
+            variants: vec![
+                Variant {
+                    key: -1,
+                    value: "ERR".to_string(),
+                },
+                Variant {
+                    key: 0,
+                    value: "OK".to_string(),
+                },
+            ],
+            default_value: None,
+        };
+        let conversion = ConversionInfo::Status(status.clone());
+        assert_eq!(
+            EngineeringValue::Status("OK".to_string()),
+            conversion.convert(RawValue::Unsigned(0), 8)
+        );
+        assert_eq!(
+            EngineeringValue::Status("ERR".to_string()),
+            conversion.convert(RawValue::Signed(-1), 8)
+        );
+        assert_eq!(
+            EngineeringValue::Raw(RawValue::Unsigned(2)),
+            conversion.convert(RawValue::Unsigned(2), 8)
+        );
+
+        let conversion = ConversionInfo::Status(Status {
+            default_value: Some("N/A".to_string()),
+            ..status
+        });
+        assert_eq!(
+            EngineeringValue::Status("N/A".to_string()),
+            conversion.convert(RawValue::Unsigned(2), 8)
+        );
+    }
+
+    #[test]
+    fn test_hex() {
+        let value = ConversionInfo::Hex.convert(RawValue::Signed(-2), 12);
+        assert_eq!(
+            EngineeringValue::Hex {
+                value: 0xFFE,
+                bit_length: 12
+            },
+            value
+        );
+        assert_eq!("0xFFE", value.to_string());
+        assert_eq!(
+            "0x0A",
+            ConversionInfo::Hex
+                .convert(RawValue::Unsigned(10), 8)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn test_none() {
+        let value = ConversionInfo::None.convert(RawValue::Float(0.5), 32);
+        assert_eq!(EngineeringValue::Raw(RawValue::Float(0.5)), value);
+        assert_eq!("0.5", value.to_string());
+    }
+}
@@ -0,0 +1,193 @@
+//! テレメトリのオクテット列から各 [Field] の生値を抜き出す
+
+use anyhow::{anyhow, ensure, Context, Result};
+
+use super::{Content, Field, FieldExtractionInfo, Telemetry, VariableType};
+
+/// C2A のテレメトリ Space Packet における SH.TLM_ID の位置と幅（オクテット）
+///
+/// TLM DB に書かれる Octet Pos. と同じく、パケットの先頭から数える。
+pub const TLM_ID_OFFSET: usize = 11;
+pub const TLM_ID_LEN: usize = 1;
+
+/// [Field] から抜き出した生値
+///
+/// 所属する [`FieldGroup`](super::FieldGroup) の [VariableType] に従って解釈される。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawValue {
+    /// 符号なし整数型として解釈された値
+    Unsigned(u64),
+    /// 符号あり整数型として解釈された値（ビット幅に従って符号拡張済み）
+    Signed(i64),
+    /// 浮動小数型として解釈された値
+    Float(f64),
+}
+
+/// デコードされたフィールド
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedField<'a> {
+    pub field: &'a Field,
+    pub variable_type: VariableType,
+    pub value: RawValue,
+}
+
+/// `packet` から `telemetry` に含まれるすべての [Field] の値を抜き出す
+///
+/// フィールドはテレメトリ定義に現れる順に返される。
+pub fn decode<'a>(telemetry: &'a Telemetry, packet: &[u8]) -> Result<Vec<DecodedField<'a>>> {
+    if let Content::Blob = telemetry.content {
+        return Err(anyhow!("blob telemetry {} has no fields", telemetry.name));
+    }
+    let mut fields = vec![];
+    for (field_group, field) in telemetry.fields() {
+        let variable_type = field_group.onboard_software_info.variable_type;
+        let value = decode_field(packet, field, variable_type)
+            .with_context(|| format!("decoding field {}", field.name))?;
+        fields.push(DecodedField {
+            field,
+            variable_type,
+            value,
+        });
+    }
+    Ok(fields)
+}
+
+/// `field` の値を `variable_type` に従って解釈する
+pub fn decode_field(packet: &[u8], field: &Field, variable_type: VariableType) -> Result<RawValue> {
+    let bit_length = field.extraction_info.bit_length;
+    let bits = extract_bits(packet, &field.extraction_info)?;
+    let value = match variable_type {
+        VariableType::Uint8 | VariableType::Uint16 | VariableType::Uint32 => {
+            RawValue::Unsigned(bits)
+        }
+        VariableType::Int8 | VariableType::Int16 | VariableType::Int32 => {
+            // bit_length ビットの2の補数として符号拡張する
+            let shift = 64 - bit_length;
+            RawValue::Signed(((bits << shift) as i64) >> shift)
+        }
+        VariableType::Float => {
+            ensure!(
+                bit_length == 32,
+                "bit length of float field must be 32, but got {}",
+                bit_length
+            );
+            RawValue::Float(f32::from_bits(bits as u32) as f64)
+        }
+        VariableType::Double => {
+            ensure!(
+                bit_length == 64,
+                "bit length of double field must be 64, but got {}",
+                bit_length
+            );
+            RawValue::Float(f64::from_bits(bits))
+        }
+    };
+    Ok(value)
+}
+
+/// `packet` から `extraction_info` で指定されたビット列をビッグエンディアンの符号なし整数として抜き出す
+///
+/// ビット位置は各オクテットの MSB を0として数え、オクテット境界をまたいでもよい。
+pub fn extract_bits(packet: &[u8], extraction_info: &FieldExtractionInfo) -> Result<u64> {
+    let bit_length = extraction_info.bit_length;
+    ensure!(
+        (1..=64).contains(&bit_length),
+        "bit length must be between 1 and 64, but got {}",
+        bit_length
+    );
+    let start = extraction_info.octet_position * 8 + extraction_info.bit_position;
+    let end = start + bit_length;
+    let first_octet = start / 8;
+    let last_octet = (end - 1) / 8;
+    ensure!(
+        last_octet < packet.len(),
+        "packet is too short: field ends at octet {}, but packet length is {}",
+        last_octet,
+        packet.len()
+    );
+    // 高々9オクテットなので u128 に収まる
+    let mut acc: u128 = 0;
+    for octet in &packet[first_octet..=last_octet] {
+        acc = (acc << 8) | *octet as u128;
+    }
+    let trailing = (last_octet + 1) * 8 - end;
+    let mask = (1u128 << bit_length) - 1;
+    Ok(((acc >> trailing) & mask) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlm::testing::{field, group, telemetry};
+
+    #[test]
+    fn test_decode() {
+        let telemetry = telemetry(
+            "TEST",
+            0,
+            vec![
+                group(
+                    VariableType::Uint16,
+                    vec![
+                        field("PH.VER", 0, 0, 3),
+                        field("PH.TYPE", 0, 3, 1),
+                        field("PH.SH_FLAG", 0, 4, 1),
+                        field("PH.APID", 0, 5, 11),
+                    ],
+                ),
+                group(VariableType::Int8, vec![field("I8", 2, 0, 8)]),
+                group(VariableType::Int16, vec![field("I16", 3, 0, 16)]),
+                group(VariableType::Float, vec![field("F32", 5, 0, 32)]),
+                group(VariableType::Double, vec![field("F64", 9, 0, 64)]),
+            ],
+        );
+        let mut packet = vec![0b0001_1101, 0b1010_0101, 0xFF, 0xFF, 0xFE];
+        packet.extend_from_slice(&1.5f32.to_be_bytes());
+        packet.extend_from_slice(&(-0.25f64).to_be_bytes());
+
+        let values: Vec<_> = decode(&telemetry, &packet)
+            .unwrap()
+            .into_iter()
+            .map(|decoded| (decoded.field.name.as_str(), decoded.value))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("PH.VER", RawValue::Unsigned(0)),
+                ("PH.TYPE", RawValue::Unsigned(1)),
+                ("PH.SH_FLAG", RawValue::Unsigned(1)),
+                ("PH.APID", RawValue::Unsigned(0b101_1010_0101)),
+                ("I8", RawValue::Signed(-1)),
+                ("I16", RawValue::Signed(-2)),
+                ("F32", RawValue::Float(1.5)),
+                ("F64", RawValue::Float(-0.25)),
+            ]
+        );
+
+        assert!(decode(&telemetry, &packet[..16]).is_err());
+    }
+
+    #[test]
+    fn test_extract_bits() {
+        let packet = [0xAB, 0xCD, 0xEF];
+        let bits = |octet_position, bit_position, bit_length| {
+            extract_bits(
+                &packet,
+                &field("", octet_position, bit_position, bit_length).extraction_info,
+            )
+        };
+        assert_eq!(0xABCDEF, bits(0, 0, 24).unwrap());
+        assert_eq!(0xBCDE, bits(0, 4, 16).unwrap());
+        assert_eq!(0b1, bits(2, 7, 1).unwrap());
+        assert_eq!(0b1_1110, bits(1, 7, 5).unwrap());
+        assert!(bits(2, 7, 2).is_err());
+        assert!(bits(0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_signed_bit_field() {
+        let packet = [0b0000_0110];
+        let value = decode_field(&packet, &field("", 0, 5, 3), VariableType::Int8).unwrap();
+        assert_eq!(RawValue::Signed(-2), value);
+    }
+}
@@ -0,0 +1,139 @@
+//! [FieldGroup] の並びから各 [Field] のオクテット位置・ビット位置を求める
+//!
+//! TLM DB の xlsm の数式（`=R[-1]C+INT((R[-1]C[1]+R[-1]C[2])/8)`）と同様に、
+//! 各フィールドは [FieldGroup] の区切りによらず直前のフィールドの直後に置かれる。
+//! ビット長の和が変数型の幅に満たない [FieldGroup] は、[`validate`](crate::validate) で別に報告する。
+//! 位置は xlsm と同様に、ビット位置が 0〜7 となるよう正規化する。
+
+use std::fmt;
+
+use super::{field_groups, field_groups_mut, Entry, Field, FieldGroup};
+
+/// フィールドの先頭の位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub octet_position: usize,
+    pub bit_position: usize,
+}
+
+impl Position {
+    fn from_bits(bits: usize) -> Self {
+        Self {
+            octet_position: bits / 8,
+            bit_position: bits % 8,
+        }
+    }
+
+    fn of(field: &Field) -> Self {
+        Self {
+            octet_position: field.extraction_info.octet_position,
+            bit_position: field.extraction_info.bit_position,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "octet {} bit {}", self.octet_position, self.bit_position)
+    }
+}
+
+/// 格納されている位置が計算した位置と食い違っているフィールド
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// フィールドの通し番号（0始まり）。コメントは数えない
+    pub index: usize,
+    pub name: String,
+    pub stored: Position,
+    pub computed: Position,
+}
+
+/// 各フィールドの位置を、現れる順に返す
+pub fn compute(entries: &[Entry]) -> Vec<Position> {
+    let mut positions = vec![];
+    let mut cursor = 0;
+    for field in field_groups(entries).flat_map(FieldGroup::fields) {
+        positions.push(Position::from_bits(cursor));
+        cursor += field.extraction_info.bit_length;
+    }
+    positions
+}
+
+/// 各フィールドの位置を計算した位置で置き換える
+pub fn assign(entries: &mut [Entry]) {
+    let positions = compute(entries);
+    let fields = field_groups_mut(entries).flat_map(FieldGroup::fields_mut);
+    for (field, position) in fields.zip(positions) {
+        field.extraction_info.octet_position = position.octet_position;
+        field.extraction_info.bit_position = position.bit_position;
+    }
+}
+
+/// 格納されている位置が計算した位置と異なるフィールドを返す
+pub fn check(entries: &[Entry]) -> Vec<Mismatch> {
+    let positions = compute(entries);
+    field_groups(entries)
+        .flat_map(FieldGroup::fields)
+        .zip(positions)
+        .enumerate()
+        .filter(|(_, (field, computed))| Position::of(field) != *computed)
+        .map(|(index, (field, computed))| Mismatch {
+            index,
+            name: field.name.clone(),
+            stored: Position::of(field),
+            computed,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tlm::{
+        testing::{field, group},
+        VariableType,
+    };
+
+    #[test]
+    fn test_assign_and_check() {
+        let mut entries = vec![
+            group(
+                VariableType::Uint16,
+                vec![field("VER", 0, 0, 3), field("APID", 0, 3, 13)],
+            ),
+            // ビット長の和が変数型の幅に満たない FieldGroup の後も、xlsm と同様に詰めて並べる
+            group(VariableType::Uint8, vec![field("FLAG", 2, 0, 1)]),
+            // 行を挿入したことで、以降の位置がずれている
+            group(VariableType::Uint32, vec![field("INSERTED", 9, 0, 32)]),
+            group(VariableType::Uint8, vec![field("LAST", 2, 1, 8)]),
+        ];
+        let mismatches = check(&entries);
+        assert_eq!(
+            vec![
+                Mismatch {
+                    index: 3,
+                    name: "INSERTED".to_string(),
+                    stored: Position::from_bits(9 * 8),
+                    computed: Position::from_bits(17),
+                },
+                Mismatch {
+                    index: 4,
+                    name: "LAST".to_string(),
+                    stored: Position::from_bits(17),
+                    computed: Position::from_bits(17 + 32),
+                },
+            ],
+            mismatches
+        );
+
+        assign(&mut entries);
+        assert!(check(&entries).is_empty());
+        assert_eq!(
+            vec![0, 3, 16, 17, 49],
+            compute(&entries)
+                .into_iter()
+                .map(|p| p.octet_position * 8 + p.bit_position)
+                .collect::<Vec<_>>()
+        );
+    }
+}
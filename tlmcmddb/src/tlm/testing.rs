@@ -0,0 +1,49 @@
+//! テストで用いるテレメトリ定義の生成
+
+use super::{
+    Content, ConversionInfo, Entry, Field, FieldExtractionInfo, FieldGroup, Metadata,
+    OnboardSoftwareInfo, SubEntry, Telemetry, VariableType,
+};
+
+/// 変換のない [Field]
+pub fn field(name: &str, octet_position: usize, bit_position: usize, bit_length: usize) -> Field {
+    Field {
+        name: name.to_string(),
+        extraction_info: FieldExtractionInfo {
+            extraction_type: "PACKET".to_string(),
+            octet_position,
+            bit_position,
+            bit_length,
+        },
+        conversion_info: ConversionInfo::None,
+        display_info: None,
+        description: String::new(),
+        note: String::new(),
+    }
+}
+
+/// `fields` からなる [FieldGroup]
+pub fn group(variable_type: VariableType, fields: Vec<Field>) -> Entry {
+    Entry::FieldGroup(FieldGroup {
+        onboard_software_info: OnboardSoftwareInfo {
+            variable_type,
+            expression: String::new(),
+        },
+        sub_entries: fields.into_iter().map(SubEntry::Field).collect(),
+    })
+}
+
+/// ターゲット `OBC` の有効な struct テレメトリ
+pub fn telemetry(name: &str, packet_id: u8, entries: Vec<Entry>) -> Telemetry {
+    Telemetry {
+        name: name.to_string(),
+        metadata: Metadata {
+            target: "OBC".to_string(),
+            packet_id,
+            is_enabled: true,
+            is_restricted: false,
+            local_variables: String::new(),
+        },
+        content: Content::Struct(entries),
+    }
+}
@@ -0,0 +1,324 @@
+//! [Database] の意味的な検査
+//!
+//! CSV パーサはヘッダと各行の構文しか検査しないため、行をまたいだ整合性はここで検査する。
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{bct, cmd, tlm, Component, Database};
+
+/// 検査規則の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Rule {
+    /// テレメトリ内でビットフィールドが重なっている
+    OverlappingBitFields,
+    /// フィールドが所属する [`FieldGroup`](tlm::FieldGroup) の変数型の幅をはみ出している
+    FieldExceedsVariableType,
+    /// 複数のフィールドを含む [`FieldGroup`](tlm::FieldGroup) の変数型が符号なし整数型でない
+    MultiFieldGroupNotUnsigned,
+    /// [`FieldGroup`](tlm::FieldGroup) のフィールドのビット長の和が変数型の幅に満たない
+    ///
+    /// 位置はフィールドを詰めて計算されるが、搭載ソフトウェアは変数型の幅で書き込むため、後続のフィールドと重なる。
+    UnderfilledFieldGroup,
+    /// 同じターゲットで PacketID が重複している
+    DuplicatePacketId,
+    /// 同じターゲットでコマンドのコードが重複している
+    DuplicateCommandCode,
+    /// テレメトリ内でフィールド名が重複している
+    DuplicateFieldName,
+    /// BCT 内で BCID が重複している
+    DuplicateBcid,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Rule::OverlappingBitFields => "overlapping-bit-fields",
+            Rule::FieldExceedsVariableType => "field-exceeds-variable-type",
+            Rule::MultiFieldGroupNotUnsigned => "multi-field-group-not-unsigned",
+            Rule::UnderfilledFieldGroup => "underfilled-field-group",
+            Rule::DuplicatePacketId => "duplicate-packet-id",
+            Rule::DuplicateCommandCode => "duplicate-command-code",
+            Rule::DuplicateFieldName => "duplicate-field-name",
+            Rule::DuplicateBcid => "duplicate-bcid",
+        };
+        f.write_str(name)
+    }
+}
+
+/// 検査で見つかった問題
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: Rule,
+    /// 問題のある箇所。`COMPONENT.TELEMETRY.FIELD` のような形式
+    pub location: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}: {}", self.rule, self.location, self.message)
+    }
+}
+
+/// `database` を検査し、見つかったすべての問題を返す
+pub fn validate(database: &Database) -> Vec<Finding> {
+    let mut findings = vec![];
+    for component in &database.components {
+        validate_component(component, &mut findings);
+    }
+    findings
+}
+
+fn validate_component(component: &Component, findings: &mut Vec<Finding>) {
+    let mut packet_ids: HashMap<(&str, u8), &str> = HashMap::new();
+    for telemetry in &component.tlm.telemetries {
+        let location = format!("{}.{}", component.name, telemetry.name);
+        let metadata = &telemetry.metadata;
+        if let Some(other) =
+            packet_ids.insert((&metadata.target, metadata.packet_id), &telemetry.name)
+        {
+            findings.push(Finding {
+                rule: Rule::DuplicatePacketId,
+                location: location.clone(),
+                message: format!(
+                    "PacketID 0x{:02X} of target {} is also used by {}",
+                    metadata.packet_id, metadata.target, other
+                ),
+            });
+        }
+        if let tlm::Content::Struct(entries) = &telemetry.content {
+            validate_telemetry_entries(&location, entries, findings);
+        }
+    }
+
+    let mut codes: HashMap<(&str, u16), &str> = HashMap::new();
+    for entry in &component.cmd.entries {
+        let cmd::Entry::Command(command) = entry else {
+            continue;
+        };
+        if let Some(other) = codes.insert((&command.target, command.code), &command.name) {
+            findings.push(Finding {
+                rule: Rule::DuplicateCommandCode,
+                location: format!("{}.{}", component.name, command.name),
+                message: format!(
+                    "code 0x{:04X} of target {} is also used by {}",
+                    command.code, command.target, other
+                ),
+            });
+        }
+    }
+
+    let mut bcids: HashMap<u16, &str> = HashMap::new();
+    for entry in &component.bct.entries {
+        let bct::Entry::BlockCommand(block_command) = entry else {
+            continue;
+        };
+        if let Some(other) = bcids.insert(block_command.bcid, &block_command.name) {
+            findings.push(Finding {
+                rule: Rule::DuplicateBcid,
+                location: format!("{}.{}", component.name, block_command.name),
+                message: format!("BCID {} is also used by {}", block_command.bcid, other),
+            });
+        }
+    }
+}
+
+/// フィールドが占めるビット範囲（テレメトリ先頭からのビット位置の半開区間）
+fn bit_range(field: &tlm::Field) -> (usize, usize) {
+    let info = &field.extraction_info;
+    let start = info.octet_position * 8 + info.bit_position;
+    (start, start + info.bit_length)
+}
+
+fn validate_telemetry_entries(location: &str, entries: &[tlm::Entry], findings: &mut Vec<Finding>) {
+    let mut names = HashSet::new();
+    let mut ranges = vec![];
+    for field_group in tlm::field_groups(entries) {
+        let variable_type = field_group.onboard_software_info.variable_type;
+        let fields: Vec<_> = field_group.fields().collect();
+        let Some(first) = fields.first() else {
+            continue;
+        };
+        if fields.len() > 1 && !variable_type.is_unsigned_integer() {
+            findings.push(Finding {
+                rule: Rule::MultiFieldGroupNotUnsigned,
+                location: format!("{}.{}", location, first.name),
+                message: format!(
+                    "field group with {} fields must have an unsigned integer type, but got {:?}",
+                    fields.len(),
+                    variable_type
+                ),
+            });
+        }
+        let bit_length: usize = fields
+            .iter()
+            .map(|field| field.extraction_info.bit_length)
+            .sum();
+        if bit_length < variable_type.bit_width() {
+            findings.push(Finding {
+                rule: Rule::UnderfilledFieldGroup,
+                location: format!("{}.{}", location, first.name),
+                message: format!(
+                    "fields occupy only {} of the {} bits of {:?}",
+                    bit_length,
+                    variable_type.bit_width(),
+                    variable_type
+                ),
+            });
+        }
+        // FieldGroup は先頭のフィールドの位置から変数型の幅だけの領域を占める
+        let (group_start, _) = bit_range(first);
+        let group_end = group_start + variable_type.bit_width();
+        for field in fields {
+            let field_location = format!("{}.{}", location, field.name);
+            if !names.insert(&field.name) {
+                findings.push(Finding {
+                    rule: Rule::DuplicateFieldName,
+                    location: field_location.clone(),
+                    message: "field name is defined more than once".to_string(),
+                });
+            }
+            let (start, end) = bit_range(field);
+            if start < group_start || end > group_end {
+                findings.push(Finding {
+                    rule: Rule::FieldExceedsVariableType,
+                    location: field_location,
+                    message: format!(
+                        "bits {}..{} exceed {:?} of the field group (bits {}..{})",
+                        start, end, variable_type, group_start, group_end
+                    ),
+                });
+            }
+            ranges.push((start, end, &field.name));
+        }
+    }
+
+    ranges.sort();
+    let mut last: Option<(usize, &String)> = None;
+    for (start, end, name) in ranges {
+        match last {
+            Some((last_end, last_name)) if start < last_end => {
+                findings.push(Finding {
+                    rule: Rule::OverlappingBitFields,
+                    location: format!("{}.{}", location, name),
+                    message: format!("bits {}..{} overlap with {}", start, end, last_name),
+                });
+                if end > last_end {
+                    last = Some((end, name));
+                }
+            }
+            _ => last = Some((end, name)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        cmd::testing::command,
+        tlm::testing::{field, group, telemetry},
+    };
+
+    fn block_command(name: &str, bcid: u16) -> bct::Entry {
+        bct::Entry::BlockCommand(bct::BlockCommand {
+            name: name.to_string(),
+            short_name: String::new(),
+            bcid,
+            aliases: Default::default(),
+            is_danger: false,
+            description: String::new(),
+            note: String::new(),
+        })
+    }
+
+    #[test]
+    fn test_valid() {
+        use tlm::VariableType::*;
+        let database = Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![
+                        telemetry(
+                            "HK",
+                            0xf0,
+                            vec![
+                                group(Uint16, vec![field("A", 0, 0, 3), field("B", 0, 3, 13)]),
+                                group(Int8, vec![field("C", 2, 0, 8)]),
+                            ],
+                        ),
+                        telemetry(
+                            "MOBC",
+                            0xf1,
+                            vec![group(Double, vec![field("A", 0, 0, 64)])],
+                        ),
+                    ],
+                },
+                cmd: cmd::Database {
+                    entries: vec![
+                        command("Cmd_NOP", 0, &[], false),
+                        command("Cmd_RESET", 1, &[], false),
+                    ],
+                },
+                bct: Default::default(),
+            }],
+        };
+        assert_eq!(Vec::<Finding>::new(), validate(&database));
+    }
+
+    #[test]
+    fn test_findings() {
+        use tlm::VariableType::*;
+        let database = Database {
+            components: vec![Component {
+                name: "MOBC".to_string(),
+                tlm: tlm::Database {
+                    telemetries: vec![
+                        telemetry(
+                            "HK",
+                            0xf0,
+                            vec![
+                                group(Int16, vec![field("A", 0, 0, 8), field("B", 0, 6, 12)]),
+                                group(Uint8, vec![field("A", 2, 0, 8)]),
+                                group(Uint32, vec![field("C", 3, 0, 24)]),
+                            ],
+                        ),
+                        telemetry("MOBC", 0xf0, vec![]),
+                    ],
+                },
+                cmd: cmd::Database {
+                    entries: vec![
+                        command("Cmd_NOP", 0, &[], false),
+                        command("Cmd_RESET", 0, &[], false),
+                    ],
+                },
+                bct: bct::Database {
+                    entries: vec![block_command("BC_A", 1), block_command("BC_B", 1)],
+                },
+            }],
+        };
+        let mut rules: Vec<_> = validate(&database)
+            .into_iter()
+            .map(|finding| (finding.rule, finding.location))
+            .collect();
+        rules.sort();
+        assert_eq!(
+            vec![
+                (Rule::OverlappingBitFields, "MOBC.HK.A".to_string()),
+                (Rule::OverlappingBitFields, "MOBC.HK.B".to_string()),
+                (Rule::FieldExceedsVariableType, "MOBC.HK.B".to_string()),
+                (Rule::MultiFieldGroupNotUnsigned, "MOBC.HK.A".to_string()),
+                (Rule::UnderfilledFieldGroup, "MOBC.HK.C".to_string()),
+                (Rule::DuplicatePacketId, "MOBC.MOBC".to_string()),
+                (Rule::DuplicateCommandCode, "MOBC.Cmd_RESET".to_string()),
+                (Rule::DuplicateFieldName, "MOBC.HK.A".to_string()),
+                (Rule::DuplicateBcid, "MOBC.BC_B".to_string()),
+            ],
+            rules
+        );
+    }
+}